@@ -0,0 +1,80 @@
+//! PDA seeds, address seeds and protocol limits
+
+pub(crate) const POOL_AUTH_SEED: &[u8] = b"pool_authority";
+
+/// Pool vault PDAs: ["pool_vault", pool_authority, mint]
+pub(crate) const POOL_VAULT_SEED: &[u8] = b"pool_vault";
+
+pub(crate) const SCALAR_BYTE: u8 = 0;
+
+/// Current `SwapPool` layout; pools written before versioning read as version 0
+pub(crate) const SWAP_POOL_VERSION: u8 = 1;
+
+/// StableSwap amplification bounds and ramp limits
+pub(crate) const MIN_AMP: u64 = 1;
+
+pub(crate) const MAX_AMP: u64 = 10_000;
+
+pub(crate) const MAX_AMP_CHANGE: u64 = 10;
+
+pub(crate) const MIN_AMP_RAMP_SECS: i64 = 86_400;
+
+/// Weighted pools use small integer weight ratios, e.g. 4:1 for an 80/20 pool
+pub(crate) const MAX_WEIGHT_SUM: u8 = 5;
+
+/// Q32 fixed-point scale of the encrypted TWAP price accumulators
+pub(crate) const PRICE_SCALE: u128 = 1 << 32;
+
+pub(crate) const OBSERVATIONS_SEED: &[u8] = b"observations";
+
+/// Slots in the compressed price observation ring buffer
+pub(crate) const OBSERVATION_SLOTS: usize = 8;
+
+/// Escrow authority PDA owning the Inco vaults that hold order funds, per token pair
+pub(crate) const ORDER_ESCROW_SEED: &[u8] = b"order_escrow";
+
+pub(crate) const LIMIT_ORDER_SEED: &[u8] = b"limit_order";
+
+pub(crate) const DCA_ORDER_SEED: &[u8] = b"dca_order";
+
+/// Per-order escrow authority PDA for DCA budgets: ["dca_escrow", order_address]
+pub(crate) const DCA_ESCROW_SEED: &[u8] = b"dca_escrow";
+
+pub(crate) const BPS_DENOMINATOR: u128 = 10_000;
+
+pub(crate) const REFERRER_FEES_SEED: &[u8] = b"referrer_fees";
+
+pub(crate) const RESERVE_ATTESTATION_SEED: &[u8] = b"reserve_attestation";
+
+/// Pool registry entries: ["pool_index", mint_a, mint_b, fee_bps]
+pub(crate) const POOL_INDEX_SEED: &[u8] = b"pool_index";
+
+/// SPL wrapping: ["wrap_authority", spl_mint, inco_mint] is the Inco mint's mint authority
+/// and owns the SPL backing vaults; unwrap tickets live at ["unwrap_ticket", owner, nonce]
+pub(crate) const WRAP_AUTHORITY_SEED: &[u8] = b"wrap_authority";
+
+pub(crate) const UNWRAP_TICKET_SEED: &[u8] = b"unwrap_ticket";
+
+/// Minimum time a pool stays paused before the authority can unpause it
+pub(crate) const UNPAUSE_DELAY_SECS: i64 = 3_600;
+
+/// Default time a pool must stay paused before emergency withdrawal opens
+pub(crate) const DEFAULT_EMERGENCY_DELAY_SECS: u32 = 7 * 86_400;
+
+// Batch auctions: intents per batch are bounded by the FHE ops available to settle_batch
+pub(crate) const SWAP_BATCH_SEED: &[u8] = b"swap_batch";
+
+pub(crate) const MAX_BATCH_INTENTS: usize = 8;
+
+/// Remaining accounts supplied per hop ahead of the Light accounts in `swap_route`:
+/// pool_authority, pool_vault_in, pool_vault_out, oracle (read only when the pool has an
+/// oracle price band; any account otherwise)
+pub(crate) const ROUTE_ACCOUNTS_PER_HOP: usize = 4;
+
+pub(crate) const MAX_ROUTE_HOPS: usize = 3;
+
+/// Pending payouts live at ["payout", beneficiary, nonce]. `settle_payout` takes remaining
+/// accounts per executed leg ahead of the Light accounts: authority, source, destination
+pub(crate) const PAYOUT_SEED: &[u8] = b"payout";
+
+pub(crate) const PAYOUT_ACCOUNTS_PER_LEG: usize = 3;
@@ -0,0 +1,688 @@
+//! Encrypted swap math: curve invariants, fees, circuit breaker and TWAP accumulators
+
+use anchor_lang::prelude::*;
+use inco_lightning::cpi::{as_euint128, e_add, e_ge, e_mul, e_select, e_sub, new_euint128};
+use inco_lightning::types::{Ebool, Euint128};
+
+use crate::constants::*;
+use crate::fhe::*;
+use crate::state::*;
+
+/// Encrypted results of a single swap against one pool
+pub(crate) struct SwapUpdates {
+    pub(crate) new_reserve_in: Euint128,
+    pub(crate) new_reserve_out: Euint128,
+    pub(crate) new_protocol_fee: Euint128,
+    /// Input and output amounts after validation (zero if the swap was rejected)
+    pub(crate) amount_in: Euint128,
+    pub(crate) amount_out: Euint128,
+    /// Protocol's part of the fee after validation (zero if the swap was rejected)
+    pub(crate) protocol_fee_amount: Euint128,
+    /// Circuit breaker state after the swap, when the breaker is enabled
+    pub(crate) breaker: Option<BreakerUpdates>,
+}
+
+/// Encrypted circuit breaker limits for the output side of a swap
+pub(crate) struct OutflowLimit {
+    pub(crate) max_trade_out: Euint128,
+    pub(crate) window_cap: Euint128,
+    pub(crate) window_outflow: Euint128,
+    pub(crate) tripped: Euint128,
+    pub(crate) auto_pause: bool,
+}
+
+pub(crate) struct BreakerUpdates {
+    pub(crate) window_outflow: Euint128,
+    pub(crate) tripped: Euint128,
+}
+
+/// Add `price * elapsed` to both encrypted price accumulators, using reserves before the update,
+/// and advance `price_cumulative_ts` to `now`.
+/// Prices are Q32 fixed point: price_a = reserve_b / reserve_a, price_b = reserve_a / reserve_b.
+/// While either reserve is zero both prices accumulate as zero.
+pub(crate) fn accumulate_prices<'info>(
+    inco_program: &AccountInfo<'info>,
+    signer: &AccountInfo<'info>,
+    pool: &mut SwapPool,
+    now: i64,
+) -> Result<()> {
+    let elapsed = now.saturating_sub(pool.price_cumulative_ts);
+    if elapsed <= 0 {
+        return Ok(());
+    }
+    pool.price_cumulative_ts = now;
+
+    let zero = as_euint128(operation(inco_program, signer), 0)?;
+
+    // A reserve is zero exactly when 0 >= reserve
+    let reserve_a_empty: Ebool =
+        e_ge(operation(inco_program, signer), zero, pool.reserve_a, SCALAR_BYTE)?;
+
+    let reserve_b_empty: Ebool =
+        e_ge(operation(inco_program, signer), zero, pool.reserve_b, SCALAR_BYTE)?;
+
+    let weight = as_euint128(operation(inco_program, signer), PRICE_SCALE * elapsed as u128)?;
+
+    let weighted_b = e_mul(operation(inco_program, signer), pool.reserve_b, weight, SCALAR_BYTE)?;
+
+    let price_a_elapsed =
+        e_div(operation(inco_program, signer), weighted_b, pool.reserve_a, SCALAR_BYTE)?;
+
+    let price_a_elapsed = e_select(
+        operation(inco_program, signer),
+        reserve_a_empty,
+        zero,
+        price_a_elapsed,
+        SCALAR_BYTE,
+    )?;
+
+    let price_a_elapsed = e_select(
+        operation(inco_program, signer),
+        reserve_b_empty,
+        zero,
+        price_a_elapsed,
+        SCALAR_BYTE,
+    )?;
+
+    pool.price_cumulative_a = e_add(
+        operation(inco_program, signer),
+        pool.price_cumulative_a,
+        price_a_elapsed,
+        SCALAR_BYTE,
+    )?;
+
+    let weighted_a = e_mul(operation(inco_program, signer), pool.reserve_a, weight, SCALAR_BYTE)?;
+
+    let price_b_elapsed =
+        e_div(operation(inco_program, signer), weighted_a, pool.reserve_b, SCALAR_BYTE)?;
+
+    let price_b_elapsed = e_select(
+        operation(inco_program, signer),
+        reserve_b_empty,
+        zero,
+        price_b_elapsed,
+        SCALAR_BYTE,
+    )?;
+
+    let price_b_elapsed = e_select(
+        operation(inco_program, signer),
+        reserve_a_empty,
+        zero,
+        price_b_elapsed,
+        SCALAR_BYTE,
+    )?;
+
+    pool.price_cumulative_b = e_add(
+        operation(inco_program, signer),
+        pool.price_cumulative_b,
+        price_b_elapsed,
+        SCALAR_BYTE,
+    )?;
+
+    Ok(())
+}
+
+/// Encrypted pool fee on an encrypted input amount: amount_in * fee_bps / 10_000
+pub(crate) fn compute_fee<'info>(
+    inco_program: &AccountInfo<'info>,
+    signer: &AccountInfo<'info>,
+    amount_in: Euint128,
+    fee_bps: u16,
+) -> Result<Euint128> {
+    let fee_rate = as_euint128(operation(inco_program, signer), fee_bps as u128)?;
+
+    let denominator = as_euint128(operation(inco_program, signer), BPS_DENOMINATOR)?;
+
+    let scaled = e_mul(operation(inco_program, signer), amount_in, fee_rate, SCALAR_BYTE)?;
+
+    e_div(operation(inco_program, signer), scaled, denominator, SCALAR_BYTE)
+}
+
+/// Split a fee into (protocol part, LP part) by the protocol share in bps.
+/// The LP part is `None` when the protocol takes the whole fee.
+pub(crate) fn split_fee<'info>(
+    inco_program: &AccountInfo<'info>,
+    signer: &AccountInfo<'info>,
+    fee_amount: Euint128,
+    protocol_fee_share_bps: u16,
+) -> Result<(Euint128, Option<Euint128>)> {
+    if protocol_fee_share_bps as u128 >= BPS_DENOMINATOR {
+        return Ok((fee_amount, None));
+    }
+
+    let protocol_fee_amount = compute_fee(inco_program, signer, fee_amount, protocol_fee_share_bps)?;
+
+    let lp_fee_amount =
+        e_sub(operation(inco_program, signer), fee_amount, protocol_fee_amount, SCALAR_BYTE)?;
+    Ok((protocol_fee_amount, Some(lp_fee_amount)))
+}
+
+/// Keep a swap's pool updates only if `keep` holds; otherwise restore the reserves,
+/// protocol fee and breaker outflow they were computed from
+pub(crate) fn gate_swap_updates<'info>(
+    inco_program: &AccountInfo<'info>,
+    signer: &AccountInfo<'info>,
+    keep: Ebool,
+    pool: &SwapPool,
+    a_to_b: bool,
+    updates: &mut SwapUpdates,
+) -> Result<()> {
+    let (reserve_in, reserve_out, protocol_fee_in) = pool.swap_side(a_to_b);
+
+    updates.new_reserve_in = e_select(
+        operation(inco_program, signer),
+        keep,
+        updates.new_reserve_in,
+        reserve_in,
+        SCALAR_BYTE,
+    )?;
+
+    updates.new_reserve_out = e_select(
+        operation(inco_program, signer),
+        keep,
+        updates.new_reserve_out,
+        reserve_out,
+        SCALAR_BYTE,
+    )?;
+
+    updates.new_protocol_fee = e_select(
+        operation(inco_program, signer),
+        keep,
+        updates.new_protocol_fee,
+        protocol_fee_in,
+        SCALAR_BYTE,
+    )?;
+
+    if let Some(breaker) = updates.breaker.as_mut() {
+        let window_outflow = if a_to_b { pool.window_outflow_b } else { pool.window_outflow_a };
+        breaker.window_outflow = e_select(
+            operation(inco_program, signer),
+            keep,
+            breaker.window_outflow,
+            window_outflow,
+            SCALAR_BYTE,
+        )?;
+    }
+    Ok(())
+}
+
+/// Parse the client ciphertexts of a swap into encrypted handles
+pub(crate) fn parse_swap_amounts<'info>(
+    inco_program: &AccountInfo<'info>,
+    signer: &AccountInfo<'info>,
+    amount_in_ciphertext: &[u8],
+    amount_out_ciphertext: &[u8],
+    fee_amount_ciphertext: &[u8],
+    input_type: u8,
+) -> Result<(Euint128, Euint128, Euint128)> {
+    let amount_in =
+        new_euint128(operation(inco_program, signer), amount_in_ciphertext.to_vec(), input_type)?;
+
+    let amount_out =
+        new_euint128(operation(inco_program, signer), amount_out_ciphertext.to_vec(), input_type)?;
+
+    let fee_amount =
+        new_euint128(operation(inco_program, signer), fee_amount_ciphertext.to_vec(), input_type)?;
+
+    Ok((amount_in, amount_out, fee_amount))
+}
+
+/// Curve used to validate a swap against one pool
+#[derive(Clone, Copy)]
+pub(crate) enum SwapCurve {
+    ConstantProduct,
+    /// Amplification coefficient at execution time, the client-supplied invariant D and
+    /// the reserve down-scaling in bits
+    StableSwap { amp: u64, invariant: Euint128, scale_bits: u8 },
+    /// Integer weights of the input/output side and the reserve down-scaling in bits
+    Weighted { weight_in: u8, weight_out: u8, scale_bits: u8 },
+}
+
+/// Resolve the swap curve of a pool, parsing the client's invariant ciphertext for stable pools
+pub(crate) fn load_swap_curve<'info>(
+    inco_program: &AccountInfo<'info>,
+    signer: &AccountInfo<'info>,
+    pool: &SwapPool,
+    invariant_ciphertext: &[u8],
+    input_type: u8,
+    a_to_b: bool,
+    now: i64,
+) -> Result<SwapCurve> {
+    match pool.pool_kind {
+        PoolKind::ConstantProduct => Ok(SwapCurve::ConstantProduct),
+        PoolKind::Weighted => {
+            let (weight_in, weight_out) = if a_to_b {
+                (pool.weight_a, pool.weight_b)
+            } else {
+                (pool.weight_b, pool.weight_a)
+            };
+            Ok(SwapCurve::Weighted { weight_in, weight_out, scale_bits: pool.curve_scale_bits })
+        }
+        PoolKind::StableSwap => {
+            let invariant = new_euint128(
+                operation(inco_program, signer),
+                invariant_ciphertext.to_vec(),
+                input_type,
+            )?;
+            Ok(SwapCurve::StableSwap {
+                amp: pool.current_amp(now),
+                invariant,
+                scale_bits: pool.curve_scale_bits,
+            })
+        }
+    }
+}
+
+/// Circuit breaker limits for the output side of a swap, rolling the outflow window
+/// over to zero once `breaker_window_secs` have passed. `None` when the breaker is off.
+pub(crate) fn load_outflow_limit<'info>(
+    inco_program: &AccountInfo<'info>,
+    signer: &AccountInfo<'info>,
+    pool: &mut SwapPool,
+    a_to_b: bool,
+    now: i64,
+) -> Result<Option<OutflowLimit>> {
+    if !pool.breaker_enabled {
+        return Ok(None);
+    }
+    if now >= pool.breaker_window_start_ts + pool.breaker_window_secs as i64 {
+        let zero = as_euint128(operation(inco_program, signer), 0)?;
+        pool.window_outflow_a = zero;
+        pool.window_outflow_b = zero;
+        pool.breaker_window_start_ts = now;
+    }
+    let (max_trade_out, window_cap, window_outflow) = if a_to_b {
+        (pool.max_trade_out_b, pool.window_outflow_cap_b, pool.window_outflow_b)
+    } else {
+        (pool.max_trade_out_a, pool.window_outflow_cap_a, pool.window_outflow_a)
+    };
+    Ok(Some(OutflowLimit {
+        max_trade_out,
+        window_cap,
+        window_outflow,
+        tripped: pool.breaker_tripped,
+        auto_pause: pool.breaker_auto_pause,
+    }))
+}
+
+/// Two-coin StableSwap invariant terms at point (x, y) for invariant D, scaled by 4xy:
+/// lhs = 16A * xy * (x + y) + 4D * xy, rhs = 16A * D * xy + D^3
+/// lhs >= rhs exactly when the invariant of (x, y) is at least D.
+pub(crate) fn stable_invariant_terms<'info>(
+    inco_program: &AccountInfo<'info>,
+    signer: &AccountInfo<'info>,
+    x: Euint128,
+    y: Euint128,
+    invariant: Euint128,
+    invariant_cubed: Euint128,
+    amp_16: Euint128,
+    four: Euint128,
+) -> Result<(Euint128, Euint128)> {
+    let xy = e_mul(operation(inco_program, signer), x, y, SCALAR_BYTE)?;
+
+    let sum = e_add(operation(inco_program, signer), x, y, SCALAR_BYTE)?;
+
+    let xy_sum = e_mul(operation(inco_program, signer), xy, sum, SCALAR_BYTE)?;
+
+    let amp_term = e_mul(operation(inco_program, signer), xy_sum, amp_16, SCALAR_BYTE)?;
+
+    let d_xy = e_mul(operation(inco_program, signer), invariant, xy, SCALAR_BYTE)?;
+
+    let d_term = e_mul(operation(inco_program, signer), d_xy, four, SCALAR_BYTE)?;
+
+    let amp_d_term = e_mul(operation(inco_program, signer), d_xy, amp_16, SCALAR_BYTE)?;
+
+    let lhs = e_add(operation(inco_program, signer), amp_term, d_term, SCALAR_BYTE)?;
+
+    let rhs = e_add(operation(inco_program, signer), amp_d_term, invariant_cubed, SCALAR_BYTE)?;
+
+    Ok((lhs, rhs))
+}
+
+/// Largest value S with (4A + 1) * S^3 <= u128::MAX. With x + y <= S and D <= S, every
+/// StableSwap invariant term stays in u128.
+pub(crate) fn stable_reserve_bound(amp: u64) -> u128 {
+    let limit = u128::MAX / (4 * amp as u128 + 1);
+    let (mut low, mut high) = (0u128, 1u128 << 42);
+    while low < high {
+        let mid = (low + high).div_ceil(2);
+        let fits = mid
+            .checked_mul(mid)
+            .and_then(|square| square.checked_mul(mid))
+            .is_some_and(|cube| cube <= limit);
+        if fits {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+    low
+}
+
+/// Validate a StableSwap trade against the client-supplied invariant D:
+/// D(old reserves) <= D <= D(new reserves), so the true invariant never decreases.
+/// D must not exceed the old reserve sum, as for any point on the curve. Reserves and D are
+/// divided by 2^scale_bits first, and the scaled D and reserve sums must stay within
+/// `stable_reserve_bound`, so all terms fit in u128; the truncation lets a trade take up to
+/// about 2^scale_bits base units of output beyond the exact curve.
+pub(crate) fn stable_invariant_checks<'info>(
+    inco_program: &AccountInfo<'info>,
+    signer: &AccountInfo<'info>,
+    amp: u64,
+    invariant: Euint128,
+    scale_bits: u8,
+    reserve_in: Euint128,
+    reserve_out: Euint128,
+    new_reserve_in: Euint128,
+    new_reserve_out: Euint128,
+) -> Result<Vec<Ebool>> {
+    // D <= reserve_in + reserve_out
+    let reserve_sum = e_add(operation(inco_program, signer), reserve_in, reserve_out, SCALAR_BYTE)?;
+
+    let invariant_ok: Ebool =
+        e_ge(operation(inco_program, signer), reserve_sum, invariant, SCALAR_BYTE)?;
+
+    let scale = as_euint128(operation(inco_program, signer), 1u128 << scale_bits)?;
+
+    let mut scaled = Vec::with_capacity(5);
+    for value in [invariant, reserve_in, reserve_out, new_reserve_in, new_reserve_out] {
+        scaled.push(e_div(operation(inco_program, signer), value, scale, SCALAR_BYTE)?);
+    }
+    let [invariant, reserve_in, reserve_out, new_reserve_in, new_reserve_out] = scaled[..] else {
+        unreachable!()
+    };
+
+    // Scaled D and reserve sums within the bound that keeps the terms in u128
+    let bound = as_euint128(operation(inco_program, signer), stable_reserve_bound(amp))?;
+
+    let scaled_invariant_ok: Ebool =
+        e_ge(operation(inco_program, signer), bound, invariant, SCALAR_BYTE)?;
+
+    let old_sum = e_add(operation(inco_program, signer), reserve_in, reserve_out, SCALAR_BYTE)?;
+
+    let old_sum_ok: Ebool = e_ge(operation(inco_program, signer), bound, old_sum, SCALAR_BYTE)?;
+
+    let new_sum =
+        e_add(operation(inco_program, signer), new_reserve_in, new_reserve_out, SCALAR_BYTE)?;
+
+    let new_sum_ok: Ebool = e_ge(operation(inco_program, signer), bound, new_sum, SCALAR_BYTE)?;
+
+    let amp_16 = as_euint128(operation(inco_program, signer), 16 * amp as u128)?;
+
+    let four = as_euint128(operation(inco_program, signer), 4)?;
+
+    let invariant_squared =
+        e_mul(operation(inco_program, signer), invariant, invariant, SCALAR_BYTE)?;
+
+    let invariant_cubed =
+        e_mul(operation(inco_program, signer), invariant_squared, invariant, SCALAR_BYTE)?;
+
+    // Old reserves must lie on or below the curve for D
+    let (old_lhs, old_rhs) = stable_invariant_terms(
+        inco_program,
+        signer,
+        reserve_in,
+        reserve_out,
+        invariant,
+        invariant_cubed,
+        amp_16,
+        four,
+    )?;
+    let old_ok: Ebool = e_ge(operation(inco_program, signer), old_rhs, old_lhs, SCALAR_BYTE)?;
+
+    // New reserves must lie on or above the curve for D
+    let (new_lhs, new_rhs) = stable_invariant_terms(
+        inco_program,
+        signer,
+        new_reserve_in,
+        new_reserve_out,
+        invariant,
+        invariant_cubed,
+        amp_16,
+        four,
+    )?;
+    let new_ok: Ebool = e_ge(operation(inco_program, signer), new_lhs, new_rhs, SCALAR_BYTE)?;
+
+    Ok(vec![invariant_ok, scaled_invariant_ok, old_sum_ok, new_sum_ok, old_ok, new_ok])
+}
+
+/// Weighted invariant x^w_in * y^w_out evaluated on reserves divided by 2^scale_bits
+pub(crate) fn weighted_invariant<'info>(
+    inco_program: &AccountInfo<'info>,
+    signer: &AccountInfo<'info>,
+    reserve_in: Euint128,
+    reserve_out: Euint128,
+    weight_in: u8,
+    weight_out: u8,
+    scale: Euint128,
+) -> Result<Euint128> {
+    let scaled_in = e_div(operation(inco_program, signer), reserve_in, scale, SCALAR_BYTE)?;
+
+    let scaled_out = e_div(operation(inco_program, signer), reserve_out, scale, SCALAR_BYTE)?;
+
+    let term_in = e_pow(inco_program, signer, scaled_in, weight_in)?;
+    let term_out = e_pow(inco_program, signer, scaled_out, weight_out)?;
+
+    e_mul(operation(inco_program, signer), term_in, term_out, SCALAR_BYTE)
+}
+
+/// Validate a weighted-pool trade: (x'/s)^w_in * (y'/s)^w_out >= (x/s)^w_in * (y/s)^w_out
+/// with s = 2^scale_bits. Truncating the reserves to multiples of s lets a trade extract
+/// strictly less than s base units of the output token beyond the exact weighted curve,
+/// and keeps the powers in u128 while each scaled reserve stays below 2^(128 / (w_in + w_out)).
+pub(crate) fn weighted_invariant_check<'info>(
+    inco_program: &AccountInfo<'info>,
+    signer: &AccountInfo<'info>,
+    weight_in: u8,
+    weight_out: u8,
+    scale_bits: u8,
+    reserve_in: Euint128,
+    reserve_out: Euint128,
+    new_reserve_in: Euint128,
+    new_reserve_out: Euint128,
+) -> Result<Ebool> {
+    let scale = as_euint128(operation(inco_program, signer), 1u128 << scale_bits)?;
+
+    let old_invariant = weighted_invariant(
+        inco_program,
+        signer,
+        reserve_in,
+        reserve_out,
+        weight_in,
+        weight_out,
+        scale,
+    )?;
+    let new_invariant = weighted_invariant(
+        inco_program,
+        signer,
+        new_reserve_in,
+        new_reserve_out,
+        weight_in,
+        weight_out,
+        scale,
+    )?;
+
+    e_ge(operation(inco_program, signer), new_invariant, old_invariant, SCALAR_BYTE)
+}
+
+/// Compute encrypted swap updates using Inco Lightning FHE operations.
+/// `amount_in` is the gross input and includes `fee_amount`; the curve is checked on the
+/// input net of the fee, and only the protocol's part of the fee leaves the reserves.
+/// `fee_amount` must be at least `amount_in * fee_bps / 10_000` at the pool's current rate.
+#[inline(never)]
+pub(crate) fn compute_swap_updates<'info>(
+    inco_program: &AccountInfo<'info>,
+    signer: &AccountInfo<'info>,
+    reserve_in: Euint128,
+    reserve_out: Euint128,
+    protocol_fee_in: Euint128,
+    mut amount_in: Euint128,
+    mut amount_out: Euint128,
+    mut fee_amount: Euint128,
+    fee_bps: u16,
+    curve: SwapCurve,
+    protocol_fee_share_bps: u16,
+    outflow_limit: Option<OutflowLimit>,
+) -> Result<SwapUpdates> {
+    let zero = as_euint128(operation(inco_program, signer), 0)?;
+
+    // Check liquidity: reserve_out >= amount_out
+    let has_liquidity: Ebool =
+        e_ge(operation(inco_program, signer), reserve_out, amount_out, SCALAR_BYTE)?;
+
+    // The fee is part of the input: amount_in >= fee_amount
+    let covers_fee: Ebool =
+        e_ge(operation(inco_program, signer), amount_in, fee_amount, SCALAR_BYTE)?;
+
+    // The fee must be at least the pool rate on the gross input: fee_amount >= amount_in * fee_bps / 10_000
+    let min_fee = compute_fee(inco_program, signer, amount_in, fee_bps)?;
+    let fee_ok: Ebool = e_ge(operation(inco_program, signer), fee_amount, min_fee, SCALAR_BYTE)?;
+
+    // Zero out amounts if no liquidity or the fee is outside [rate, amount_in]
+    for amounts_ok in [has_liquidity, covers_fee, fee_ok] {
+        amount_in =
+            e_select(operation(inco_program, signer), amounts_ok, amount_in, zero, SCALAR_BYTE)?;
+
+        amount_out =
+            e_select(operation(inco_program, signer), amounts_ok, amount_out, zero, SCALAR_BYTE)?;
+
+        fee_amount =
+            e_select(operation(inco_program, signer), amounts_ok, fee_amount, zero, SCALAR_BYTE)?;
+    }
+
+    // Calculate new reserves on the input net of the fee
+    let net_amount_in = e_sub(operation(inco_program, signer), amount_in, fee_amount, SCALAR_BYTE)?;
+
+    let temp_reserve_in =
+        e_add(operation(inco_program, signer), reserve_in, net_amount_in, SCALAR_BYTE)?;
+
+    let temp_reserve_out =
+        e_sub(operation(inco_program, signer), reserve_out, amount_out, SCALAR_BYTE)?;
+
+    // Verify the pool curve invariant on the updated reserves
+    let mut invariant_checks = match curve {
+        SwapCurve::ConstantProduct => {
+            // new_k >= old_k
+            let old_k =
+                e_mul(operation(inco_program, signer), reserve_in, reserve_out, SCALAR_BYTE)?;
+
+            let new_k = e_mul(
+                operation(inco_program, signer),
+                temp_reserve_in,
+                temp_reserve_out,
+                SCALAR_BYTE,
+            )?;
+
+            let k_ok: Ebool = e_ge(operation(inco_program, signer), new_k, old_k, SCALAR_BYTE)?;
+            vec![k_ok]
+        }
+        SwapCurve::StableSwap { amp, invariant, scale_bits } => stable_invariant_checks(
+            inco_program,
+            signer,
+            amp,
+            invariant,
+            scale_bits,
+            reserve_in,
+            reserve_out,
+            temp_reserve_in,
+            temp_reserve_out,
+        )?,
+        SwapCurve::Weighted { weight_in, weight_out, scale_bits } => vec![weighted_invariant_check(
+            inco_program,
+            signer,
+            weight_in,
+            weight_out,
+            scale_bits,
+            reserve_in,
+            reserve_out,
+            temp_reserve_in,
+            temp_reserve_out,
+        )?],
+    };
+
+    // Circuit breaker: cap the trade and the window outflow; either violation trips the flag
+    let mut tripped = None;
+    if let Some(limit) = &outflow_limit {
+        let one = as_euint128(operation(inco_program, signer), 1)?;
+
+        let trade_ok: Ebool =
+            e_ge(operation(inco_program, signer), limit.max_trade_out, amount_out, SCALAR_BYTE)?;
+
+        let window_remaining = e_sub(
+            operation(inco_program, signer),
+            limit.window_cap,
+            limit.window_outflow,
+            SCALAR_BYTE,
+        )?;
+
+        let window_ok: Ebool =
+            e_ge(operation(inco_program, signer), window_remaining, amount_out, SCALAR_BYTE)?;
+
+        let flag =
+            e_select(operation(inco_program, signer), trade_ok, limit.tripped, one, SCALAR_BYTE)?;
+
+        tripped =
+            Some(e_select(operation(inco_program, signer), window_ok, flag, one, SCALAR_BYTE)?);
+
+        invariant_checks.push(trade_ok);
+        invariant_checks.push(window_ok);
+        if limit.auto_pause {
+            // Once tripped, every later swap is rejected until the authority resets the breaker
+            let not_tripped: Ebool =
+                e_ge(operation(inco_program, signer), zero, limit.tripped, SCALAR_BYTE)?;
+            invariant_checks.push(not_tripped);
+        }
+    }
+
+    // Zero out if invariant violated
+    for invariant_ok in invariant_checks {
+        amount_in =
+            e_select(operation(inco_program, signer), invariant_ok, amount_in, zero, SCALAR_BYTE)?;
+
+        amount_out =
+            e_select(operation(inco_program, signer), invariant_ok, amount_out, zero, SCALAR_BYTE)?;
+
+        fee_amount =
+            e_select(operation(inco_program, signer), invariant_ok, fee_amount, zero, SCALAR_BYTE)?;
+    }
+
+    // Final reserve calculations: the gross input is credited less the protocol's part
+    // of the fee, so the LP part compounds into reserve_in
+    let (protocol_fee_amount, _) =
+        split_fee(inco_program, signer, fee_amount, protocol_fee_share_bps)?;
+
+    let gross_reserve_in =
+        e_add(operation(inco_program, signer), reserve_in, amount_in, SCALAR_BYTE)?;
+
+    let new_reserve_in =
+        e_sub(operation(inco_program, signer), gross_reserve_in, protocol_fee_amount, SCALAR_BYTE)?;
+
+    let new_reserve_out =
+        e_sub(operation(inco_program, signer), reserve_out, amount_out, SCALAR_BYTE)?;
+
+    let new_protocol_fee =
+        e_add(operation(inco_program, signer), protocol_fee_in, protocol_fee_amount, SCALAR_BYTE)?;
+
+    let breaker = match (outflow_limit, tripped) {
+        (Some(limit), Some(tripped)) => {
+            let window_outflow = e_add(
+                operation(inco_program, signer),
+                limit.window_outflow,
+                amount_out,
+                SCALAR_BYTE,
+            )?;
+            Some(BreakerUpdates { window_outflow, tripped })
+        }
+        _ => None,
+    };
+
+    Ok(SwapUpdates {
+        new_reserve_in,
+        new_reserve_out,
+        new_protocol_fee,
+        amount_in,
+        amount_out,
+        protocol_fee_amount,
+        breaker,
+    })
+}
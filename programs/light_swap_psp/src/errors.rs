@@ -0,0 +1,95 @@
+//! Program error codes
+
+use anchor_lang::prelude::*;
+
+#[error_code]
+pub enum ErrorCode {
+    #[msg("Pool is paused")]
+    PoolPaused,
+    #[msg("Input mint does not match pool")]
+    InvalidInputMint,
+    #[msg("Output mint does not match pool")]
+    InvalidOutputMint,
+    #[msg("Unauthorized - only pool authority can perform this action")]
+    Unauthorized,
+    #[msg("Route hops, pool data and remaining accounts do not line up")]
+    InvalidRoute,
+    #[msg("Pool vault is not owned by the pool authority or has the wrong mint")]
+    InvalidPoolVault,
+    #[msg("Operation is not supported for this pool kind")]
+    InvalidPoolKind,
+    #[msg("Amplification coefficient out of range")]
+    InvalidAmp,
+    #[msg("Amplification ramp too fast or too short")]
+    InvalidAmpRamp,
+    #[msg("Pool weights must be non-zero, sum to at most 5 and use a scale below 128 bits")]
+    InvalidWeights,
+    #[msg("Observation account does not belong to this pool or slot is out of range")]
+    InvalidObservations,
+    #[msg("Flash swaps are disabled for this pool")]
+    FlashSwapsDisabled,
+    #[msg("Invalid flash swap callback program or accounts")]
+    InvalidFlashCallback,
+    #[msg("Escrow vault is not owned by the order escrow authority or has the wrong mint")]
+    InvalidEscrowVault,
+    #[msg("Limit order does not belong to this pool")]
+    InvalidLimitOrder,
+    #[msg("DCA order is invalid or does not belong to this pool")]
+    InvalidDcaOrder,
+    #[msg("DCA order has no executions remaining")]
+    DcaOrderExhausted,
+    #[msg("DCA interval has not elapsed since the last execution")]
+    DcaIntervalNotElapsed,
+    #[msg("Pool is in batch auction mode; queue a swap intent instead")]
+    BatchModeActive,
+    #[msg("Swap batch is invalid or does not belong to this pool")]
+    InvalidSwapBatch,
+    #[msg("Swap batch no longer accepts this operation")]
+    SwapBatchClosed,
+    #[msg("Swap batch window has not closed yet")]
+    SwapBatchOpen,
+    #[msg("Swap batch has reached its intent limit")]
+    SwapBatchFull,
+    #[msg("Referral share must not exceed 10000 bps")]
+    InvalidReferralShare,
+    #[msg("Referrer fee account does not belong to this pool")]
+    InvalidReferrer,
+    #[msg("Protocol fee share must not exceed 10000 bps")]
+    InvalidProtocolFeeShare,
+    #[msg("Dynamic fee bounds must satisfy min <= max <= 10000 with a non-zero half-life")]
+    InvalidDynamicFee,
+    #[msg("Circuit breaker window must be non-zero")]
+    InvalidCircuitBreaker,
+    #[msg("Oracle account is missing, mismatched or malformed")]
+    InvalidOracle,
+    #[msg("Oracle price is stale")]
+    StaleOracle,
+    #[msg("Pool is not paused")]
+    PoolNotPaused,
+    #[msg("Pool cannot be unpaused before the unpause delay has elapsed")]
+    UnpauseTooEarly,
+    #[msg("Emergency delay must be at least the unpause delay")]
+    InvalidEmergencyDelay,
+    #[msg("Pool has not been paused for the emergency delay")]
+    EmergencyDelayNotElapsed,
+    #[msg("Pool reserves and fees are not attested to be empty")]
+    PoolNotEmpty,
+    #[msg("Pool uses the legacy layout; call migrate_pool first")]
+    PoolNeedsMigration,
+    #[msg("Unsupported pool layout version")]
+    UnsupportedPoolVersion,
+    #[msg("Pool is already on the current layout")]
+    PoolAlreadyMigrated,
+    #[msg("Invalid SPL wrapping accounts")]
+    InvalidWrapAccounts,
+    #[msg("Invalid unwrap ticket")]
+    InvalidUnwrapTicket,
+    #[msg("Unwrap burn is not attested to cover the amount")]
+    UnwrapNotAttested,
+    #[msg("Payout accounts or attestations do not match the pending payout")]
+    InvalidPayout,
+    #[msg("Curve scale must be below 128 bits")]
+    InvalidCurveScale,
+    #[msg("Pool index entry does not belong to this pool")]
+    InvalidPoolIndex,
+}
@@ -0,0 +1,28 @@
+//! Events emitted by the program
+
+use anchor_lang::prelude::*;
+
+/// Emitted for every swap executed against a pool's curve; amounts stay encrypted
+#[event]
+pub struct SwapEvent {
+    pub pool_address: [u8; 32],
+    pub a_to_b: bool,
+    /// Fee rate applied to the swap, in bps
+    pub fee_bps: u16,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PoolPausedEvent {
+    pub pool_address: [u8; 32],
+    pub caller: Pubkey,
+    pub by_guardian: bool,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PoolUnpausedEvent {
+    pub pool_address: [u8; 32],
+    pub paused_at_ts: i64,
+    pub timestamp: i64,
+}
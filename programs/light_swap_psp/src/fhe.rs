@@ -0,0 +1,82 @@
+//! Inco Lightning helpers not covered by the `inco_lightning` CPI wrappers
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::solana_program::program::invoke;
+use inco_lightning::cpi::accounts::Operation;
+use inco_lightning::cpi::{as_euint128, e_add, e_ge, e_mul, e_select};
+use inco_lightning::types::{Ebool, Euint128};
+use inco_lightning::ID as INCO_LIGHTNING_ID;
+
+use crate::constants::*;
+
+/// CPI context for an Inco Lightning operation signed by `signer`
+pub(crate) fn operation<'info>(
+    inco_program: &AccountInfo<'info>,
+    signer: &AccountInfo<'info>,
+) -> CpiContext<'static, 'static, 'static, 'info, Operation<'info>> {
+    CpiContext::new(inco_program.clone(), Operation { signer: signer.clone() })
+}
+
+/// Encrypted truncating division `lhs / rhs`.
+/// inco-lightning 0.1.4 ships no CPI wrapper for `e_div`, so the binary-op call is built here
+/// with the same encoding as the crate's other operations.
+pub(crate) fn e_div<'info>(
+    ctx: CpiContext<'_, '_, '_, 'info, Operation<'info>>,
+    lhs: Euint128,
+    rhs: Euint128,
+    scalar_byte: u8,
+) -> Result<Euint128> {
+    let mut data = Vec::with_capacity(8 + 16 + 16 + 1);
+    data.extend_from_slice(&anchor_lang::solana_program::hash::hash(b"global:e_div").to_bytes()[..8]);
+    data.extend_from_slice(&lhs.0.to_le_bytes());
+    data.extend_from_slice(&rhs.0.to_le_bytes());
+    data.push(scalar_byte);
+
+    let ix = Instruction {
+        program_id: INCO_LIGHTNING_ID,
+        accounts: vec![AccountMeta::new(ctx.accounts.signer.key(), true)],
+        data,
+    };
+    invoke(&ix, std::slice::from_ref(&ctx.accounts.signer))?;
+
+    let (_program_id, return_data) = anchor_lang::solana_program::program::get_return_data()
+        .ok_or(ProgramError::InvalidAccountData)?;
+    Euint128::try_from_slice(&return_data).map_err(|_| ProgramError::InvalidAccountData.into())
+}
+
+/// Encrypted AND of flags: counts the true ones and compares against their number
+pub(crate) fn e_all<'info>(
+    inco_program: &AccountInfo<'info>,
+    signer: &AccountInfo<'info>,
+    flags: &[Ebool],
+) -> Result<Ebool> {
+    let zero = as_euint128(operation(inco_program, signer), 0)?;
+
+    let one = as_euint128(operation(inco_program, signer), 1)?;
+
+    let mut count = zero;
+    for flag in flags {
+        let bit = e_select(operation(inco_program, signer), *flag, one, zero, SCALAR_BYTE)?;
+
+        count = e_add(operation(inco_program, signer), count, bit, SCALAR_BYTE)?;
+    }
+
+    let expected = as_euint128(operation(inco_program, signer), flags.len() as u128)?;
+
+    e_ge(operation(inco_program, signer), count, expected, SCALAR_BYTE)
+}
+
+/// Raise an encrypted value to a small plaintext power by repeated multiplication
+pub(crate) fn e_pow<'info>(
+    inco_program: &AccountInfo<'info>,
+    signer: &AccountInfo<'info>,
+    base: Euint128,
+    exponent: u8,
+) -> Result<Euint128> {
+    let mut result = base;
+    for _ in 1..exponent {
+        result = e_mul(operation(inco_program, signer), result, base, SCALAR_BYTE)?;
+    }
+    Ok(result)
+}
@@ -0,0 +1,226 @@
+//! Client interface for the Inco Token program (`idl/inco_token.json`).
+//!
+//! Only the account layouts and instructions this program uses are declared here,
+//! so the crate builds without a checkout of the Inco Token sources.
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::program::invoke_signed;
+use inco_lightning::types::Euint128;
+
+pub const ID: Pubkey = pubkey!("CYVSeUyVzHGVcrxsJt3E8tbaPCQT8ASdRR45g5WxUEW7");
+
+/// Borsh-encoded `Option`, as stored in Inco Token accounts
+#[derive(Clone, Copy, Debug, PartialEq, Eq, AnchorSerialize, AnchorDeserialize)]
+pub enum COption<T> {
+    None,
+    Some(T),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, AnchorSerialize, AnchorDeserialize)]
+pub enum AccountState {
+    Uninitialized,
+    Initialized,
+    Frozen,
+}
+
+/// Encrypted token account, owned by the Inco Token program
+#[derive(Clone, Debug, AnchorSerialize, AnchorDeserialize)]
+pub struct IncoAccount {
+    pub mint: Pubkey,
+    pub owner: Pubkey,
+    pub amount: Euint128,
+    pub delegate: COption<Pubkey>,
+    pub state: AccountState,
+    pub is_native: COption<u64>,
+    pub delegated_amount: Euint128,
+    pub close_authority: COption<Pubkey>,
+}
+
+/// Encrypted token mint, owned by the Inco Token program
+#[derive(Clone, Debug, AnchorSerialize, AnchorDeserialize)]
+pub struct IncoMint {
+    pub mint_authority: COption<Pubkey>,
+    pub supply: Euint128,
+    pub decimals: u8,
+    pub is_initialized: bool,
+    pub freeze_authority: COption<Pubkey>,
+}
+
+macro_rules! inco_token_account {
+    ($name:ident, $discriminator:expr) => {
+        impl Discriminator for $name {
+            const DISCRIMINATOR: &'static [u8] = &$discriminator;
+        }
+
+        impl Owner for $name {
+            fn owner() -> Pubkey {
+                ID
+            }
+        }
+
+        impl AccountSerialize for $name {
+            fn try_serialize<W: std::io::Write>(&self, writer: &mut W) -> Result<()> {
+                writer
+                    .write_all(Self::DISCRIMINATOR)
+                    .map_err(|_| ErrorCode::AccountDidNotSerialize)?;
+                AnchorSerialize::serialize(self, writer)
+                    .map_err(|_| ErrorCode::AccountDidNotSerialize)?;
+                Ok(())
+            }
+        }
+
+        impl AccountDeserialize for $name {
+            fn try_deserialize(buf: &mut &[u8]) -> Result<Self> {
+                if buf.len() < Self::DISCRIMINATOR.len() {
+                    return Err(ErrorCode::AccountDiscriminatorNotFound.into());
+                }
+                if &buf[..Self::DISCRIMINATOR.len()] != Self::DISCRIMINATOR {
+                    return Err(ErrorCode::AccountDiscriminatorMismatch.into());
+                }
+                Self::try_deserialize_unchecked(buf)
+            }
+
+            fn try_deserialize_unchecked(buf: &mut &[u8]) -> Result<Self> {
+                let mut data = &buf[Self::DISCRIMINATOR.len()..];
+                AnchorDeserialize::deserialize(&mut data)
+                    .map_err(|_| ErrorCode::AccountDidNotDeserialize.into())
+            }
+        }
+    };
+}
+
+inco_token_account!(IncoAccount, [18, 233, 131, 18, 230, 173, 249, 89]);
+inco_token_account!(IncoMint, [254, 129, 245, 169, 202, 143, 198, 4]);
+
+pub mod cpi {
+    use super::*;
+
+    pub mod accounts {
+        use anchor_lang::prelude::*;
+        use anchor_lang::solana_program::instruction::AccountMeta;
+
+        /// Accounts of an Inco Token instruction, in IDL order.
+        /// Each entry is (account, is_writable, is_signer).
+        macro_rules! inco_token_accounts {
+            ($name:ident { $($field:ident: $writable:expr, $signer:expr),* $(,)? }) => {
+                #[derive(Clone)]
+                pub struct $name<'info> {
+                    $(pub $field: AccountInfo<'info>,)*
+                }
+
+                impl<'info> ToAccountInfos<'info> for $name<'info> {
+                    fn to_account_infos(&self) -> Vec<AccountInfo<'info>> {
+                        vec![$(self.$field.clone()),*]
+                    }
+                }
+
+                impl<'info> ToAccountMetas for $name<'info> {
+                    fn to_account_metas(&self, _is_signer: Option<bool>) -> Vec<AccountMeta> {
+                        vec![$(
+                            if $writable {
+                                AccountMeta::new(self.$field.key(), $signer)
+                            } else {
+                                AccountMeta::new_readonly(self.$field.key(), $signer)
+                            }
+                        ),*]
+                    }
+                }
+            };
+        }
+
+        inco_token_accounts!(InitializeAccount {
+            account: true, true,
+            mint: false, false,
+            owner: false, false,
+            payer: true, true,
+            system_program: false, false,
+            inco_lightning_program: false, false,
+        });
+
+        inco_token_accounts!(MintTo {
+            mint: true, false,
+            account: true, false,
+            mint_authority: true, true,
+            inco_lightning_program: false, false,
+            system_program: false, false,
+        });
+
+        inco_token_accounts!(IncoTransfer {
+            source: true, false,
+            destination: true, false,
+            authority: true, true,
+            inco_lightning_program: false, false,
+            system_program: false, false,
+        });
+
+        inco_token_accounts!(Burn {
+            account: true, false,
+            mint: true, false,
+            authority: true, true,
+            inco_lightning_program: false, false,
+            system_program: false, false,
+        });
+
+        inco_token_accounts!(CloseAccount {
+            account: true, false,
+            destination: true, false,
+            authority: true, true,
+        });
+    }
+
+    use accounts::{Burn, CloseAccount, IncoTransfer, InitializeAccount, MintTo};
+
+    fn invoke_instruction<'info, T: ToAccountMetas + ToAccountInfos<'info>>(
+        ctx: CpiContext<'_, '_, '_, 'info, T>,
+        discriminator: [u8; 8],
+        args: Option<(Vec<u8>, u8)>,
+    ) -> Result<()> {
+        let mut data = discriminator.to_vec();
+        if let Some((ciphertext, input_type)) = args {
+            (ciphertext, input_type)
+                .serialize(&mut data)
+                .map_err(|_| ErrorCode::InstructionDidNotSerialize)?;
+        }
+        let ix = Instruction {
+            program_id: ID,
+            accounts: ctx.accounts.to_account_metas(None),
+            data,
+        };
+        invoke_signed(&ix, &ctx.to_account_infos(), ctx.signer_seeds).map_err(Into::into)
+    }
+
+    pub fn initialize_account<'info>(
+        ctx: CpiContext<'_, '_, '_, 'info, InitializeAccount<'info>>,
+    ) -> Result<()> {
+        invoke_instruction(ctx, [74, 115, 99, 93, 197, 69, 103, 7], None)
+    }
+
+    pub fn mint_to<'info>(
+        ctx: CpiContext<'_, '_, '_, 'info, MintTo<'info>>,
+        ciphertext: Vec<u8>,
+        input_type: u8,
+    ) -> Result<()> {
+        invoke_instruction(ctx, [241, 34, 48, 186, 37, 179, 123, 192], Some((ciphertext, input_type)))
+    }
+
+    pub fn transfer<'info>(
+        ctx: CpiContext<'_, '_, '_, 'info, IncoTransfer<'info>>,
+        ciphertext: Vec<u8>,
+        input_type: u8,
+    ) -> Result<()> {
+        invoke_instruction(ctx, [163, 52, 200, 231, 140, 3, 69, 186], Some((ciphertext, input_type)))
+    }
+
+    pub fn burn<'info>(
+        ctx: CpiContext<'_, '_, '_, 'info, Burn<'info>>,
+        ciphertext: Vec<u8>,
+        input_type: u8,
+    ) -> Result<()> {
+        invoke_instruction(ctx, [116, 110, 29, 56, 107, 219, 42, 93], Some((ciphertext, input_type)))
+    }
+
+    pub fn close_account<'info>(ctx: CpiContext<'_, '_, '_, 'info, CloseAccount<'info>>) -> Result<()> {
+        invoke_instruction(ctx, [125, 255, 149, 14, 110, 34, 72, 24], None)
+    }
+}
@@ -0,0 +1,184 @@
+//! Single-sided deposits that swap part of the amount into the other side
+
+use anchor_lang::prelude::*;
+use inco_lightning::cpi::{as_euint128, e_add, e_ge, e_select, e_sub, new_euint128};
+use inco_lightning::types::Ebool;
+use light_sdk::{
+    account::LightAccount,
+    cpi::v2::{CpiAccounts, LightSystemProgramCpi},
+    cpi::{InvokeLightSystemProgram, LightCpiInstruction},
+    instruction::ValidityProof as SdkValidityProof,
+};
+
+use super::add_liquidity::AddLiquidity;
+use crate::constants::*;
+use crate::curve::*;
+use crate::errors::ErrorCode;
+use crate::events::*;
+use crate::fhe::*;
+use crate::oracle::*;
+use crate::state::*;
+
+pub fn handle_add_liquidity_single<'info>(
+    ctx: Context<'_, '_, '_, 'info, AddLiquidity<'info>>,
+    proof: SdkValidityProof,
+    pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+    pool_data: Vec<u8>,
+    amount_ciphertext: Vec<u8>,
+    swap_amount_ciphertext: Vec<u8>,
+    swap_out_ciphertext: Vec<u8>,
+    fee_amount_ciphertext: Vec<u8>,
+    invariant_ciphertext: Vec<u8>,
+    input_type: u8,
+    deposit_a: bool,
+) -> Result<()> {
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.fee_payer.as_ref(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let pool_state = SwapPool::load(&pool_data)?;
+    let mut pool_account = LightAccount::<SwapPool>::new_mut(
+        &crate::ID,
+        &pool_meta,
+        pool_state,
+    )?;
+
+    require!(!pool_account.is_paused, ErrorCode::PoolPaused);
+    require_keys_eq!(pool_account.authority, ctx.accounts.authority.key(), ErrorCode::Unauthorized);
+
+    let inco_program = ctx.accounts.inco_lightning_program.to_account_info();
+    let signer = ctx.accounts.fee_payer.to_account_info();
+
+    // Accumulate prices over the elapsed interval before reserves change
+    let now = Clock::get()?.unix_timestamp;
+    accumulate_prices(&inco_program, &signer, &mut pool_account, now)?;
+    let fee_bps = pool_account.effective_fee_bps(now);
+
+    // Parse encrypted amounts
+    let amount =
+        new_euint128(operation(&inco_program, &signer), amount_ciphertext, input_type)?;
+
+    let (mut swap_in, mut swap_out, mut fee_amount) = parse_swap_amounts(
+        &inco_program,
+        &signer,
+        &swap_amount_ciphertext,
+        &swap_out_ciphertext,
+        &fee_amount_ciphertext,
+        input_type,
+    )?;
+
+    // The swapped portion, fee included, must come out of the deposit
+    let within_deposit: Ebool =
+        e_ge(operation(&inco_program, &signer), amount, swap_in, SCALAR_BYTE)?;
+
+    let zero = as_euint128(operation(&inco_program, &signer), 0)?;
+
+    swap_in = e_select(
+        operation(&inco_program, &signer),
+        within_deposit,
+        swap_in,
+        zero,
+        SCALAR_BYTE,
+    )?;
+
+    swap_out = e_select(
+        operation(&inco_program, &signer),
+        within_deposit,
+        swap_out,
+        zero,
+        SCALAR_BYTE,
+    )?;
+
+    fee_amount = e_select(
+        operation(&inco_program, &signer),
+        within_deposit,
+        fee_amount,
+        zero,
+        SCALAR_BYTE,
+    )?;
+
+    if let Some(price_q32) = pool_oracle_price(&pool_account, ctx.accounts.oracle.as_deref(), now)? {
+        enforce_price_band(
+            &inco_program,
+            &signer,
+            price_q32,
+            pool_account.oracle_max_deviation_bps,
+            deposit_a,
+            &mut swap_in,
+            &mut swap_out,
+            &mut fee_amount,
+        )?;
+    }
+
+    // Swap the portion through the curve
+    let (reserve_in, reserve_out, protocol_fee_in) = pool_account.swap_side(deposit_a);
+    let curve = load_swap_curve(
+        &inco_program,
+        &signer,
+        &pool_account,
+        &invariant_ciphertext,
+        input_type,
+        deposit_a,
+        now,
+    )?;
+    let outflow_limit =
+        load_outflow_limit(&inco_program, &signer, &mut pool_account, deposit_a, now)?;
+    let updates = compute_swap_updates(
+        &inco_program,
+        &signer,
+        reserve_in,
+        reserve_out,
+        protocol_fee_in,
+        swap_in,
+        swap_out,
+        fee_amount,
+        fee_bps,
+        curve,
+        pool_account.protocol_fee_share_bps,
+        outflow_limit,
+    )?;
+
+    // Deposit the unswapped remainder and the swap output on top of the post-swap reserves
+    let remaining =
+        e_sub(operation(&inco_program, &signer), amount, updates.amount_in, SCALAR_BYTE)?;
+
+    let new_reserve_in = e_add(
+        operation(&inco_program, &signer),
+        updates.new_reserve_in,
+        remaining,
+        SCALAR_BYTE,
+    )?;
+
+    let new_reserve_out = e_add(
+        operation(&inco_program, &signer),
+        updates.new_reserve_out,
+        updates.amount_out,
+        SCALAR_BYTE,
+    )?;
+
+    pool_account.apply_swap_updates(
+        &SwapUpdates {
+            new_reserve_in,
+            new_reserve_out,
+            ..updates
+        },
+        deposit_a,
+    );
+    emit!(SwapEvent {
+        pool_address: pool_meta.address,
+        a_to_b: deposit_a,
+        fee_bps,
+        timestamp: now,
+    });
+    pool_account.last_update_ts = now;
+    pool_account.bump_state_nonce();
+
+    // Commit pool state update
+    LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
+        .with_light_account(pool_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
@@ -0,0 +1,193 @@
+//! Deposits of plain SPL tokens or SOL wrapped into the pool mint on settlement
+
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Token, TokenAccount};
+use inco_lightning::cpi::{as_euint128, e_add, e_select, new_euint128};
+use inco_lightning::ID as INCO_LIGHTNING_ID;
+use light_sdk::{
+    account::LightAccount,
+    address::v2::derive_address,
+    cpi::v2::{CpiAccounts, LightSystemProgramCpi},
+    cpi::{InvokeLightSystemProgram, LightCpiInstruction},
+    instruction::{
+        PackedAddressTreeInfo, PackedAddressTreeInfoExt, ValidityProof as SdkValidityProof,
+    },
+};
+
+use crate::constants::*;
+use crate::curve::*;
+use crate::errors::ErrorCode;
+use crate::fhe::*;
+use crate::inco_token::{IncoAccount, IncoMint};
+use crate::payout::*;
+use crate::state::*;
+use crate::wrap::*;
+
+#[derive(Accounts)]
+pub struct AddLiquidityWrapped<'info> {
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+    /// Pool authority; pays native SOL deposits and decrypts the payout outcome
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    /// Authority's SPL token account of the deposited mint (wrapped SOL for native
+    /// deposits, which are paid in lamports); a refunded deposit returns here
+    #[account(mut)]
+    pub authority_spl: Account<'info, TokenAccount>,
+    /// SPL backing vault of the wrapped mint (owned by wrap_authority)
+    #[account(mut)]
+    pub spl_backing_vault: Account<'info, TokenAccount>,
+    /// CHECK: Wrap authority PDA, verified against the backing vault and Inco mint
+    pub wrap_authority: AccountInfo<'info>,
+    /// Inco mint of the deposited side, minted into the pool vault when the payout settles
+    pub inco_mint: Account<'info, IncoMint>,
+    /// Pool vault of the deposited side
+    pub pool_vault: Account<'info, IncoAccount>,
+    /// CHECK: Inco allowance PDA for (payout approved handle, authority)
+    #[account(mut)]
+    pub approved_allowance: AccountInfo<'info>,
+    /// CHECK: Inco allowance PDA for (payout refunded handle, authority)
+    #[account(mut)]
+    pub refunded_allowance: AccountInfo<'info>,
+    pub token_program: Program<'info, Token>,
+    /// CHECK: Inco Lightning program for encrypted operations
+    #[account(address = INCO_LIGHTNING_ID)]
+    pub inco_lightning_program: AccountInfo<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handle_add_liquidity_wrapped<'info>(
+    ctx: Context<'_, '_, '_, 'info, AddLiquidityWrapped<'info>>,
+    proof: SdkValidityProof,
+    address_tree_info: PackedAddressTreeInfo,
+    output_tree_index: u8,
+    payout_nonce: u64,
+    pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+    pool_data: Vec<u8>,
+    wrap_amount: u64,
+    amount_ciphertext: Vec<u8>,
+    input_type: u8,
+    deposit_a: bool,
+) -> Result<()> {
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.fee_payer.as_ref(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let pool_state = SwapPool::load(&pool_data)?;
+    let mut pool_account = LightAccount::<SwapPool>::new_mut(
+        &crate::ID,
+        &pool_meta,
+        pool_state,
+    )?;
+
+    require!(!pool_account.is_paused, ErrorCode::PoolPaused);
+    let authority = ctx.accounts.authority.key();
+    require_keys_eq!(pool_account.authority, authority, ErrorCode::Unauthorized);
+
+    let (mint, vault) = if deposit_a {
+        (pool_account.mint_a, pool_account.vault_a)
+    } else {
+        (pool_account.mint_b, pool_account.vault_b)
+    };
+    require_keys_eq!(ctx.accounts.pool_vault.key(), vault, ErrorCode::InvalidPoolVault);
+    require_keys_eq!(ctx.accounts.inco_mint.key(), mint, ErrorCode::InvalidWrapAccounts);
+    let wrap_bump = verify_wrap_authority(
+        ctx.accounts.wrap_authority.key,
+        &ctx.accounts.spl_backing_vault,
+        &ctx.accounts.inco_mint,
+    )?;
+
+    take_wrap_deposit(
+        &ctx.accounts.token_program.to_account_info(),
+        &ctx.accounts.system_program.to_account_info(),
+        &ctx.accounts.authority.to_account_info(),
+        &ctx.accounts.authority_spl,
+        &ctx.accounts.spl_backing_vault,
+        wrap_amount,
+    )?;
+
+    let inco_program = ctx.accounts.inco_lightning_program.to_account_info();
+    let signer = ctx.accounts.fee_payer.to_account_info();
+
+    // Accumulate prices over the elapsed interval before reserves change
+    let now = Clock::get()?.unix_timestamp;
+    accumulate_prices(&inco_program, &signer, &mut pool_account, now)?;
+
+    let amount =
+        new_euint128(operation(&inco_program, &signer), amount_ciphertext.clone(), input_type)?;
+
+    let approved = matches_deposit(&inco_program, &signer, amount, wrap_amount)?;
+    let refunded = refund_due(&inco_program, &signer, approved, amount, amount)?;
+
+    let zero = as_euint128(operation(&inco_program, &signer), 0)?;
+
+    let credited =
+        e_select(operation(&inco_program, &signer), approved, amount, zero, SCALAR_BYTE)?;
+
+    // Add to reserves
+    let cpi_ctx = operation(&inco_program, &signer);
+    if deposit_a {
+        pool_account.reserve_a = e_add(cpi_ctx, pool_account.reserve_a, credited, SCALAR_BYTE)?;
+    } else {
+        pool_account.reserve_b = e_add(cpi_ctx, pool_account.reserve_b, credited, SCALAR_BYTE)?;
+    }
+    pool_account.last_update_ts = now;
+    pool_account.bump_state_nonce();
+
+    let (mint_leg, refund_leg) = wrap_deposit_legs(
+        wrap_bump,
+        &ctx.accounts.spl_backing_vault,
+        mint,
+        vault,
+        ctx.accounts.authority_spl.key(),
+        amount_ciphertext,
+        wrap_amount,
+    );
+
+    // The authority decrypts the outcome to settle the payout
+    allow_payout_outcome(
+        &inco_program,
+        &signer,
+        &ctx.accounts.system_program.to_account_info(),
+        &ctx.accounts.authority.to_account_info(),
+        &[
+            (approved, ctx.accounts.approved_allowance.to_account_info()),
+            (refunded, ctx.accounts.refunded_allowance.to_account_info()),
+        ],
+    )?;
+
+    let tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|error| ProgramError::Custom(error.into()))?;
+    let (address, address_seed) = derive_address(
+        &[PAYOUT_SEED, authority.as_ref(), &payout_nonce.to_le_bytes()],
+        &tree_pubkey,
+        &crate::ID,
+    );
+    let new_address_params =
+        address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(1));
+
+    let mut payout_account = LightAccount::<PendingPayout>::new_init(
+        &crate::ID,
+        Some(address),
+        output_tree_index,
+    );
+    payout_account.beneficiary = authority;
+    payout_account.input_type = input_type;
+    payout_account.approved = approved;
+    payout_account.on_approved = vec![mint_leg];
+    payout_account.refunded = refunded;
+    payout_account.on_refunded = vec![refund_leg];
+    payout_account.created_at = now;
+
+    // Commit pool state update and the payout
+    LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
+        .with_light_account(pool_account)?
+        .with_light_account(payout_account)?
+        .with_new_addresses(&[new_address_params])
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
@@ -0,0 +1,837 @@
+//! Batch auctions: queued swap intents settled at a single clearing price
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::AccountMeta;
+use inco_lightning::cpi::{as_euint128, e_add, e_ge, e_mul, e_select, e_sub, new_euint128};
+use inco_lightning::types::{Ebool, Euint128};
+use inco_lightning::ID as INCO_LIGHTNING_ID;
+use light_sdk::{
+    account::LightAccount,
+    address::v2::derive_address,
+    cpi::v2::{CpiAccounts, LightSystemProgramCpi},
+    cpi::{InvokeLightSystemProgram, LightCpiInstruction},
+    instruction::{
+        PackedAddressTreeInfo, PackedAddressTreeInfoExt, ValidityProof as SdkValidityProof,
+    },
+};
+
+use super::update_pool_config::UpdatePoolConfig;
+use crate::constants::*;
+use crate::curve::*;
+use crate::errors::ErrorCode;
+use crate::fhe::*;
+use crate::inco_token::cpi::accounts::IncoTransfer;
+use crate::inco_token::cpi::transfer as inco_token_transfer;
+use crate::inco_token::{IncoAccount, ID as INCO_TOKEN_ID};
+use crate::oracle::*;
+use crate::payout::*;
+use crate::state::*;
+use crate::vault::*;
+
+#[derive(Accounts)]
+pub struct OpenSwapBatch<'info> {
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+    /// CHECK: Inco Lightning program for encrypted operations
+    #[account(address = INCO_LIGHTNING_ID)]
+    pub inco_lightning_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct QueueSwapIntent<'info> {
+    /// Pays Light and Inco fees; may be a relayer distinct from the trader
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+    /// Owner or approved delegate of the trader's input token account
+    pub trader: Signer<'info>,
+    /// Trader's Inco token account for the input mint
+    #[account(mut)]
+    pub trader_token_in: Account<'info, IncoAccount>,
+    /// Trader's Inco token account credited with the output at settlement; the refund of
+    /// an unfilled intent goes back to `trader_token_in`
+    pub trader_token_out: Account<'info, IncoAccount>,
+    /// Pool vault receiving the input (owned by pool_authority)
+    #[account(mut)]
+    pub pool_vault_in: Account<'info, IncoAccount>,
+    /// CHECK: Inco Lightning program for encrypted operations
+    #[account(address = INCO_LIGHTNING_ID)]
+    pub inco_lightning_program: AccountInfo<'info>,
+    /// CHECK: Inco Token program for token transfers
+    #[account(address = INCO_TOKEN_ID)]
+    pub inco_token_program: AccountInfo<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SettleBatch<'info> {
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+    /// CHECK: Price feed account, verified against the pool's configured oracle; only
+    /// required when the pool has an oracle price band
+    pub oracle: Option<UncheckedAccount<'info>>,
+    /// CHECK: Inco Lightning program for encrypted operations
+    #[account(address = INCO_LIGHTNING_ID)]
+    pub inco_lightning_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimBatchOutput<'info> {
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+    /// Trader recorded on the intent
+    pub trader: Signer<'info>,
+    /// Pool vault paying the claim (owned by pool_authority)
+    pub pool_vault: Account<'info, IncoAccount>,
+    /// Intent token account receiving the claim: `trader_token_out` for the output,
+    /// `trader_token_in` for the refund
+    pub trader_token: Account<'info, IncoAccount>,
+    /// CHECK: Inco allowance PDA for (payout approved handle, trader)
+    #[account(mut)]
+    pub approved_allowance: AccountInfo<'info>,
+    /// CHECK: Inco Lightning program for encrypted operations
+    #[account(address = INCO_LIGHTNING_ID)]
+    pub inco_lightning_program: AccountInfo<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handle_set_batch_mode<'info>(
+    ctx: Context<'_, '_, '_, 'info, UpdatePoolConfig<'info>>,
+    proof: SdkValidityProof,
+    pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+    pool_data: Vec<u8>,
+    enabled: bool,
+    window_secs: u32,
+) -> Result<()> {
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.fee_payer.as_ref(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let pool_state = SwapPool::load(&pool_data)?;
+    let mut pool_account = LightAccount::<SwapPool>::new_mut(
+        &crate::ID,
+        &pool_meta,
+        pool_state,
+    )?;
+
+    require_keys_eq!(pool_account.authority, ctx.accounts.authority.key(), ErrorCode::Unauthorized);
+    if enabled {
+        require!(pool_account.pool_kind == PoolKind::ConstantProduct, ErrorCode::InvalidPoolKind);
+        require!(window_secs > 0, ErrorCode::InvalidSwapBatch);
+    }
+    pool_account.batch_mode = enabled;
+    pool_account.batch_window_secs = window_secs;
+
+    // Commit pool state update
+    LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
+        .with_light_account(pool_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn handle_open_swap_batch<'info>(
+    ctx: Context<'_, '_, '_, 'info, OpenSwapBatch<'info>>,
+    proof: SdkValidityProof,
+    address_tree_info: PackedAddressTreeInfo,
+    output_tree_index: u8,
+    pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+    pool_data: Vec<u8>,
+) -> Result<()> {
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.fee_payer.as_ref(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let pool_state = SwapPool::load(&pool_data)?;
+    let mut pool_account = LightAccount::<SwapPool>::new_mut(
+        &crate::ID,
+        &pool_meta,
+        pool_state,
+    )?;
+
+    require!(!pool_account.is_paused, ErrorCode::PoolPaused);
+    require!(pool_account.batch_mode, ErrorCode::InvalidSwapBatch);
+
+    let tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|error| ProgramError::Custom(error.into()))?;
+    let batch_id = pool_account.next_batch_id;
+    let (address, address_seed) = derive_address(
+        &[SWAP_BATCH_SEED, pool_meta.address.as_ref(), &batch_id.to_le_bytes()],
+        &tree_pubkey,
+        &crate::ID,
+    );
+    let new_address_params =
+        address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0));
+
+    let inco_program = ctx.accounts.inco_lightning_program.to_account_info();
+    let signer = ctx.accounts.fee_payer.to_account_info();
+
+    let mut batch_account = LightAccount::<SwapBatch>::new_init(
+        &crate::ID,
+        Some(address),
+        output_tree_index,
+    );
+
+    batch_account.total_in_a = as_euint128(operation(&inco_program, &signer), 0)?;
+
+    batch_account.total_in_b = as_euint128(operation(&inco_program, &signer), 0)?;
+
+    batch_account.pool_address = pool_meta.address;
+    batch_account.mint_a = pool_account.mint_a;
+    batch_account.mint_b = pool_account.mint_b;
+    batch_account.vault_a = pool_account.vault_a;
+    batch_account.vault_b = pool_account.vault_b;
+    batch_account.pool_authority = pool_account.pool_authority;
+    batch_account.pool_authority_bump = pool_account.pool_authority_bump;
+    batch_account.batch_id = batch_id;
+    batch_account.window_end_ts =
+        Clock::get()?.unix_timestamp + pool_account.batch_window_secs as i64;
+    pool_account.next_batch_id += 1;
+
+    LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
+        .with_light_account(pool_account)?
+        .with_light_account(batch_account)?
+        .with_new_addresses(&[new_address_params])
+        .invoke(light_cpi_accounts)?;
+    Ok(())
+}
+
+pub fn handle_queue_swap_intent<'info>(
+    ctx: Context<'_, '_, '_, 'info, QueueSwapIntent<'info>>,
+    proof: SdkValidityProof,
+    pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+    pool_data: Vec<u8>,
+    batch_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+    batch_data: Vec<u8>,
+    amount_in_ciphertext: Vec<u8>,
+    min_amount_out_ciphertext: Vec<u8>,
+    input_type: u8,
+    a_to_b: bool,
+) -> Result<()> {
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.fee_payer.as_ref(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let pool_state = SwapPool::load(&pool_data)?;
+    let mut pool_account = LightAccount::<SwapPool>::new_mut(
+        &crate::ID,
+        &pool_meta,
+        pool_state,
+    )?;
+
+    let batch_state = SwapBatch::try_from_slice(&batch_data)?;
+    let mut batch_account = LightAccount::<SwapBatch>::new_mut(
+        &crate::ID,
+        &batch_meta,
+        batch_state,
+    )?;
+
+    require!(!pool_account.is_paused, ErrorCode::PoolPaused);
+    require!(batch_account.pool_address == pool_meta.address, ErrorCode::InvalidSwapBatch);
+    require!(
+        !batch_account.settled && Clock::get()?.unix_timestamp < batch_account.window_end_ts,
+        ErrorCode::SwapBatchClosed
+    );
+    require!(batch_account.intents.len() < MAX_BATCH_INTENTS, ErrorCode::SwapBatchFull);
+
+    let (mint_in, mint_out) = pool_account.swap_mints(a_to_b);
+    let (vault_in, _) = pool_account.swap_vaults(a_to_b);
+    require_keys_eq!(ctx.accounts.pool_vault_in.key(), vault_in, ErrorCode::InvalidPoolVault);
+    require_keys_eq!(ctx.accounts.trader_token_in.mint, mint_in, ErrorCode::InvalidInputMint);
+    require_keys_eq!(ctx.accounts.trader_token_out.mint, mint_out, ErrorCode::InvalidOutputMint);
+
+    let inco_program = ctx.accounts.inco_lightning_program.to_account_info();
+    let signer = ctx.accounts.fee_payer.to_account_info();
+
+    let amount_in = new_euint128(
+        operation(&inco_program, &signer),
+        amount_in_ciphertext.clone(),
+        input_type,
+    )?;
+
+    let min_amount_out =
+        new_euint128(operation(&inco_program, &signer), min_amount_out_ciphertext, input_type)?;
+
+    // CPI: Transfer amount_in from trader to pool vault (trader signs as owner or delegate),
+    // measuring what the vault received
+    let balance_before = ctx.accounts.pool_vault_in.amount;
+    let transfer_in_ctx = CpiContext::new(
+        ctx.accounts.inco_token_program.to_account_info(),
+        IncoTransfer {
+            source: ctx.accounts.trader_token_in.to_account_info(),
+            destination: ctx.accounts.pool_vault_in.to_account_info(),
+            authority: ctx.accounts.trader.to_account_info(),
+            inco_lightning_program: inco_program.clone(),
+            system_program: ctx.accounts.system_program.to_account_info(),
+        },
+    );
+    inco_token_transfer(transfer_in_ctx, amount_in_ciphertext, input_type)?;
+    let pool_vault_in = ctx.accounts.pool_vault_in.to_account_info();
+    let received = vault_received(&inco_program, &signer, &pool_vault_in, balance_before)?;
+
+    // An intent whose input did not arrive in full is queued with nothing to swap
+    let arrived: Ebool =
+        e_ge(operation(&inco_program, &signer), received, amount_in, SCALAR_BYTE)?;
+
+    let zero = as_euint128(operation(&inco_program, &signer), 0)?;
+
+    let amount_in =
+        e_select(operation(&inco_program, &signer), arrived, amount_in, zero, SCALAR_BYTE)?;
+
+    // The pool fee is settled at queue time; only the net input is batched
+    let fee_bps = pool_account.effective_fee_bps(Clock::get()?.unix_timestamp);
+    let fee_amount = compute_fee(&inco_program, &signer, amount_in, fee_bps)?;
+    let net_in = e_sub(operation(&inco_program, &signer), amount_in, fee_amount, SCALAR_BYTE)?;
+    let (protocol_fee_amount, lp_fee_amount) =
+        split_fee(&inco_program, &signer, fee_amount, pool_account.protocol_fee_share_bps)?;
+
+    let (_, _, protocol_fee_in) = pool_account.swap_side(a_to_b);
+    let new_protocol_fee = e_add(
+        operation(&inco_program, &signer),
+        protocol_fee_in,
+        protocol_fee_amount,
+        SCALAR_BYTE,
+    )?;
+    if a_to_b {
+        pool_account.protocol_fee_a = new_protocol_fee;
+        if let Some(lp_fee_amount) = lp_fee_amount {
+            pool_account.reserve_a = e_add(
+                operation(&inco_program, &signer),
+                pool_account.reserve_a,
+                lp_fee_amount,
+                SCALAR_BYTE,
+            )?;
+        }
+        pool_account.owed_a =
+            e_add(operation(&inco_program, &signer), pool_account.owed_a, net_in, SCALAR_BYTE)?;
+
+        batch_account.total_in_a = e_add(
+            operation(&inco_program, &signer),
+            batch_account.total_in_a,
+            net_in,
+            SCALAR_BYTE,
+        )?;
+    } else {
+        pool_account.protocol_fee_b = new_protocol_fee;
+        if let Some(lp_fee_amount) = lp_fee_amount {
+            pool_account.reserve_b = e_add(
+                operation(&inco_program, &signer),
+                pool_account.reserve_b,
+                lp_fee_amount,
+                SCALAR_BYTE,
+            )?;
+        }
+        pool_account.owed_b =
+            e_add(operation(&inco_program, &signer), pool_account.owed_b, net_in, SCALAR_BYTE)?;
+
+        batch_account.total_in_b = e_add(
+            operation(&inco_program, &signer),
+            batch_account.total_in_b,
+            net_in,
+            SCALAR_BYTE,
+        )?;
+    }
+    pool_account.bump_state_nonce();
+
+    batch_account.intents.push(SwapIntent {
+        trader: ctx.accounts.trader.key(),
+        trader_token_in: ctx.accounts.trader_token_in.key(),
+        trader_token_out: ctx.accounts.trader_token_out.key(),
+        a_to_b,
+        amount_in: net_in,
+        min_amount_out,
+        amount_out: Euint128::default(),
+        refund: Euint128::default(),
+    });
+
+    // Commit pool and batch updates
+    LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
+        .with_light_account(pool_account)?
+        .with_light_account(batch_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn handle_settle_batch<'info>(
+    ctx: Context<'_, '_, '_, 'info, SettleBatch<'info>>,
+    proof: SdkValidityProof,
+    pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+    pool_data: Vec<u8>,
+    batch_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+    batch_data: Vec<u8>,
+) -> Result<()> {
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.fee_payer.as_ref(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let pool_state = SwapPool::load(&pool_data)?;
+    let mut pool_account = LightAccount::<SwapPool>::new_mut(
+        &crate::ID,
+        &pool_meta,
+        pool_state,
+    )?;
+
+    let batch_state = SwapBatch::try_from_slice(&batch_data)?;
+    let mut batch_account = LightAccount::<SwapBatch>::new_mut(
+        &crate::ID,
+        &batch_meta,
+        batch_state,
+    )?;
+
+    require!(!pool_account.is_paused, ErrorCode::PoolPaused);
+    require!(batch_account.pool_address == pool_meta.address, ErrorCode::InvalidSwapBatch);
+    require!(!batch_account.settled, ErrorCode::SwapBatchClosed);
+    let now = Clock::get()?.unix_timestamp;
+    require!(now >= batch_account.window_end_ts, ErrorCode::SwapBatchOpen);
+
+    let inco_program = ctx.accounts.inco_lightning_program.to_account_info();
+    let signer = ctx.accounts.fee_payer.to_account_info();
+
+    // Accumulate prices over the elapsed interval before reserves change
+    accumulate_prices(&inco_program, &signer, &mut pool_account, now)?;
+
+    // Clearing price numerator/denominator
+    let pooled_a = e_add(
+        operation(&inco_program, &signer),
+        pool_account.reserve_a,
+        batch_account.total_in_a,
+        SCALAR_BYTE,
+    )?;
+
+    let pooled_b = e_add(
+        operation(&inco_program, &signer),
+        pool_account.reserve_b,
+        batch_account.total_in_b,
+        SCALAR_BYTE,
+    )?;
+
+    let zero = as_euint128(operation(&inco_program, &signer), 0)?;
+
+    let one = as_euint128(operation(&inco_program, &signer), 1)?;
+
+    // Circuit breaker limits per output side: A->B intents are paid in B
+    let limit_b = load_outflow_limit(&inco_program, &signer, &mut pool_account, true, now)?;
+    let limit_a = load_outflow_limit(&inco_program, &signer, &mut pool_account, false, now)?;
+    let mut tripped = pool_account.breaker_tripped;
+
+    // Inputs and outputs of the intents meeting their limit, per mint
+    let mut filled_in_a = zero;
+    let mut filled_in_b = zero;
+    let mut filled_out_a = zero;
+    let mut filled_out_b = zero;
+    let mut fills = Vec::with_capacity(batch_account.intents.len());
+    for intent in batch_account.intents.iter() {
+        let (numerator, denominator) = if intent.a_to_b {
+            (pooled_b, pooled_a)
+        } else {
+            (pooled_a, pooled_b)
+        };
+
+        let scaled =
+            e_mul(operation(&inco_program, &signer), intent.amount_in, numerator, SCALAR_BYTE)?;
+
+        let amount_out =
+            e_div(operation(&inco_program, &signer), scaled, denominator, SCALAR_BYTE)?;
+
+        let mut meets_limit: Ebool = e_ge(
+            operation(&inco_program, &signer),
+            amount_out,
+            intent.min_amount_out,
+            SCALAR_BYTE,
+        )?;
+
+        // An output above the breaker's per-trade cap trips it and is not filled
+        let limit = if intent.a_to_b { &limit_b } else { &limit_a };
+        if let Some(limit) = limit {
+            let trade_ok: Ebool = e_ge(
+                operation(&inco_program, &signer),
+                limit.max_trade_out,
+                amount_out,
+                SCALAR_BYTE,
+            )?;
+
+            tripped = e_select(
+                operation(&inco_program, &signer),
+                trade_ok,
+                tripped,
+                one,
+                SCALAR_BYTE,
+            )?;
+            meets_limit = e_all(&inco_program, &signer, &[meets_limit, trade_ok])?;
+        }
+
+        let filled_in = e_select(
+            operation(&inco_program, &signer),
+            meets_limit,
+            intent.amount_in,
+            zero,
+            SCALAR_BYTE,
+        )?;
+
+        let filled_out = e_select(
+            operation(&inco_program, &signer),
+            meets_limit,
+            amount_out,
+            zero,
+            SCALAR_BYTE,
+        )?;
+
+        if intent.a_to_b {
+            filled_in_a =
+                e_add(operation(&inco_program, &signer), filled_in_a, filled_in, SCALAR_BYTE)?;
+
+            filled_out_b = e_add(
+                operation(&inco_program, &signer),
+                filled_out_b,
+                filled_out,
+                SCALAR_BYTE,
+            )?;
+        } else {
+            filled_in_b =
+                e_add(operation(&inco_program, &signer), filled_in_b, filled_in, SCALAR_BYTE)?;
+
+            filled_out_a = e_add(
+                operation(&inco_program, &signer),
+                filled_out_a,
+                filled_out,
+                SCALAR_BYTE,
+            )?;
+        }
+        fills.push((meets_limit, amount_out));
+    }
+
+    // Filled inputs join the reserves and filled outputs leave them; the batch applies
+    // only if both sides stay solvent and the product does not shrink
+    let credited_a = e_add(
+        operation(&inco_program, &signer),
+        pool_account.reserve_a,
+        filled_in_a,
+        SCALAR_BYTE,
+    )?;
+
+    let credited_b = e_add(
+        operation(&inco_program, &signer),
+        pool_account.reserve_b,
+        filled_in_b,
+        SCALAR_BYTE,
+    )?;
+
+    let solvent_a: Ebool =
+        e_ge(operation(&inco_program, &signer), credited_a, filled_out_a, SCALAR_BYTE)?;
+
+    let solvent_b: Ebool =
+        e_ge(operation(&inco_program, &signer), credited_b, filled_out_b, SCALAR_BYTE)?;
+
+    let new_reserve_a =
+        e_sub(operation(&inco_program, &signer), credited_a, filled_out_a, SCALAR_BYTE)?;
+
+    let new_reserve_b =
+        e_sub(operation(&inco_program, &signer), credited_b, filled_out_b, SCALAR_BYTE)?;
+
+    let old_k = e_mul(
+        operation(&inco_program, &signer),
+        pool_account.reserve_a,
+        pool_account.reserve_b,
+        SCALAR_BYTE,
+    )?;
+
+    let new_k =
+        e_mul(operation(&inco_program, &signer), new_reserve_a, new_reserve_b, SCALAR_BYTE)?;
+
+    let k_ok: Ebool = e_ge(operation(&inco_program, &signer), new_k, old_k, SCALAR_BYTE)?;
+
+    // Window outflow caps apply to the batch's total output per side; a tripped breaker
+    // with auto-pause rejects the whole batch
+    let mut batch_checks = vec![solvent_a, solvent_b, k_ok];
+
+    // The clearing price (B per A) must lie within the oracle price band
+    if let Some(price_q32) = pool_oracle_price(&pool_account, ctx.accounts.oracle.as_deref(), now)? {
+        batch_checks.extend(price_band_checks(
+            &inco_program,
+            &signer,
+            price_q32,
+            pool_account.oracle_max_deviation_bps,
+            true,
+            pooled_a,
+            pooled_b,
+        )?);
+    }
+    for (limit, filled_out) in [(&limit_a, filled_out_a), (&limit_b, filled_out_b)] {
+        let Some(limit) = limit else { continue };
+        let window_remaining = e_sub(
+            operation(&inco_program, &signer),
+            limit.window_cap,
+            limit.window_outflow,
+            SCALAR_BYTE,
+        )?;
+
+        let window_ok: Ebool =
+            e_ge(operation(&inco_program, &signer), window_remaining, filled_out, SCALAR_BYTE)?;
+
+        tripped =
+            e_select(operation(&inco_program, &signer), window_ok, tripped, one, SCALAR_BYTE)?;
+        batch_checks.push(window_ok);
+    }
+    if pool_account.breaker_enabled && pool_account.breaker_auto_pause {
+        let not_tripped: Ebool = e_ge(
+            operation(&inco_program, &signer),
+            zero,
+            pool_account.breaker_tripped,
+            SCALAR_BYTE,
+        )?;
+        batch_checks.push(not_tripped);
+    }
+    let batch_ok = e_all(&inco_program, &signer, &batch_checks)?;
+
+    if pool_account.breaker_enabled {
+        let window_outflow_a = e_add(
+            operation(&inco_program, &signer),
+            pool_account.window_outflow_a,
+            filled_out_a,
+            SCALAR_BYTE,
+        )?;
+
+        pool_account.window_outflow_a = e_select(
+            operation(&inco_program, &signer),
+            batch_ok,
+            window_outflow_a,
+            pool_account.window_outflow_a,
+            SCALAR_BYTE,
+        )?;
+
+        let window_outflow_b = e_add(
+            operation(&inco_program, &signer),
+            pool_account.window_outflow_b,
+            filled_out_b,
+            SCALAR_BYTE,
+        )?;
+
+        pool_account.window_outflow_b = e_select(
+            operation(&inco_program, &signer),
+            batch_ok,
+            window_outflow_b,
+            pool_account.window_outflow_b,
+            SCALAR_BYTE,
+        )?;
+        pool_account.breaker_tripped = tripped;
+    }
+
+    pool_account.reserve_a = e_select(
+        operation(&inco_program, &signer),
+        batch_ok,
+        new_reserve_a,
+        pool_account.reserve_a,
+        SCALAR_BYTE,
+    )?;
+
+    pool_account.reserve_b = e_select(
+        operation(&inco_program, &signer),
+        batch_ok,
+        new_reserve_b,
+        pool_account.reserve_b,
+        SCALAR_BYTE,
+    )?;
+
+    // Owed balances swap filled inputs for filled outputs
+    let owed_a = e_add(
+        operation(&inco_program, &signer),
+        pool_account.owed_a,
+        filled_out_a,
+        SCALAR_BYTE,
+    )?;
+
+    let owed_a = e_sub(operation(&inco_program, &signer), owed_a, filled_in_a, SCALAR_BYTE)?;
+
+    pool_account.owed_a = e_select(
+        operation(&inco_program, &signer),
+        batch_ok,
+        owed_a,
+        pool_account.owed_a,
+        SCALAR_BYTE,
+    )?;
+
+    let owed_b = e_add(
+        operation(&inco_program, &signer),
+        pool_account.owed_b,
+        filled_out_b,
+        SCALAR_BYTE,
+    )?;
+
+    let owed_b = e_sub(operation(&inco_program, &signer), owed_b, filled_in_b, SCALAR_BYTE)?;
+
+    pool_account.owed_b = e_select(
+        operation(&inco_program, &signer),
+        batch_ok,
+        owed_b,
+        pool_account.owed_b,
+        SCALAR_BYTE,
+    )?;
+
+    // Credit each intent its output if filled, otherwise its net input back
+    for (intent, (meets_limit, amount_out)) in batch_account.intents.iter_mut().zip(fills) {
+        let filled = e_all(&inco_program, &signer, &[meets_limit, batch_ok])?;
+
+        intent.amount_out =
+            e_select(operation(&inco_program, &signer), filled, amount_out, zero, SCALAR_BYTE)?;
+
+        intent.refund = e_select(
+            operation(&inco_program, &signer),
+            filled,
+            zero,
+            intent.amount_in,
+            SCALAR_BYTE,
+        )?;
+    }
+
+    pool_account.last_update_ts = now;
+    pool_account.bump_state_nonce();
+    batch_account.settled = true;
+
+    // Commit pool and batch updates
+    LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
+        .with_light_account(pool_account)?
+        .with_light_account(batch_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn handle_claim_batch_output<'info>(
+    ctx: Context<'_, '_, '_, 'info, ClaimBatchOutput<'info>>,
+    proof: SdkValidityProof,
+    pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+    pool_data: Vec<u8>,
+    batch_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+    batch_data: Vec<u8>,
+    intent_index: u8,
+    claim_output: bool,
+    amount_ciphertext: Vec<u8>,
+    input_type: u8,
+    address_tree_info: PackedAddressTreeInfo,
+    output_tree_index: u8,
+    payout_nonce: u64,
+) -> Result<()> {
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.fee_payer.as_ref(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let pool_state = SwapPool::load(&pool_data)?;
+    let mut pool_account = LightAccount::<SwapPool>::new_mut(
+        &crate::ID,
+        &pool_meta,
+        pool_state,
+    )?;
+
+    let batch_state = SwapBatch::try_from_slice(&batch_data)?;
+    let mut batch_account = LightAccount::<SwapBatch>::new_mut(
+        &crate::ID,
+        &batch_meta,
+        batch_state,
+    )?;
+
+    require!(batch_account.pool_address == pool_meta.address, ErrorCode::InvalidSwapBatch);
+    require!(pool_account.claims_open(Clock::get()?.unix_timestamp), ErrorCode::PoolPaused);
+    require!(batch_account.settled, ErrorCode::SwapBatchOpen);
+    let intent = batch_account
+        .intents
+        .get_mut(intent_index as usize)
+        .ok_or(ErrorCode::InvalidSwapBatch)?;
+    let trader = ctx.accounts.trader.key();
+    require_keys_eq!(intent.trader, trader, ErrorCode::Unauthorized);
+
+    // The output is paid in the opposite mint, the refund in the input mint
+    let claim_a = intent.a_to_b != claim_output;
+    let vault = if claim_a { pool_account.vault_a } else { pool_account.vault_b };
+    let destination = if claim_output { intent.trader_token_out } else { intent.trader_token_in };
+    require_keys_eq!(ctx.accounts.pool_vault.key(), vault, ErrorCode::InvalidPoolVault);
+    require_keys_eq!(ctx.accounts.trader_token.key(), destination, ErrorCode::Unauthorized);
+
+    let inco_program = ctx.accounts.inco_lightning_program.to_account_info();
+    let signer = ctx.accounts.fee_payer.to_account_info();
+    let credit = if claim_output { intent.amount_out } else { intent.refund };
+
+    let claimed =
+        new_euint128(operation(&inco_program, &signer), amount_ciphertext.clone(), input_type)?;
+
+    let within_credit: Ebool =
+        e_ge(operation(&inco_program, &signer), credit, claimed, SCALAR_BYTE)?;
+
+    let zero = as_euint128(operation(&inco_program, &signer), 0)?;
+
+    let debit =
+        e_select(operation(&inco_program, &signer), within_credit, claimed, zero, SCALAR_BYTE)?;
+
+    let remaining = e_sub(operation(&inco_program, &signer), credit, debit, SCALAR_BYTE)?;
+    if claim_output {
+        intent.amount_out = remaining;
+    } else {
+        intent.refund = remaining;
+    }
+
+    let owed = if claim_a { pool_account.owed_a } else { pool_account.owed_b };
+    let owed = e_sub(operation(&inco_program, &signer), owed, debit, SCALAR_BYTE)?;
+    if claim_a {
+        pool_account.owed_a = owed;
+    } else {
+        pool_account.owed_b = owed;
+    }
+    pool_account.bump_state_nonce();
+
+    let on_approved = vec![PayoutLeg::transfer(
+        pool_account.authority_seeds(),
+        vault,
+        destination,
+        amount_ciphertext,
+    )];
+
+    // The trader decrypts the outcome to settle the payout
+    allow_payout_outcome(
+        &inco_program,
+        &signer,
+        &ctx.accounts.system_program.to_account_info(),
+        &ctx.accounts.trader.to_account_info(),
+        &[(within_credit, ctx.accounts.approved_allowance.to_account_info())],
+    )?;
+
+    let tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|error| ProgramError::Custom(error.into()))?;
+    let (address, address_seed) = derive_address(
+        &[PAYOUT_SEED, trader.as_ref(), &payout_nonce.to_le_bytes()],
+        &tree_pubkey,
+        &crate::ID,
+    );
+    let new_address_params =
+        address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(2));
+
+    let mut payout_account = LightAccount::<PendingPayout>::new_init(
+        &crate::ID,
+        Some(address),
+        output_tree_index,
+    );
+    payout_account.beneficiary = trader;
+    payout_account.input_type = input_type;
+    payout_account.approved = within_credit;
+    payout_account.on_approved = on_approved;
+    payout_account.created_at = Clock::get()?.unix_timestamp;
+
+    LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
+        .with_light_account(pool_account)?
+        .with_light_account(batch_account)?
+        .with_light_account(payout_account)?
+        .with_new_addresses(&[new_address_params])
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
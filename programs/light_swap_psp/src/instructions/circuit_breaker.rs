@@ -0,0 +1,156 @@
+//! Per-trade and per-window outflow limits
+
+use anchor_lang::prelude::*;
+use inco_lightning::cpi::accounts::Allow;
+use inco_lightning::cpi::{allow, as_euint128, new_euint128};
+use inco_lightning::ID as INCO_LIGHTNING_ID;
+use light_sdk::{
+    account::LightAccount,
+    cpi::v2::{CpiAccounts, LightSystemProgramCpi},
+    cpi::{InvokeLightSystemProgram, LightCpiInstruction},
+    instruction::ValidityProof as SdkValidityProof,
+};
+
+use crate::errors::ErrorCode;
+use crate::fhe::*;
+use crate::state::*;
+
+#[derive(Accounts)]
+pub struct SetCircuitBreaker<'info> {
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+    pub authority: Signer<'info>,
+    /// CHECK: Inco Lightning program for encrypted operations
+    #[account(address = INCO_LIGHTNING_ID)]
+    pub inco_lightning_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct GrantBreakerAccess<'info> {
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+    pub authority: Signer<'info>,
+    /// CHECK: Inco allowance PDA for (breaker_tripped handle, authority)
+    #[account(mut)]
+    pub allowance: AccountInfo<'info>,
+    /// CHECK: Inco Lightning program for encrypted operations
+    #[account(address = INCO_LIGHTNING_ID)]
+    pub inco_lightning_program: AccountInfo<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handle_set_circuit_breaker<'info>(
+    ctx: Context<'_, '_, '_, 'info, SetCircuitBreaker<'info>>,
+    proof: SdkValidityProof,
+    pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+    pool_data: Vec<u8>,
+    enabled: bool,
+    auto_pause: bool,
+    window_secs: u32,
+    max_trade_out_a_ciphertext: Vec<u8>,
+    max_trade_out_b_ciphertext: Vec<u8>,
+    window_outflow_cap_a_ciphertext: Vec<u8>,
+    window_outflow_cap_b_ciphertext: Vec<u8>,
+    input_type: u8,
+) -> Result<()> {
+    require!(!enabled || window_secs > 0, ErrorCode::InvalidCircuitBreaker);
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.fee_payer.as_ref(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let pool_state = SwapPool::load(&pool_data)?;
+    let mut pool_account = LightAccount::<SwapPool>::new_mut(
+        &crate::ID,
+        &pool_meta,
+        pool_state,
+    )?;
+
+    require_keys_eq!(pool_account.authority, ctx.accounts.authority.key(), ErrorCode::Unauthorized);
+
+    let inco_program = ctx.accounts.inco_lightning_program.to_account_info();
+    let signer = ctx.accounts.fee_payer.to_account_info();
+
+    pool_account.max_trade_out_a = new_euint128(
+        operation(&inco_program, &signer),
+        max_trade_out_a_ciphertext,
+        input_type,
+    )?;
+
+    pool_account.max_trade_out_b = new_euint128(
+        operation(&inco_program, &signer),
+        max_trade_out_b_ciphertext,
+        input_type,
+    )?;
+
+    pool_account.window_outflow_cap_a = new_euint128(
+        operation(&inco_program, &signer),
+        window_outflow_cap_a_ciphertext,
+        input_type,
+    )?;
+
+    pool_account.window_outflow_cap_b = new_euint128(
+        operation(&inco_program, &signer),
+        window_outflow_cap_b_ciphertext,
+        input_type,
+    )?;
+
+    let zero = as_euint128(operation(&inco_program, &signer), 0)?;
+    pool_account.window_outflow_a = zero;
+    pool_account.window_outflow_b = zero;
+    pool_account.breaker_tripped = zero;
+
+    pool_account.breaker_enabled = enabled;
+    pool_account.breaker_auto_pause = auto_pause;
+    pool_account.breaker_window_secs = window_secs;
+    pool_account.breaker_window_start_ts = Clock::get()?.unix_timestamp;
+
+    // Commit pool state update
+    LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
+        .with_light_account(pool_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn handle_grant_breaker_access<'info>(
+    ctx: Context<'_, '_, '_, 'info, GrantBreakerAccess<'info>>,
+    proof: SdkValidityProof,
+    pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+    pool_data: Vec<u8>,
+) -> Result<()> {
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.fee_payer.as_ref(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    // Re-committed unchanged so the validity proof covers the handle granted here
+    let pool_state = SwapPool::load(&pool_data)?;
+    let pool_account = LightAccount::<SwapPool>::new_mut(
+        &crate::ID,
+        &pool_meta,
+        pool_state,
+    )?;
+
+    require_keys_eq!(pool_account.authority, ctx.accounts.authority.key(), ErrorCode::Unauthorized);
+
+    let cpi_ctx = CpiContext::new(
+        ctx.accounts.inco_lightning_program.to_account_info(),
+        Allow {
+            allowance_account: ctx.accounts.allowance.to_account_info(),
+            signer: ctx.accounts.fee_payer.to_account_info(),
+            allowed_address: ctx.accounts.authority.to_account_info(),
+            system_program: ctx.accounts.system_program.to_account_info(),
+        },
+    );
+    allow(cpi_ctx, pool_account.breaker_tripped.0, true, ctx.accounts.authority.key())?;
+
+    LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
+        .with_light_account(pool_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
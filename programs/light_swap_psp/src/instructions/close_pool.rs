@@ -0,0 +1,237 @@
+//! Draining and closing an empty pool
+
+use anchor_lang::prelude::*;
+use inco_lightning::cpi::accounts::{Allow, VerifySignature};
+use inco_lightning::cpi::{allow, as_euint128, e_add, e_ge, is_validsignature};
+use inco_lightning::types::Ebool;
+use inco_lightning::ID as INCO_LIGHTNING_ID;
+use light_sdk::{
+    account::LightAccount,
+    cpi::v2::{CpiAccounts, LightSystemProgramCpi},
+    cpi::{InvokeLightSystemProgram, LightCpiInstruction},
+    instruction::ValidityProof as SdkValidityProof,
+};
+
+use crate::constants::*;
+use crate::errors::ErrorCode;
+use crate::fhe::*;
+use crate::inco_token::cpi::accounts::CloseAccount as IncoCloseAccount;
+use crate::inco_token::cpi::close_account as inco_token_close_account;
+use crate::inco_token::{IncoAccount, ID as INCO_TOKEN_ID};
+use crate::state::*;
+
+#[derive(Accounts)]
+pub struct PrepareClosePool<'info> {
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+    pub authority: Signer<'info>,
+    /// CHECK: Inco allowance PDA for (close_check handle, authority)
+    #[account(mut)]
+    pub allowance: AccountInfo<'info>,
+    /// CHECK: Inco Lightning program for encrypted operations
+    #[account(address = INCO_LIGHTNING_ID)]
+    pub inco_lightning_program: AccountInfo<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClosePool<'info> {
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+    /// Receives the vaults' rent
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    /// CHECK: Pool authority PDA, verified against the pool mints (mut required for CPI)
+    #[account(mut)]
+    pub pool_authority: AccountInfo<'info>,
+    /// Pool vault for token A (owned by pool_authority)
+    #[account(mut)]
+    pub pool_vault_a: Account<'info, IncoAccount>,
+    /// Pool vault for token B (owned by pool_authority)
+    #[account(mut)]
+    pub pool_vault_b: Account<'info, IncoAccount>,
+    /// CHECK: Instructions sysvar holding the Ed25519 decryption attestation
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: AccountInfo<'info>,
+    /// CHECK: Inco Lightning program for encrypted operations
+    #[account(address = INCO_LIGHTNING_ID)]
+    pub inco_lightning_program: AccountInfo<'info>,
+    /// CHECK: Inco Token program for token transfers
+    #[account(address = INCO_TOKEN_ID)]
+    pub inco_token_program: AccountInfo<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handle_prepare_close_pool<'info>(
+    ctx: Context<'_, '_, '_, 'info, PrepareClosePool<'info>>,
+    proof: SdkValidityProof,
+    pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+    pool_data: Vec<u8>,
+) -> Result<()> {
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.fee_payer.as_ref(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let pool_state = SwapPool::load(&pool_data)?;
+    let mut pool_account = LightAccount::<SwapPool>::new_mut(
+        &crate::ID,
+        &pool_meta,
+        pool_state,
+    )?;
+
+    require_keys_eq!(pool_account.authority, ctx.accounts.authority.key(), ErrorCode::Unauthorized);
+    require!(pool_account.is_paused, ErrorCode::PoolNotPaused);
+
+    let inco_program = ctx.accounts.inco_lightning_program.to_account_info();
+    let signer = ctx.accounts.fee_payer.to_account_info();
+
+    // 0 >= reserve_a + reserve_b + protocol_fee_a + protocol_fee_b + owed_a + owed_b
+    let reserves = e_add(
+        operation(&inco_program, &signer),
+        pool_account.reserve_a,
+        pool_account.reserve_b,
+        SCALAR_BYTE,
+    )?;
+
+    let fees = e_add(
+        operation(&inco_program, &signer),
+        pool_account.protocol_fee_a,
+        pool_account.protocol_fee_b,
+        SCALAR_BYTE,
+    )?;
+
+    let owed = e_add(
+        operation(&inco_program, &signer),
+        pool_account.owed_a,
+        pool_account.owed_b,
+        SCALAR_BYTE,
+    )?;
+
+    let total = e_add(operation(&inco_program, &signer), reserves, fees, SCALAR_BYTE)?;
+
+    let total = e_add(operation(&inco_program, &signer), total, owed, SCALAR_BYTE)?;
+
+    let zero = as_euint128(operation(&inco_program, &signer), 0)?;
+
+    let is_empty: Ebool = e_ge(operation(&inco_program, &signer), zero, total, SCALAR_BYTE)?;
+
+    let cpi_ctx = CpiContext::new(
+        inco_program,
+        Allow {
+            allowance_account: ctx.accounts.allowance.to_account_info(),
+            signer,
+            allowed_address: ctx.accounts.authority.to_account_info(),
+            system_program: ctx.accounts.system_program.to_account_info(),
+        },
+    );
+    allow(cpi_ctx, is_empty.0, true, ctx.accounts.authority.key())?;
+
+    pool_account.close_check = is_empty;
+    pool_account.close_check_nonce = pool_account.state_nonce;
+
+    // Commit pool state update
+    LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
+        .with_light_account(pool_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn handle_close_pool<'info>(
+    ctx: Context<'_, '_, '_, 'info, ClosePool<'info>>,
+    proof: SdkValidityProof,
+    pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+    pool_data: Vec<u8>,
+    index_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+    index_data: Vec<u8>,
+    close_check_plaintext: Vec<u8>,
+) -> Result<()> {
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.fee_payer.as_ref(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let pool_state = SwapPool::load(&pool_data)?;
+    let pool = pool_state.clone();
+    let pool_account = LightAccount::<SwapPool>::new_close(
+        &crate::ID,
+        &pool_meta,
+        pool_state,
+    )?;
+
+    let index_state = PoolIndexEntry::try_from_slice(&index_data)?;
+    require!(index_state.pool_address == pool_meta.address, ErrorCode::InvalidPoolIndex);
+    let index_account = LightAccount::<PoolIndexEntry>::new_close(
+        &crate::ID,
+        &index_meta,
+        index_state,
+    )?;
+
+    require_keys_eq!(pool.authority, ctx.accounts.authority.key(), ErrorCode::Unauthorized);
+    require!(pool.is_paused, ErrorCode::PoolNotPaused);
+    require!(
+        pool.close_check.0 != 0 && pool.close_check_nonce == pool.state_nonce,
+        ErrorCode::PoolNotEmpty
+    );
+    require!(
+        close_check_plaintext.iter().any(|byte| *byte != 0),
+        ErrorCode::PoolNotEmpty
+    );
+
+    // The attested plaintext of close_check must be true
+    let cpi_ctx = CpiContext::new(
+        ctx.accounts.inco_lightning_program.to_account_info(),
+        VerifySignature {
+            instructions: ctx.accounts.instructions.to_account_info(),
+            signer: ctx.accounts.fee_payer.to_account_info(),
+        },
+    );
+    is_validsignature(
+        cpi_ctx,
+        1,
+        Some(vec![pool.close_check.0.to_le_bytes().to_vec()]),
+        Some(vec![close_check_plaintext]),
+    )?;
+
+    // Verify pool authority and vaults
+    let pool_authority = pool.pool_authority;
+    let pool_auth_bump = pool.pool_authority_bump;
+    require_keys_eq!(ctx.accounts.pool_authority.key(), pool_authority);
+    require_keys_eq!(ctx.accounts.pool_vault_a.key(), pool.vault_a, ErrorCode::InvalidPoolVault);
+    require_keys_eq!(ctx.accounts.pool_vault_b.key(), pool.vault_b, ErrorCode::InvalidPoolVault);
+
+    // CPI: Close both vaults, rent to the authority (pool authority PDA signs)
+    let pool_auth_seeds: &[&[u8]] = &[
+        POOL_AUTH_SEED,
+        pool.mint_a.as_ref(),
+        pool.mint_b.as_ref(),
+        &[pool_auth_bump],
+    ];
+    let signer_seeds = &[pool_auth_seeds];
+    for vault in [
+        ctx.accounts.pool_vault_a.to_account_info(),
+        ctx.accounts.pool_vault_b.to_account_info(),
+    ] {
+        let close_ctx = CpiContext::new_with_signer(
+            ctx.accounts.inco_token_program.to_account_info(),
+            IncoCloseAccount {
+                account: vault,
+                destination: ctx.accounts.authority.to_account_info(),
+                authority: ctx.accounts.pool_authority.to_account_info(),
+            },
+            signer_seeds,
+        );
+        inco_token_close_account(close_ctx)?;
+    }
+
+    // Nullify the compressed pool account and its index entry
+    LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
+        .with_light_account(pool_account)?
+        .with_light_account(index_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
@@ -0,0 +1,574 @@
+//! Dollar-cost-averaging orders executed in slices
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::AccountMeta;
+use inco_lightning::cpi::{as_euint128, e_ge, e_select, e_sub, new_euint128};
+use inco_lightning::types::Ebool;
+use inco_lightning::ID as INCO_LIGHTNING_ID;
+use light_sdk::{
+    account::LightAccount,
+    address::v2::derive_address,
+    cpi::v2::{CpiAccounts, LightSystemProgramCpi},
+    cpi::{InvokeLightSystemProgram, LightCpiInstruction},
+    instruction::{
+        PackedAddressTreeInfo, PackedAddressTreeInfoExt, ValidityProof as SdkValidityProof,
+    },
+};
+
+use crate::constants::*;
+use crate::curve::*;
+use crate::errors::ErrorCode;
+use crate::events::*;
+use crate::fhe::*;
+use crate::inco_token::cpi::accounts::IncoTransfer;
+use crate::inco_token::cpi::transfer as inco_token_transfer;
+use crate::inco_token::{IncoAccount, ID as INCO_TOKEN_ID};
+use crate::oracle::*;
+use crate::payout::*;
+use crate::state::*;
+use crate::vault::*;
+
+#[derive(Accounts)]
+pub struct CreateDcaOrder<'info> {
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+    pub owner: Signer<'info>,
+    /// Owner's Inco token account for the order's input mint
+    #[account(mut)]
+    pub owner_token_in: Account<'info, IncoAccount>,
+    /// Escrow vault for the input mint (owned by this order's DCA escrow PDA)
+    #[account(mut)]
+    pub escrow_vault_in: Account<'info, IncoAccount>,
+    /// CHECK: Inco Lightning program for encrypted operations
+    #[account(address = INCO_LIGHTNING_ID)]
+    pub inco_lightning_program: AccountInfo<'info>,
+    /// CHECK: Inco Token program for token transfers
+    #[account(address = INCO_TOKEN_ID)]
+    pub inco_token_program: AccountInfo<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteDca<'info> {
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+    /// CHECK: Order owner, verified against the order; decrypts the payout outcome
+    pub owner: AccountInfo<'info>,
+    /// Escrow vault holding the order's remaining budget
+    pub escrow_vault_in: Account<'info, IncoAccount>,
+    /// Pool vault receiving the slice (owned by pool_authority)
+    pub pool_vault_in: Account<'info, IncoAccount>,
+    /// Pool vault paying the slice output (owned by pool_authority)
+    pub pool_vault_out: Account<'info, IncoAccount>,
+    /// Order owner's Inco token account for the output mint
+    pub owner_token_out: Account<'info, IncoAccount>,
+    /// CHECK: Inco allowance PDA for (payout approved handle, owner)
+    #[account(mut)]
+    pub approved_allowance: AccountInfo<'info>,
+    /// CHECK: Price feed account, verified against the pool's configured oracle; only
+    /// required when the pool has an oracle price band
+    pub oracle: Option<UncheckedAccount<'info>>,
+    /// CHECK: Inco Lightning program for encrypted operations
+    #[account(address = INCO_LIGHTNING_ID)]
+    pub inco_lightning_program: AccountInfo<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelDcaOrder<'info> {
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+    pub owner: Signer<'info>,
+    /// Escrow vault holding the order's remaining budget
+    pub escrow_vault_in: Account<'info, IncoAccount>,
+    /// Owner's Inco token account receiving the refund
+    pub owner_token_in: Account<'info, IncoAccount>,
+    /// CHECK: Inco allowance PDA for (payout approved handle, owner)
+    #[account(mut)]
+    pub approved_allowance: AccountInfo<'info>,
+    /// CHECK: Inco Lightning program for encrypted operations
+    #[account(address = INCO_LIGHTNING_ID)]
+    pub inco_lightning_program: AccountInfo<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handle_create_dca_order<'info>(
+    ctx: Context<'_, '_, '_, 'info, CreateDcaOrder<'info>>,
+    proof: SdkValidityProof,
+    address_tree_info: PackedAddressTreeInfo,
+    output_tree_index: u8,
+    order_nonce: u64,
+    mint_a: Pubkey,
+    mint_b: Pubkey,
+    a_to_b: bool,
+    budget_ciphertext: Vec<u8>,
+    amount_per_interval_ciphertext: Vec<u8>,
+    min_amount_out_ciphertext: Vec<u8>,
+    input_type: u8,
+    interval_secs: u32,
+    executions: u32,
+) -> Result<()> {
+    require!(interval_secs > 0 && executions > 0, ErrorCode::InvalidDcaOrder);
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.fee_payer.as_ref(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|error| ProgramError::Custom(error.into()))?;
+    let owner = ctx.accounts.owner.key();
+    let (address, address_seed) = derive_address(
+        &[DCA_ORDER_SEED, owner.as_ref(), &order_nonce.to_le_bytes()],
+        &tree_pubkey,
+        &crate::ID,
+    );
+    let new_address_params =
+        address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0));
+
+    // Orders live in the same address tree as the pool they execute against
+    let (pool_address, _) =
+        derive_address(&[b"pool", mint_a.as_ref(), mint_b.as_ref()], &tree_pubkey, &crate::ID);
+
+    // Verify the order's own escrow authority and vault
+    let (escrow_authority, _) =
+        Pubkey::find_program_address(&[DCA_ESCROW_SEED, address.as_ref()], &crate::ID);
+    let mint_in = if a_to_b { mint_a } else { mint_b };
+    require!(
+        ctx.accounts.escrow_vault_in.owner == escrow_authority
+            && ctx.accounts.escrow_vault_in.mint == mint_in,
+        ErrorCode::InvalidEscrowVault
+    );
+    require_keys_eq!(ctx.accounts.owner_token_in.mint, mint_in, ErrorCode::InvalidInputMint);
+
+    let inco_program = ctx.accounts.inco_lightning_program.to_account_info();
+    let signer = ctx.accounts.fee_payer.to_account_info();
+
+    let mut order_account = LightAccount::<DcaOrder>::new_init(
+        &crate::ID,
+        Some(address),
+        output_tree_index,
+    );
+
+    let budget =
+        new_euint128(operation(&inco_program, &signer), budget_ciphertext.clone(), input_type)?;
+
+    order_account.amount_per_interval = new_euint128(
+        operation(&inco_program, &signer),
+        amount_per_interval_ciphertext.clone(),
+        input_type,
+    )?;
+
+    order_account.min_amount_out = new_euint128(
+        operation(&inco_program, &signer),
+        min_amount_out_ciphertext.clone(),
+        input_type,
+    )?;
+
+    order_account.owner = owner;
+    order_account.pool_address = pool_address;
+    order_account.mint_a = mint_a;
+    order_account.mint_b = mint_b;
+    order_account.a_to_b = a_to_b;
+    order_account.amount_per_interval_ciphertext = amount_per_interval_ciphertext;
+    order_account.min_amount_out_ciphertext = min_amount_out_ciphertext;
+    order_account.input_type = input_type;
+    order_account.interval_secs = interval_secs;
+    order_account.executions_remaining = executions;
+    order_account.last_execution_ts = 0;
+
+    // CPI: Escrow the total budget from owner (owner signs), measuring what arrived
+    let balance_before = ctx.accounts.escrow_vault_in.amount;
+    let transfer_in_ctx = CpiContext::new(
+        ctx.accounts.inco_token_program.to_account_info(),
+        IncoTransfer {
+            source: ctx.accounts.owner_token_in.to_account_info(),
+            destination: ctx.accounts.escrow_vault_in.to_account_info(),
+            authority: ctx.accounts.owner.to_account_info(),
+            inco_lightning_program: inco_program.clone(),
+            system_program: ctx.accounts.system_program.to_account_info(),
+        },
+    );
+    inco_token_transfer(transfer_in_ctx, budget_ciphertext, input_type)?;
+    let escrow_vault_in = ctx.accounts.escrow_vault_in.to_account_info();
+    let received = vault_received(&inco_program, &signer, &escrow_vault_in, balance_before)?;
+
+    // A budget that did not arrive in full funds no slices
+    let arrived: Ebool =
+        e_ge(operation(&inco_program, &signer), received, budget, SCALAR_BYTE)?;
+
+    let zero = as_euint128(operation(&inco_program, &signer), 0)?;
+
+    order_account.remaining_budget =
+        e_select(operation(&inco_program, &signer), arrived, budget, zero, SCALAR_BYTE)?;
+
+    LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
+        .with_light_account(order_account)?
+        .with_new_addresses(&[new_address_params])
+        .invoke(light_cpi_accounts)?;
+    Ok(())
+}
+
+pub fn handle_execute_dca<'info>(
+    ctx: Context<'_, '_, '_, 'info, ExecuteDca<'info>>,
+    proof: SdkValidityProof,
+    pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+    pool_data: Vec<u8>,
+    order_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+    order_data: Vec<u8>,
+    invariant_ciphertext: Vec<u8>,
+    address_tree_info: PackedAddressTreeInfo,
+    output_tree_index: u8,
+    payout_nonce: u64,
+) -> Result<()> {
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.fee_payer.as_ref(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let pool_state = SwapPool::load(&pool_data)?;
+    let mut pool_account = LightAccount::<SwapPool>::new_mut(
+        &crate::ID,
+        &pool_meta,
+        pool_state,
+    )?;
+
+    let order_state = DcaOrder::try_from_slice(&order_data)?;
+    let mut order_account = LightAccount::<DcaOrder>::new_mut(
+        &crate::ID,
+        &order_meta,
+        order_state,
+    )?;
+
+    require!(!pool_account.is_paused, ErrorCode::PoolPaused);
+    require!(!pool_account.batch_mode, ErrorCode::BatchModeActive);
+    require!(order_account.pool_address == pool_meta.address, ErrorCode::InvalidDcaOrder);
+    require!(order_account.executions_remaining > 0, ErrorCode::DcaOrderExhausted);
+
+    let now = Clock::get()?.unix_timestamp;
+    require!(
+        now >= order_account.last_execution_ts + order_account.interval_secs as i64,
+        ErrorCode::DcaIntervalNotElapsed
+    );
+
+    // Verify escrow and pool vaults
+    require_keys_eq!(ctx.accounts.owner.key(), order_account.owner, ErrorCode::Unauthorized);
+    let (escrow_authority, escrow_bump) =
+        Pubkey::find_program_address(&[DCA_ESCROW_SEED, order_meta.address.as_ref()], &crate::ID);
+
+    let a_to_b = order_account.a_to_b;
+    let (mint_in, mint_out) = pool_account.swap_mints(a_to_b);
+    require!(
+        ctx.accounts.escrow_vault_in.owner == escrow_authority
+            && ctx.accounts.escrow_vault_in.mint == mint_in,
+        ErrorCode::InvalidEscrowVault
+    );
+    let (vault_in, vault_out) = pool_account.swap_vaults(a_to_b);
+    require_keys_eq!(ctx.accounts.pool_vault_in.key(), vault_in, ErrorCode::InvalidPoolVault);
+    require_keys_eq!(ctx.accounts.pool_vault_out.key(), vault_out, ErrorCode::InvalidPoolVault);
+    require_keys_eq!(ctx.accounts.owner_token_out.owner, order_account.owner, ErrorCode::Unauthorized);
+    require_keys_eq!(ctx.accounts.owner_token_out.mint, mint_out, ErrorCode::InvalidOutputMint);
+
+    let inco_program = ctx.accounts.inco_lightning_program.to_account_info();
+    let signer = ctx.accounts.fee_payer.to_account_info();
+
+    // Accumulate prices over the elapsed interval before reserves change
+    accumulate_prices(&inco_program, &signer, &mut pool_account, now)?;
+    let fee_bps = pool_account.effective_fee_bps(now);
+
+    // Zero the slice if the remaining budget cannot cover it
+    let zero = as_euint128(operation(&inco_program, &signer), 0)?;
+
+    let has_budget: Ebool = e_ge(
+        operation(&inco_program, &signer),
+        order_account.remaining_budget,
+        order_account.amount_per_interval,
+        SCALAR_BYTE,
+    )?;
+
+    let mut slice_in = e_select(
+        operation(&inco_program, &signer),
+        has_budget,
+        order_account.amount_per_interval,
+        zero,
+        SCALAR_BYTE,
+    )?;
+
+    let mut slice_out = e_select(
+        operation(&inco_program, &signer),
+        has_budget,
+        order_account.min_amount_out,
+        zero,
+        SCALAR_BYTE,
+    )?;
+
+    // The pool fee is taken out of the slice; a slice outside the oracle band is zeroed
+    // and fails the checks below
+    let mut fee_amount = compute_fee(&inco_program, &signer, slice_in, fee_bps)?;
+    if let Some(price_q32) = pool_oracle_price(&pool_account, ctx.accounts.oracle.as_deref(), now)? {
+        enforce_price_band(
+            &inco_program,
+            &signer,
+            price_q32,
+            pool_account.oracle_max_deviation_bps,
+            a_to_b,
+            &mut slice_in,
+            &mut slice_out,
+            &mut fee_amount,
+        )?;
+    }
+
+    let (reserve_in, reserve_out, protocol_fee_in) = pool_account.swap_side(a_to_b);
+    let curve = load_swap_curve(
+        &inco_program,
+        &signer,
+        &pool_account,
+        &invariant_ciphertext,
+        order_account.input_type,
+        a_to_b,
+        now,
+    )?;
+    let outflow_limit =
+        load_outflow_limit(&inco_program, &signer, &mut pool_account, a_to_b, now)?;
+    let mut updates = compute_swap_updates(
+        &inco_program,
+        &signer,
+        reserve_in,
+        reserve_out,
+        protocol_fee_in,
+        slice_in,
+        slice_out,
+        fee_amount,
+        fee_bps,
+        curve,
+        pool_account.protocol_fee_share_bps,
+        outflow_limit,
+    )?;
+
+    // The slice passes when the pool takes the full slice and pays its full output
+    let took_slice: Ebool = e_ge(
+        operation(&inco_program, &signer),
+        updates.amount_in,
+        order_account.amount_per_interval,
+        SCALAR_BYTE,
+    )?;
+
+    let paid_slice: Ebool = e_ge(
+        operation(&inco_program, &signer),
+        updates.amount_out,
+        order_account.min_amount_out,
+        SCALAR_BYTE,
+    )?;
+
+    let approved = e_all(&inco_program, &signer, &[has_budget, took_slice, paid_slice])?;
+    gate_swap_updates(&inco_program, &signer, approved, &pool_account, a_to_b, &mut updates)?;
+    pool_account.apply_swap_updates(&updates, a_to_b);
+    emit!(SwapEvent {
+        pool_address: pool_meta.address,
+        a_to_b,
+        fee_bps,
+        timestamp: now,
+    });
+    pool_account.last_update_ts = now;
+    pool_account.bump_state_nonce();
+
+    // Only an approved slice is spent from the budget
+    let spent = e_select(
+        operation(&inco_program, &signer),
+        approved,
+        order_account.amount_per_interval,
+        zero,
+        SCALAR_BYTE,
+    )?;
+
+    order_account.remaining_budget = e_sub(
+        operation(&inco_program, &signer),
+        order_account.remaining_budget,
+        spent,
+        SCALAR_BYTE,
+    )?;
+    order_account.executions_remaining -= 1;
+    order_account.last_execution_ts = now;
+
+    // On approval, move the slice from escrow into the pool and pay its output
+    let escrow_seeds = vec![
+        DCA_ESCROW_SEED.to_vec(),
+        order_meta.address.to_vec(),
+        vec![escrow_bump],
+    ];
+    let on_approved = vec![
+        PayoutLeg::transfer(
+            escrow_seeds,
+            ctx.accounts.escrow_vault_in.key(),
+            vault_in,
+            order_account.amount_per_interval_ciphertext.clone(),
+        ),
+        PayoutLeg::transfer(
+            pool_account.authority_seeds(),
+            vault_out,
+            ctx.accounts.owner_token_out.key(),
+            order_account.min_amount_out_ciphertext.clone(),
+        ),
+    ];
+
+    // The owner decrypts the outcome to settle the payout
+    allow_payout_outcome(
+        &inco_program,
+        &signer,
+        &ctx.accounts.system_program.to_account_info(),
+        &ctx.accounts.owner.to_account_info(),
+        &[(approved, ctx.accounts.approved_allowance.to_account_info())],
+    )?;
+
+    let tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|error| ProgramError::Custom(error.into()))?;
+    let owner = order_account.owner;
+    let (address, address_seed) = derive_address(
+        &[PAYOUT_SEED, owner.as_ref(), &payout_nonce.to_le_bytes()],
+        &tree_pubkey,
+        &crate::ID,
+    );
+    let new_address_params =
+        address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(2));
+
+    let mut payout_account = LightAccount::<PendingPayout>::new_init(
+        &crate::ID,
+        Some(address),
+        output_tree_index,
+    );
+    payout_account.beneficiary = owner;
+    payout_account.input_type = order_account.input_type;
+    payout_account.approved = approved;
+    payout_account.on_approved = on_approved;
+    payout_account.created_at = now;
+
+    // Commit pool and order updates and the payout
+    LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
+        .with_light_account(pool_account)?
+        .with_light_account(order_account)?
+        .with_light_account(payout_account)?
+        .with_new_addresses(&[new_address_params])
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn handle_cancel_dca_order<'info>(
+    ctx: Context<'_, '_, '_, 'info, CancelDcaOrder<'info>>,
+    proof: SdkValidityProof,
+    order_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+    order_data: Vec<u8>,
+    refund_ciphertext: Vec<u8>,
+    input_type: u8,
+    address_tree_info: PackedAddressTreeInfo,
+    output_tree_index: u8,
+    payout_nonce: u64,
+) -> Result<()> {
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.fee_payer.as_ref(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let order_state = DcaOrder::try_from_slice(&order_data)?;
+    let mut order_account = LightAccount::<DcaOrder>::new_mut(
+        &crate::ID,
+        &order_meta,
+        order_state,
+    )?;
+
+    let owner = ctx.accounts.owner.key();
+    require_keys_eq!(order_account.owner, owner, ErrorCode::Unauthorized);
+
+    let (escrow_authority, escrow_bump) =
+        Pubkey::find_program_address(&[DCA_ESCROW_SEED, order_meta.address.as_ref()], &crate::ID);
+    let mint_in = if order_account.a_to_b { order_account.mint_a } else { order_account.mint_b };
+    require!(
+        ctx.accounts.escrow_vault_in.owner == escrow_authority
+            && ctx.accounts.escrow_vault_in.mint == mint_in,
+        ErrorCode::InvalidEscrowVault
+    );
+    require_keys_eq!(ctx.accounts.owner_token_in.mint, mint_in, ErrorCode::InvalidInputMint);
+
+    let inco_program = ctx.accounts.inco_lightning_program.to_account_info();
+    let signer = ctx.accounts.fee_payer.to_account_info();
+
+    // The refund is approved and debited only if the unspent budget covers it
+    let refund =
+        new_euint128(operation(&inco_program, &signer), refund_ciphertext.clone(), input_type)?;
+
+    let approved: Ebool = e_ge(
+        operation(&inco_program, &signer),
+        order_account.remaining_budget,
+        refund,
+        SCALAR_BYTE,
+    )?;
+
+    let zero = as_euint128(operation(&inco_program, &signer), 0)?;
+
+    let debit =
+        e_select(operation(&inco_program, &signer), approved, refund, zero, SCALAR_BYTE)?;
+
+    order_account.remaining_budget = e_sub(
+        operation(&inco_program, &signer),
+        order_account.remaining_budget,
+        debit,
+        SCALAR_BYTE,
+    )?;
+    order_account.executions_remaining = 0;
+
+    let escrow_seeds = vec![
+        DCA_ESCROW_SEED.to_vec(),
+        order_meta.address.to_vec(),
+        vec![escrow_bump],
+    ];
+    let on_approved = vec![PayoutLeg::transfer(
+        escrow_seeds,
+        ctx.accounts.escrow_vault_in.key(),
+        ctx.accounts.owner_token_in.key(),
+        refund_ciphertext,
+    )];
+
+    // The owner decrypts the outcome to settle the payout
+    allow_payout_outcome(
+        &inco_program,
+        &signer,
+        &ctx.accounts.system_program.to_account_info(),
+        &ctx.accounts.owner.to_account_info(),
+        &[(approved, ctx.accounts.approved_allowance.to_account_info())],
+    )?;
+
+    let tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|error| ProgramError::Custom(error.into()))?;
+    let (address, address_seed) = derive_address(
+        &[PAYOUT_SEED, owner.as_ref(), &payout_nonce.to_le_bytes()],
+        &tree_pubkey,
+        &crate::ID,
+    );
+    let new_address_params =
+        address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(1));
+
+    let mut payout_account = LightAccount::<PendingPayout>::new_init(
+        &crate::ID,
+        Some(address),
+        output_tree_index,
+    );
+    payout_account.beneficiary = owner;
+    payout_account.input_type = input_type;
+    payout_account.approved = approved;
+    payout_account.on_approved = on_approved;
+    payout_account.created_at = Clock::get()?.unix_timestamp;
+
+    LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
+        .with_light_account(order_account)?
+        .with_light_account(payout_account)?
+        .with_new_addresses(&[new_address_params])
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
@@ -0,0 +1,320 @@
+//! Time-locked emergency withdrawal from a paused pool
+
+use anchor_lang::prelude::*;
+use inco_lightning::cpi::accounts::Allow;
+use inco_lightning::cpi::{allow, as_euint128, e_add, e_ge, e_select, new_euint128};
+use inco_lightning::ID as INCO_LIGHTNING_ID;
+use light_sdk::{
+    account::LightAccount,
+    address::v2::derive_address,
+    cpi::v2::{CpiAccounts, LightSystemProgramCpi},
+    cpi::{InvokeLightSystemProgram, LightCpiInstruction},
+    instruction::{
+        PackedAddressTreeInfo, PackedAddressTreeInfoExt, ValidityProof as SdkValidityProof,
+    },
+};
+
+use super::update_pool_config::UpdatePoolConfig;
+use crate::constants::*;
+use crate::errors::ErrorCode;
+use crate::fhe::*;
+use crate::inco_token::IncoAccount;
+use crate::payout::*;
+use crate::state::*;
+
+#[derive(Accounts)]
+pub struct GrantWithdrawalAccess<'info> {
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+    pub authority: Signer<'info>,
+    /// CHECK: Inco allowance PDA for (reserve_a handle, authority)
+    #[account(mut)]
+    pub reserve_a_allowance: AccountInfo<'info>,
+    /// CHECK: Inco allowance PDA for (reserve_b handle, authority)
+    #[account(mut)]
+    pub reserve_b_allowance: AccountInfo<'info>,
+    /// CHECK: Inco allowance PDA for (protocol_fee_a handle, authority)
+    #[account(mut)]
+    pub protocol_fee_a_allowance: AccountInfo<'info>,
+    /// CHECK: Inco allowance PDA for (protocol_fee_b handle, authority)
+    #[account(mut)]
+    pub protocol_fee_b_allowance: AccountInfo<'info>,
+    /// CHECK: Inco Lightning program for encrypted operations
+    #[account(address = INCO_LIGHTNING_ID)]
+    pub inco_lightning_program: AccountInfo<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct EmergencyWithdraw<'info> {
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+    /// Pool authority; decrypts the payout outcome
+    pub authority: Signer<'info>,
+    /// Pool vault for token A (owned by pool_authority)
+    pub pool_vault_a: Account<'info, IncoAccount>,
+    /// Pool vault for token B (owned by pool_authority)
+    pub pool_vault_b: Account<'info, IncoAccount>,
+    /// Inco token account receiving token A
+    pub recipient_token_a: Account<'info, IncoAccount>,
+    /// Inco token account receiving token B
+    pub recipient_token_b: Account<'info, IncoAccount>,
+    /// CHECK: Inco allowance PDA for (payout approved handle, authority)
+    #[account(mut)]
+    pub approved_allowance: AccountInfo<'info>,
+    /// CHECK: Inco Lightning program for encrypted operations
+    #[account(address = INCO_LIGHTNING_ID)]
+    pub inco_lightning_program: AccountInfo<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handle_set_emergency_delay<'info>(
+    ctx: Context<'_, '_, '_, 'info, UpdatePoolConfig<'info>>,
+    proof: SdkValidityProof,
+    pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+    pool_data: Vec<u8>,
+    emergency_delay_secs: u32,
+) -> Result<()> {
+    require!(
+        emergency_delay_secs as i64 >= UNPAUSE_DELAY_SECS,
+        ErrorCode::InvalidEmergencyDelay
+    );
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.fee_payer.as_ref(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let pool_state = SwapPool::load(&pool_data)?;
+    let mut pool_account = LightAccount::<SwapPool>::new_mut(
+        &crate::ID,
+        &pool_meta,
+        pool_state,
+    )?;
+
+    require_keys_eq!(pool_account.authority, ctx.accounts.authority.key(), ErrorCode::Unauthorized);
+    require!(!pool_account.is_paused, ErrorCode::PoolPaused);
+    pool_account.emergency_delay_secs = emergency_delay_secs;
+
+    // Commit pool state update
+    LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
+        .with_light_account(pool_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn handle_grant_withdrawal_access<'info>(
+    ctx: Context<'_, '_, '_, 'info, GrantWithdrawalAccess<'info>>,
+    proof: SdkValidityProof,
+    pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+    pool_data: Vec<u8>,
+) -> Result<()> {
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.fee_payer.as_ref(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    // Re-committed unchanged so the validity proof covers the handles granted here
+    let pool_state = SwapPool::load(&pool_data)?;
+    let pool_account = LightAccount::<SwapPool>::new_mut(
+        &crate::ID,
+        &pool_meta,
+        pool_state,
+    )?;
+
+    require_keys_eq!(pool_account.authority, ctx.accounts.authority.key(), ErrorCode::Unauthorized);
+    require!(pool_account.is_paused, ErrorCode::PoolNotPaused);
+    let now = Clock::get()?.unix_timestamp;
+    require!(
+        now >= pool_account.paused_at_ts + pool_account.emergency_delay_secs as i64,
+        ErrorCode::EmergencyDelayNotElapsed
+    );
+
+    for (handle, allowance) in [
+        (pool_account.reserve_a, &ctx.accounts.reserve_a_allowance),
+        (pool_account.reserve_b, &ctx.accounts.reserve_b_allowance),
+        (pool_account.protocol_fee_a, &ctx.accounts.protocol_fee_a_allowance),
+        (pool_account.protocol_fee_b, &ctx.accounts.protocol_fee_b_allowance),
+    ] {
+        let cpi_ctx = CpiContext::new(
+            ctx.accounts.inco_lightning_program.to_account_info(),
+            Allow {
+                allowance_account: allowance.to_account_info(),
+                signer: ctx.accounts.fee_payer.to_account_info(),
+                allowed_address: ctx.accounts.authority.to_account_info(),
+                system_program: ctx.accounts.system_program.to_account_info(),
+            },
+        );
+        allow(cpi_ctx, handle.0, true, ctx.accounts.authority.key())?;
+    }
+
+    LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
+        .with_light_account(pool_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn handle_emergency_withdraw<'info>(
+    ctx: Context<'_, '_, '_, 'info, EmergencyWithdraw<'info>>,
+    proof: SdkValidityProof,
+    pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+    pool_data: Vec<u8>,
+    amount_a_ciphertext: Vec<u8>,
+    amount_b_ciphertext: Vec<u8>,
+    input_type: u8,
+    address_tree_info: PackedAddressTreeInfo,
+    output_tree_index: u8,
+    payout_nonce: u64,
+) -> Result<()> {
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.fee_payer.as_ref(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let pool_state = SwapPool::load(&pool_data)?;
+    let mut pool_account = LightAccount::<SwapPool>::new_mut(
+        &crate::ID,
+        &pool_meta,
+        pool_state,
+    )?;
+
+    let authority = ctx.accounts.authority.key();
+    require_keys_eq!(pool_account.authority, authority, ErrorCode::Unauthorized);
+    require!(pool_account.is_paused, ErrorCode::PoolNotPaused);
+    let now = Clock::get()?.unix_timestamp;
+    require!(
+        now >= pool_account.paused_at_ts + pool_account.emergency_delay_secs as i64,
+        ErrorCode::EmergencyDelayNotElapsed
+    );
+
+    // Verify vaults and recipients
+    require_keys_eq!(ctx.accounts.pool_vault_a.key(), pool_account.vault_a, ErrorCode::InvalidPoolVault);
+    require_keys_eq!(ctx.accounts.pool_vault_b.key(), pool_account.vault_b, ErrorCode::InvalidPoolVault);
+    require_keys_eq!(ctx.accounts.recipient_token_a.mint, pool_account.mint_a, ErrorCode::InvalidOutputMint);
+    require_keys_eq!(ctx.accounts.recipient_token_b.mint, pool_account.mint_b, ErrorCode::InvalidOutputMint);
+
+    let inco_program = ctx.accounts.inco_lightning_program.to_account_info();
+    let signer = ctx.accounts.fee_payer.to_account_info();
+
+    let amount_a = new_euint128(
+        operation(&inco_program, &signer),
+        amount_a_ciphertext.clone(),
+        input_type,
+    )?;
+
+    let amount_b = new_euint128(
+        operation(&inco_program, &signer),
+        amount_b_ciphertext.clone(),
+        input_type,
+    )?;
+
+    // Each amount must equal reserve + protocol_fee of its side
+    let mut exact = Vec::with_capacity(4);
+    for (amount, reserve, protocol_fee) in [
+        (amount_a, pool_account.reserve_a, pool_account.protocol_fee_a),
+        (amount_b, pool_account.reserve_b, pool_account.protocol_fee_b),
+    ] {
+        let due = e_add(operation(&inco_program, &signer), reserve, protocol_fee, SCALAR_BYTE)?;
+
+        exact.push(e_ge(operation(&inco_program, &signer), amount, due, SCALAR_BYTE)?);
+
+        exact.push(e_ge(operation(&inco_program, &signer), due, amount, SCALAR_BYTE)?);
+    }
+    let approved = e_all(&inco_program, &signer, &exact)?;
+
+    let zero = as_euint128(operation(&inco_program, &signer), 0)?;
+
+    pool_account.reserve_a = e_select(
+        operation(&inco_program, &signer),
+        approved,
+        zero,
+        pool_account.reserve_a,
+        SCALAR_BYTE,
+    )?;
+
+    pool_account.reserve_b = e_select(
+        operation(&inco_program, &signer),
+        approved,
+        zero,
+        pool_account.reserve_b,
+        SCALAR_BYTE,
+    )?;
+
+    pool_account.protocol_fee_a = e_select(
+        operation(&inco_program, &signer),
+        approved,
+        zero,
+        pool_account.protocol_fee_a,
+        SCALAR_BYTE,
+    )?;
+
+    pool_account.protocol_fee_b = e_select(
+        operation(&inco_program, &signer),
+        approved,
+        zero,
+        pool_account.protocol_fee_b,
+        SCALAR_BYTE,
+    )?;
+    pool_account.last_update_ts = now;
+    pool_account.bump_state_nonce();
+
+    let on_approved = vec![
+        PayoutLeg::transfer(
+            pool_account.authority_seeds(),
+            pool_account.vault_a,
+            ctx.accounts.recipient_token_a.key(),
+            amount_a_ciphertext,
+        ),
+        PayoutLeg::transfer(
+            pool_account.authority_seeds(),
+            pool_account.vault_b,
+            ctx.accounts.recipient_token_b.key(),
+            amount_b_ciphertext,
+        ),
+    ];
+
+    // The authority decrypts the outcome to settle the payout
+    allow_payout_outcome(
+        &inco_program,
+        &signer,
+        &ctx.accounts.system_program.to_account_info(),
+        &ctx.accounts.authority.to_account_info(),
+        &[(approved, ctx.accounts.approved_allowance.to_account_info())],
+    )?;
+
+    let tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|error| ProgramError::Custom(error.into()))?;
+    let (address, address_seed) = derive_address(
+        &[PAYOUT_SEED, authority.as_ref(), &payout_nonce.to_le_bytes()],
+        &tree_pubkey,
+        &crate::ID,
+    );
+    let new_address_params =
+        address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(1));
+
+    let mut payout_account = LightAccount::<PendingPayout>::new_init(
+        &crate::ID,
+        Some(address),
+        output_tree_index,
+    );
+    payout_account.beneficiary = authority;
+    payout_account.input_type = input_type;
+    payout_account.approved = approved;
+    payout_account.on_approved = on_approved;
+    payout_account.created_at = now;
+
+    // Commit the pool state update and the payout
+    LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
+        .with_light_account(pool_account)?
+        .with_light_account(payout_account)?
+        .with_new_addresses(&[new_address_params])
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
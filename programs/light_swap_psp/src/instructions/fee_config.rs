@@ -0,0 +1,100 @@
+//! Protocol fee share and dynamic fee configuration
+
+use anchor_lang::prelude::*;
+use light_sdk::{
+    account::LightAccount,
+    cpi::v2::{CpiAccounts, LightSystemProgramCpi},
+    cpi::{InvokeLightSystemProgram, LightCpiInstruction},
+    instruction::ValidityProof as SdkValidityProof,
+};
+
+use super::update_pool_config::UpdatePoolConfig;
+use crate::constants::*;
+use crate::errors::ErrorCode;
+use crate::state::*;
+
+pub fn handle_set_protocol_fee_share<'info>(
+    ctx: Context<'_, '_, '_, 'info, UpdatePoolConfig<'info>>,
+    proof: SdkValidityProof,
+    pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+    pool_data: Vec<u8>,
+    protocol_fee_share_bps: u16,
+) -> Result<()> {
+    require!(
+        protocol_fee_share_bps as u128 <= BPS_DENOMINATOR,
+        ErrorCode::InvalidProtocolFeeShare
+    );
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.fee_payer.as_ref(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let pool_state = SwapPool::load(&pool_data)?;
+    let mut pool_account = LightAccount::<SwapPool>::new_mut(
+        &crate::ID,
+        &pool_meta,
+        pool_state,
+    )?;
+
+    require_keys_eq!(pool_account.authority, ctx.accounts.authority.key(), ErrorCode::Unauthorized);
+    pool_account.protocol_fee_share_bps = protocol_fee_share_bps;
+
+    // Commit pool state update
+    LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
+        .with_light_account(pool_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn handle_set_dynamic_fee<'info>(
+    ctx: Context<'_, '_, '_, 'info, UpdatePoolConfig<'info>>,
+    proof: SdkValidityProof,
+    pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+    pool_data: Vec<u8>,
+    enabled: bool,
+    min_fee_bps: u16,
+    max_fee_bps: u16,
+    volatility_step_bps: u16,
+    volatility_half_life_secs: u32,
+) -> Result<()> {
+    if enabled {
+        require!(
+            min_fee_bps <= max_fee_bps
+                && max_fee_bps as u128 <= BPS_DENOMINATOR
+                && volatility_half_life_secs > 0,
+            ErrorCode::InvalidDynamicFee
+        );
+    }
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.fee_payer.as_ref(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let pool_state = SwapPool::load(&pool_data)?;
+    let mut pool_account = LightAccount::<SwapPool>::new_mut(
+        &crate::ID,
+        &pool_meta,
+        pool_state,
+    )?;
+
+    require_keys_eq!(pool_account.authority, ctx.accounts.authority.key(), ErrorCode::Unauthorized);
+    pool_account.dynamic_fee_enabled = enabled;
+    pool_account.min_fee_bps = min_fee_bps;
+    pool_account.max_fee_bps = max_fee_bps;
+    pool_account.volatility_step_bps = volatility_step_bps;
+    pool_account.volatility_half_life_secs = volatility_half_life_secs;
+    pool_account.volatility_accumulator = 0;
+    pool_account.volatility_updated_ts = Clock::get()?.unix_timestamp;
+
+    // Commit pool state update
+    LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
+        .with_light_account(pool_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
@@ -0,0 +1,357 @@
+//! Flash swaps repaid within the same transaction
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::solana_program::program::invoke;
+use inco_lightning::cpi::{as_euint128, e_add, e_ge, e_select, e_sub, new_euint128};
+use inco_lightning::types::{Ebool, Euint128};
+use inco_lightning::ID as INCO_LIGHTNING_ID;
+use light_sdk::{
+    account::LightAccount,
+    cpi::v2::{CpiAccounts, LightSystemProgramCpi},
+    cpi::{InvokeLightSystemProgram, LightCpiInstruction},
+    instruction::ValidityProof as SdkValidityProof,
+};
+
+use super::update_pool_config::UpdatePoolConfig;
+use crate::constants::*;
+use crate::curve::*;
+use crate::errors::ErrorCode;
+use crate::events::*;
+use crate::fhe::*;
+use crate::inco_token::cpi::accounts::IncoTransfer;
+use crate::inco_token::cpi::transfer as inco_token_transfer;
+use crate::inco_token::{IncoAccount, ID as INCO_TOKEN_ID};
+use crate::oracle::*;
+use crate::state::*;
+
+#[derive(Accounts)]
+pub struct FlashSwap<'info> {
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+    /// CHECK: Pool authority PDA, verified against the pool mints (mut required for CPI)
+    #[account(mut)]
+    pub pool_authority: AccountInfo<'info>,
+    /// Borrower approved by the pool authority (`flash_borrower`)
+    pub borrower: Signer<'info>,
+    /// Borrower's Inco token account receiving the lent output
+    #[account(mut, constraint = borrower_token_out.owner == borrower.key() @ ErrorCode::Unauthorized)]
+    pub borrower_token_out: Account<'info, IncoAccount>,
+    /// Pool vault receiving the repayment (owned by pool_authority)
+    #[account(mut)]
+    pub pool_vault_in: Account<'info, IncoAccount>,
+    /// Pool vault lending the output (owned by pool_authority)
+    #[account(mut)]
+    pub pool_vault_out: Account<'info, IncoAccount>,
+    /// CHECK: Borrower's program invoked between lending and settlement
+    #[account(executable)]
+    pub callback_program: AccountInfo<'info>,
+    /// CHECK: Price feed account, verified against the pool's configured oracle; only
+    /// required when the pool has an oracle price band
+    pub oracle: Option<UncheckedAccount<'info>>,
+    /// CHECK: Inco Lightning program for encrypted operations
+    #[account(address = INCO_LIGHTNING_ID)]
+    pub inco_lightning_program: AccountInfo<'info>,
+    /// CHECK: Inco Token program for token transfers
+    #[account(address = INCO_TOKEN_ID)]
+    pub inco_token_program: AccountInfo<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handle_set_flash_borrower<'info>(
+    ctx: Context<'_, '_, '_, 'info, UpdatePoolConfig<'info>>,
+    proof: SdkValidityProof,
+    pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+    pool_data: Vec<u8>,
+    borrower: Pubkey,
+) -> Result<()> {
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.fee_payer.as_ref(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let pool_state = SwapPool::load(&pool_data)?;
+    let mut pool_account = LightAccount::<SwapPool>::new_mut(
+        &crate::ID,
+        &pool_meta,
+        pool_state,
+    )?;
+
+    require_keys_eq!(pool_account.authority, ctx.accounts.authority.key(), ErrorCode::Unauthorized);
+    require!(borrower != crate::ID, ErrorCode::InvalidFlashCallback);
+    pool_account.flash_borrower = borrower;
+
+    // Commit pool state update
+    LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
+        .with_light_account(pool_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn handle_flash_swap<'info>(
+    ctx: Context<'_, '_, '_, 'info, FlashSwap<'info>>,
+    proof: SdkValidityProof,
+    pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+    pool_data: Vec<u8>,
+    amount_out_ciphertext: Vec<u8>,
+    fee_amount_ciphertext: Vec<u8>,
+    invariant_ciphertext: Vec<u8>,
+    input_type: u8,
+    a_to_b: bool,
+    callback_data: Vec<u8>,
+    callback_accounts_len: u8,
+) -> Result<()> {
+    let callback_accounts_len = callback_accounts_len as usize;
+    require!(
+        ctx.remaining_accounts.len() >= callback_accounts_len,
+        ErrorCode::InvalidFlashCallback
+    );
+    let (callback_accounts, light_accounts) =
+        ctx.remaining_accounts.split_at(callback_accounts_len);
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.fee_payer.as_ref(),
+        light_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let pool_state = SwapPool::load(&pool_data)?;
+    let mut pool_account = LightAccount::<SwapPool>::new_mut(
+        &crate::ID,
+        &pool_meta,
+        pool_state,
+    )?;
+
+    require!(!pool_account.is_paused, ErrorCode::PoolPaused);
+    require!(!pool_account.batch_mode, ErrorCode::BatchModeActive);
+    require!(
+        pool_account.flash_borrower != Pubkey::default(),
+        ErrorCode::FlashSwapsDisabled
+    );
+    require_keys_eq!(
+        ctx.accounts.borrower.key(),
+        pool_account.flash_borrower,
+        ErrorCode::Unauthorized
+    );
+    require!(
+        ctx.accounts.callback_program.key() != crate::ID,
+        ErrorCode::InvalidFlashCallback
+    );
+
+    // Verify pool authority, vaults and the borrower's output account
+    let pool_authority = pool_account.pool_authority;
+    let bump = pool_account.pool_authority_bump;
+    require_keys_eq!(ctx.accounts.pool_authority.key(), pool_authority);
+    let (vault_in, vault_out) = pool_account.swap_vaults(a_to_b);
+    require_keys_eq!(ctx.accounts.pool_vault_in.key(), vault_in, ErrorCode::InvalidPoolVault);
+    require_keys_eq!(ctx.accounts.pool_vault_out.key(), vault_out, ErrorCode::InvalidPoolVault);
+    let (_, mint_out) = pool_account.swap_mints(a_to_b);
+    require_keys_eq!(ctx.accounts.borrower_token_out.mint, mint_out, ErrorCode::InvalidOutputMint);
+
+    let inco_program = ctx.accounts.inco_lightning_program.to_account_info();
+    let inco_token_program = ctx.accounts.inco_token_program.to_account_info();
+    let signer = ctx.accounts.fee_payer.to_account_info();
+
+    // Accumulate prices over the elapsed interval before reserves change
+    let now = Clock::get()?.unix_timestamp;
+    accumulate_prices(&inco_program, &signer, &mut pool_account, now)?;
+    let fee_bps = pool_account.effective_fee_bps(now);
+
+    let balance_before = ctx.accounts.pool_vault_in.amount;
+    let lend_balance_before = ctx.accounts.pool_vault_out.amount;
+
+    let requested_out = new_euint128(
+        operation(&inco_program, &signer),
+        amount_out_ciphertext.clone(),
+        input_type,
+    )?;
+    let mut amount_out = requested_out;
+
+    let mut fee_amount =
+        new_euint128(operation(&inco_program, &signer), fee_amount_ciphertext, input_type)?;
+
+    // CPI: Lend amount_out from pool vault to the borrower (pool authority PDA signs)
+    let mint_a_key = pool_account.mint_a;
+    let mint_b_key = pool_account.mint_b;
+    let pool_auth_seeds: &[&[u8]] = &[
+        POOL_AUTH_SEED,
+        mint_a_key.as_ref(),
+        mint_b_key.as_ref(),
+        &[bump],
+    ];
+    let signer_seeds = &[pool_auth_seeds];
+    let transfer_out_ctx = CpiContext::new_with_signer(
+        inco_token_program,
+        IncoTransfer {
+            source: ctx.accounts.pool_vault_out.to_account_info(),
+            destination: ctx.accounts.borrower_token_out.to_account_info(),
+            authority: ctx.accounts.pool_authority.to_account_info(),
+            inco_lightning_program: inco_program.clone(),
+            system_program: ctx.accounts.system_program.to_account_info(),
+        },
+        signer_seeds,
+    );
+    inco_token_transfer(transfer_out_ctx, amount_out_ciphertext, input_type)?;
+    ctx.accounts.pool_vault_out.reload()?;
+
+    let lent = e_sub(
+        operation(&inco_program, &signer),
+        lend_balance_before,
+        ctx.accounts.pool_vault_out.amount,
+        SCALAR_BYTE,
+    )?;
+
+    // Hand control to the borrower, who must repay into pool_vault_in
+    let callback_ix = Instruction {
+        program_id: ctx.accounts.callback_program.key(),
+        accounts: callback_accounts
+            .iter()
+            .map(|account| AccountMeta {
+                pubkey: account.key(),
+                is_signer: account.is_signer,
+                is_writable: account.is_writable,
+            })
+            .collect(),
+        data: callback_data,
+    };
+    let mut callback_infos = callback_accounts.to_vec();
+    callback_infos.push(ctx.accounts.callback_program.to_account_info());
+    invoke(&callback_ix, &callback_infos)?;
+
+    // Repayment is the encrypted growth of the input vault across the callback;
+    // the fee is carved out of it
+    ctx.accounts.pool_vault_in.reload()?;
+    let repaid = e_sub(
+        operation(&inco_program, &signer),
+        ctx.accounts.pool_vault_in.amount,
+        balance_before,
+        SCALAR_BYTE,
+    )?;
+    let mut amount_in = repaid;
+    if let Some(price_q32) = pool_oracle_price(&pool_account, ctx.accounts.oracle.as_deref(), now)? {
+        enforce_price_band(
+            &inco_program,
+            &signer,
+            price_q32,
+            pool_account.oracle_max_deviation_bps,
+            a_to_b,
+            &mut amount_in,
+            &mut amount_out,
+            &mut fee_amount,
+        )?;
+    }
+
+    // Verify invariant plus fee before committing the pool
+    let (reserve_in, reserve_out, protocol_fee_in) = pool_account.swap_side(a_to_b);
+    let curve = load_swap_curve(
+        &inco_program,
+        &signer,
+        &pool_account,
+        &invariant_ciphertext,
+        input_type,
+        a_to_b,
+        now,
+    )?;
+    let outflow_limit =
+        load_outflow_limit(&inco_program, &signer, &mut pool_account, a_to_b, now)?;
+    let mut updates = compute_swap_updates(
+        &inco_program,
+        &signer,
+        reserve_in,
+        reserve_out,
+        protocol_fee_in,
+        amount_in,
+        amount_out,
+        fee_amount,
+        fee_bps,
+        curve,
+        pool_account.protocol_fee_share_bps,
+        outflow_limit,
+    )?;
+
+    // The repayment is accepted only if every check passed on the full requested output
+    let accepted: Ebool = e_ge(
+        operation(&inco_program, &signer),
+        updates.amount_out,
+        requested_out,
+        SCALAR_BYTE,
+    )?;
+    book_rejected_flash_loan(
+        &inco_program,
+        &signer,
+        accepted,
+        &mut pool_account,
+        a_to_b,
+        repaid,
+        lent,
+        &mut updates,
+    )?;
+    pool_account.apply_swap_updates(&updates, a_to_b);
+    emit!(SwapEvent {
+        pool_address: pool_meta.address,
+        a_to_b,
+        fee_bps,
+        timestamp: now,
+    });
+    pool_account.last_update_ts = now;
+    pool_account.bump_state_nonce();
+
+    // Commit pool state update to Light Protocol
+    LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
+        .with_light_account(pool_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+/// Settle a flash swap whose repayment was rejected. The lent output cannot be taken back,
+/// so instead of the (zeroed) swap updates the reserves book what actually moved: `repaid`
+/// joins reserve_in and `lent` leaves reserve_out, floored at zero. `lent` is added to the
+/// borrower's flash debt on the output side.
+pub(crate) fn book_rejected_flash_loan<'info>(
+    inco_program: &AccountInfo<'info>,
+    signer: &AccountInfo<'info>,
+    accepted: Ebool,
+    pool: &mut SwapPool,
+    a_to_b: bool,
+    repaid: Euint128,
+    lent: Euint128,
+    updates: &mut SwapUpdates,
+) -> Result<()> {
+    let (reserve_in, reserve_out, _) = pool.swap_side(a_to_b);
+
+    let zero = as_euint128(operation(inco_program, signer), 0)?;
+
+    let booked_in = e_add(operation(inco_program, signer), reserve_in, repaid, SCALAR_BYTE)?;
+
+    let covers_loan: Ebool = e_ge(operation(inco_program, signer), reserve_out, lent, SCALAR_BYTE)?;
+
+    let booked_out = e_sub(operation(inco_program, signer), reserve_out, lent, SCALAR_BYTE)?;
+
+    let booked_out =
+        e_select(operation(inco_program, signer), covers_loan, booked_out, zero, SCALAR_BYTE)?;
+
+    updates.new_reserve_in = e_select(
+        operation(inco_program, signer),
+        accepted,
+        updates.new_reserve_in,
+        booked_in,
+        SCALAR_BYTE,
+    )?;
+
+    updates.new_reserve_out = e_select(
+        operation(inco_program, signer),
+        accepted,
+        updates.new_reserve_out,
+        booked_out,
+        SCALAR_BYTE,
+    )?;
+
+    let shortfall = e_select(operation(inco_program, signer), accepted, zero, lent, SCALAR_BYTE)?;
+
+    let debt = if a_to_b { &mut pool.flash_debt_b } else { &mut pool.flash_debt_a };
+    *debt = e_add(operation(inco_program, signer), *debt, shortfall, SCALAR_BYTE)?;
+    Ok(())
+}
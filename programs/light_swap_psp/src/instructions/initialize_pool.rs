@@ -0,0 +1,221 @@
+//! Pool creation
+
+use anchor_lang::prelude::*;
+use inco_lightning::cpi::as_euint128;
+use inco_lightning::ID as INCO_LIGHTNING_ID;
+use light_sdk::{
+    account::LightAccount,
+    address::v2::derive_address,
+    cpi::v2::{CpiAccounts, LightSystemProgramCpi},
+    cpi::{InvokeLightSystemProgram, LightCpiInstruction},
+    instruction::{
+        PackedAddressTreeInfo, PackedAddressTreeInfoExt, ValidityProof as SdkValidityProof,
+    },
+};
+
+use crate::constants::*;
+use crate::errors::ErrorCode;
+use crate::fhe::*;
+use crate::inco_token::cpi::accounts::InitializeAccount as IncoInitializeAccount;
+use crate::inco_token::cpi::initialize_account as inco_token_initialize_account;
+use crate::inco_token::{IncoMint, ID as INCO_TOKEN_ID};
+use crate::state::*;
+
+#[derive(Accounts)]
+pub struct InitializePool<'info> {
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    /// CHECK: Pool authority PDA, verified against the mints; owns the new vaults
+    pub pool_authority: AccountInfo<'info>,
+    pub mint_a: Account<'info, IncoMint>,
+    pub mint_b: Account<'info, IncoMint>,
+    /// CHECK: Pool vault PDA for token A, created here
+    #[account(mut)]
+    pub pool_vault_a: AccountInfo<'info>,
+    /// CHECK: Pool vault PDA for token B, created here
+    #[account(mut)]
+    pub pool_vault_b: AccountInfo<'info>,
+    /// CHECK: Inco Lightning program for encrypted operations
+    #[account(address = INCO_LIGHTNING_ID)]
+    pub inco_lightning_program: AccountInfo<'info>,
+    /// CHECK: Inco Token program for vault creation
+    #[account(address = INCO_TOKEN_ID)]
+    pub inco_token_program: AccountInfo<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handle_initialize_pool<'info>(
+    ctx: Context<'_, '_, '_, 'info, InitializePool<'info>>,
+    proof: SdkValidityProof,
+    address_tree_info: PackedAddressTreeInfo,
+    output_tree_index: u8,
+    mint_a: Pubkey,
+    mint_b: Pubkey,
+    fee_bps: u16,
+    pool_kind: PoolKind,
+    amp: u64,
+    weight_a: u8,
+    weight_b: u8,
+    curve_scale_bits: u8,
+) -> Result<()> {
+    if pool_kind == PoolKind::StableSwap {
+        require!((MIN_AMP..=MAX_AMP).contains(&amp), ErrorCode::InvalidAmp);
+    }
+    if pool_kind == PoolKind::Weighted {
+        require!(
+            weight_a > 0
+                && weight_b > 0
+                && weight_a.checked_add(weight_b).is_some_and(|sum| sum <= MAX_WEIGHT_SUM),
+            ErrorCode::InvalidWeights
+        );
+    }
+    require!(curve_scale_bits < 128, ErrorCode::InvalidCurveScale);
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.fee_payer.as_ref(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|error| ProgramError::Custom(error.into()))?;
+    let (address, address_seed) =
+        derive_address(&[b"pool", mint_a.as_ref(), mint_b.as_ref()], &tree_pubkey, &crate::ID);
+    let new_address_params =
+        address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0));
+
+    // Registry entry so pools can be enumerated on-chain
+    let (index_address, index_address_seed) = derive_address(
+        &[POOL_INDEX_SEED, mint_a.as_ref(), mint_b.as_ref(), &fee_bps.to_le_bytes()],
+        &tree_pubkey,
+        &crate::ID,
+    );
+    let index_address_params =
+        address_tree_info.into_new_address_params_assigned_packed(index_address_seed, Some(1));
+
+    let mut pool_account = LightAccount::<SwapPool>::new_init(
+        &crate::ID,
+        Some(address),
+        output_tree_index,
+    );
+    let inco_program = ctx.accounts.inco_lightning_program.to_account_info();
+    let signer = ctx.accounts.fee_payer.to_account_info();
+    
+    pool_account.reserve_a = as_euint128(operation(&inco_program, &signer), 0)?;
+
+    pool_account.reserve_b = as_euint128(operation(&inco_program, &signer), 0)?;
+
+    pool_account.protocol_fee_a = as_euint128(operation(&inco_program, &signer), 0)?;
+
+    pool_account.protocol_fee_b = as_euint128(operation(&inco_program, &signer), 0)?;
+
+    pool_account.price_cumulative_a = as_euint128(operation(&inco_program, &signer), 0)?;
+
+    pool_account.price_cumulative_b = as_euint128(operation(&inco_program, &signer), 0)?;
+
+    pool_account.owed_a = as_euint128(operation(&inco_program, &signer), 0)?;
+
+    pool_account.owed_b = as_euint128(operation(&inco_program, &signer), 0)?;
+
+    pool_account.skimmed_a = as_euint128(operation(&inco_program, &signer), 0)?;
+
+    pool_account.skimmed_b = as_euint128(operation(&inco_program, &signer), 0)?;
+
+    pool_account.flash_debt_a = as_euint128(operation(&inco_program, &signer), 0)?;
+
+    pool_account.flash_debt_b = as_euint128(operation(&inco_program, &signer), 0)?;
+    
+    let (pool_authority, pool_authority_bump) = Pubkey::find_program_address(
+        &[POOL_AUTH_SEED, mint_a.as_ref(), mint_b.as_ref()],
+        &crate::ID,
+    );
+    require_keys_eq!(ctx.accounts.pool_authority.key(), pool_authority);
+    require_keys_eq!(ctx.accounts.mint_a.key(), mint_a, ErrorCode::InvalidInputMint);
+    require_keys_eq!(ctx.accounts.mint_b.key(), mint_b, ErrorCode::InvalidOutputMint);
+
+    // CPI: Create both vaults owned by the pool authority (vault PDAs sign)
+    for (vault, mint) in [
+        (ctx.accounts.pool_vault_a.to_account_info(), ctx.accounts.mint_a.to_account_info()),
+        (ctx.accounts.pool_vault_b.to_account_info(), ctx.accounts.mint_b.to_account_info()),
+    ] {
+        let (expected_vault, vault_bump) = Pubkey::find_program_address(
+            &[POOL_VAULT_SEED, pool_authority.as_ref(), mint.key.as_ref()],
+            &crate::ID,
+        );
+        require_keys_eq!(vault.key(), expected_vault, ErrorCode::InvalidPoolVault);
+        let vault_seeds: &[&[u8]] = &[
+            POOL_VAULT_SEED,
+            pool_authority.as_ref(),
+            mint.key.as_ref(),
+            &[vault_bump],
+        ];
+        let signer_seeds = &[vault_seeds];
+        let init_ctx = CpiContext::new_with_signer(
+            ctx.accounts.inco_token_program.to_account_info(),
+            IncoInitializeAccount {
+                account: vault,
+                mint,
+                owner: ctx.accounts.pool_authority.to_account_info(),
+                payer: ctx.accounts.fee_payer.to_account_info(),
+                system_program: ctx.accounts.system_program.to_account_info(),
+                inco_lightning_program: ctx.accounts.inco_lightning_program.to_account_info(),
+            },
+            signer_seeds,
+        );
+        inco_token_initialize_account(init_ctx)?;
+    }
+
+    pool_account.authority = ctx.accounts.authority.key();
+    pool_account.pool_authority = pool_authority;
+    pool_account.pool_authority_bump = pool_authority_bump;
+    pool_account.mint_a = mint_a;
+    pool_account.mint_b = mint_b;
+    pool_account.vault_a = ctx.accounts.pool_vault_a.key();
+    pool_account.vault_b = ctx.accounts.pool_vault_b.key();
+    pool_account.fee_bps = fee_bps;
+    pool_account.protocol_fee_share_bps = BPS_DENOMINATOR as u16;
+    pool_account.is_paused = false;
+    pool_account.emergency_delay_secs = DEFAULT_EMERGENCY_DELAY_SECS;
+    pool_account.last_update_ts = Clock::get()?.unix_timestamp;
+    pool_account.price_cumulative_ts = pool_account.last_update_ts;
+    pool_account.version = SWAP_POOL_VERSION;
+    pool_account.pool_kind = pool_kind;
+    if pool_kind == PoolKind::StableSwap {
+        pool_account.amp_initial = amp;
+        pool_account.amp_future = amp;
+        pool_account.amp_ramp_start_ts = pool_account.last_update_ts;
+        pool_account.amp_ramp_end_ts = pool_account.last_update_ts;
+    }
+    if pool_kind == PoolKind::Weighted {
+        pool_account.weight_a = weight_a;
+        pool_account.weight_b = weight_b;
+    }
+    if pool_kind != PoolKind::ConstantProduct {
+        pool_account.curve_scale_bits = curve_scale_bits;
+    }
+
+    let mut index_account = LightAccount::<PoolIndexEntry>::new_init(
+        &crate::ID,
+        Some(index_address),
+        output_tree_index,
+    );
+    index_account.pool_address = address;
+    index_account.pool_authority = pool_authority;
+    index_account.mint_a = mint_a;
+    index_account.mint_b = mint_b;
+    index_account.fee_bps = fee_bps;
+    index_account.pool_kind = pool_kind;
+    index_account.vault_a = pool_account.vault_a;
+    index_account.vault_b = pool_account.vault_b;
+    index_account.created_at = pool_account.last_update_ts;
+
+    LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
+        .with_light_account(pool_account)?
+        .with_light_account(index_account)?
+        .with_new_addresses(&[new_address_params, index_address_params])
+        .invoke(light_cpi_accounts)?;
+    Ok(())
+}
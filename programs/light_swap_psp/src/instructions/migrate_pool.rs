@@ -0,0 +1,173 @@
+//! Migration of pools stored in the legacy layout
+
+use anchor_lang::prelude::*;
+use inco_lightning::cpi::as_euint128;
+use inco_lightning::ID as INCO_LIGHTNING_ID;
+use light_sdk::{
+    account::LightAccount,
+    address::v2::derive_address,
+    cpi::v2::{CpiAccounts, LightSystemProgramCpi},
+    cpi::{InvokeLightSystemProgram, LightCpiInstruction},
+    instruction::account_meta::CompressedAccountMetaBurn,
+    instruction::{
+        PackedAddressTreeInfo, PackedAddressTreeInfoExt, ValidityProof as SdkValidityProof,
+    },
+};
+
+use crate::constants::*;
+use crate::errors::ErrorCode;
+use crate::fhe::*;
+use crate::inco_token::IncoAccount;
+use crate::state::*;
+
+#[derive(Accounts)]
+pub struct MigratePool<'info> {
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+    pub authority: Signer<'info>,
+    /// Existing pool vault for token A (owned by pool_authority)
+    pub pool_vault_a: Account<'info, IncoAccount>,
+    /// Existing pool vault for token B (owned by pool_authority)
+    pub pool_vault_b: Account<'info, IncoAccount>,
+    /// CHECK: Inco Lightning program for encrypted operations
+    #[account(address = INCO_LIGHTNING_ID)]
+    pub inco_lightning_program: AccountInfo<'info>,
+}
+
+pub fn handle_migrate_pool<'info>(
+    ctx: Context<'_, '_, '_, 'info, MigratePool<'info>>,
+    proof: SdkValidityProof,
+    pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+    pool_data: Vec<u8>,
+    address_tree_info: PackedAddressTreeInfo,
+) -> Result<()> {
+    require!(pool_data.len() == LegacySwapPool::LEN, ErrorCode::PoolAlreadyMigrated);
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.fee_payer.as_ref(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let legacy = LegacySwapPool::try_from_slice(&pool_data)?;
+    require_keys_eq!(legacy.authority, ctx.accounts.authority.key(), ErrorCode::Unauthorized);
+
+    let (pool_authority, pool_authority_bump) = Pubkey::find_program_address(
+        &[POOL_AUTH_SEED, legacy.mint_a.as_ref(), legacy.mint_b.as_ref()],
+        &crate::ID,
+    );
+    require_keys_eq!(legacy.pool_authority, pool_authority);
+
+    // Legacy vaults were created off-chain; record them after checking owner and mint
+    require!(
+        ctx.accounts.pool_vault_a.owner == legacy.pool_authority
+            && ctx.accounts.pool_vault_a.mint == legacy.mint_a,
+        ErrorCode::InvalidPoolVault
+    );
+    require!(
+        ctx.accounts.pool_vault_b.owner == legacy.pool_authority
+            && ctx.accounts.pool_vault_b.mint == legacy.mint_b,
+        ErrorCode::InvalidPoolVault
+    );
+
+    // The legacy account is consumed and re-created under the same address
+    let legacy_meta = CompressedAccountMetaBurn {
+        tree_info: pool_meta.tree_info,
+        address: pool_meta.address,
+    };
+    let legacy_account = LightAccount::<LegacySwapPool>::new_burn(
+        &crate::ID,
+        &legacy_meta,
+        legacy.clone(),
+    )?;
+    let mut pool_account = LightAccount::<SwapPool>::new_init(
+        &crate::ID,
+        Some(pool_meta.address),
+        pool_meta.output_state_tree_index,
+    );
+
+    // Legacy pools predate the registry; add their entry now
+    let tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|error| ProgramError::Custom(error.into()))?;
+    let (index_address, index_address_seed) = derive_address(
+        &[
+            POOL_INDEX_SEED,
+            legacy.mint_a.as_ref(),
+            legacy.mint_b.as_ref(),
+            &legacy.fee_bps.to_le_bytes(),
+        ],
+        &tree_pubkey,
+        &crate::ID,
+    );
+    let index_address_params =
+        address_tree_info.into_new_address_params_assigned_packed(index_address_seed, Some(2));
+
+    let inco_program = ctx.accounts.inco_lightning_program.to_account_info();
+    let signer = ctx.accounts.fee_payer.to_account_info();
+
+    pool_account.price_cumulative_a = as_euint128(operation(&inco_program, &signer), 0)?;
+
+    pool_account.price_cumulative_b = as_euint128(operation(&inco_program, &signer), 0)?;
+
+    pool_account.owed_a = as_euint128(operation(&inco_program, &signer), 0)?;
+
+    pool_account.owed_b = as_euint128(operation(&inco_program, &signer), 0)?;
+
+    pool_account.skimmed_a = as_euint128(operation(&inco_program, &signer), 0)?;
+
+    pool_account.skimmed_b = as_euint128(operation(&inco_program, &signer), 0)?;
+
+    pool_account.flash_debt_a = as_euint128(operation(&inco_program, &signer), 0)?;
+
+    pool_account.flash_debt_b = as_euint128(operation(&inco_program, &signer), 0)?;
+
+    let now = Clock::get()?.unix_timestamp;
+    pool_account.authority = legacy.authority;
+    pool_account.pool_authority = legacy.pool_authority;
+    pool_account.pool_authority_bump = pool_authority_bump;
+    pool_account.mint_a = legacy.mint_a;
+    pool_account.mint_b = legacy.mint_b;
+    pool_account.vault_a = ctx.accounts.pool_vault_a.key();
+    pool_account.vault_b = ctx.accounts.pool_vault_b.key();
+    pool_account.reserve_a = legacy.reserve_a;
+    pool_account.reserve_b = legacy.reserve_b;
+    pool_account.protocol_fee_a = legacy.protocol_fee_a;
+    pool_account.protocol_fee_b = legacy.protocol_fee_b;
+    pool_account.fee_bps = legacy.fee_bps;
+    pool_account.is_paused = legacy.is_paused;
+    // The emergency delay of an already paused pool runs from the migration
+    if legacy.is_paused {
+        pool_account.paused_at_ts = now;
+    }
+    pool_account.last_update_ts = legacy.last_update_ts;
+    pool_account.price_cumulative_ts = now;
+    pool_account.version = SWAP_POOL_VERSION;
+    pool_account.pool_kind = PoolKind::ConstantProduct;
+    pool_account.protocol_fee_share_bps = BPS_DENOMINATOR as u16;
+    pool_account.emergency_delay_secs = DEFAULT_EMERGENCY_DELAY_SECS;
+
+    let mut index_account = LightAccount::<PoolIndexEntry>::new_init(
+        &crate::ID,
+        Some(index_address),
+        pool_meta.output_state_tree_index,
+    );
+    index_account.pool_address = pool_meta.address;
+    index_account.pool_authority = legacy.pool_authority;
+    index_account.mint_a = legacy.mint_a;
+    index_account.mint_b = legacy.mint_b;
+    index_account.fee_bps = legacy.fee_bps;
+    index_account.pool_kind = PoolKind::ConstantProduct;
+    index_account.vault_a = pool_account.vault_a;
+    index_account.vault_b = pool_account.vault_b;
+    index_account.created_at = now;
+
+    LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
+        .with_light_account(legacy_account)?
+        .with_light_account(pool_account)?
+        .with_light_account(index_account)?
+        .with_new_addresses(&[index_address_params])
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
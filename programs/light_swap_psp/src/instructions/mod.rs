@@ -0,0 +1,53 @@
+pub mod add_liquidity;
+pub mod add_liquidity_single;
+pub mod add_liquidity_wrapped;
+pub mod batch;
+pub mod circuit_breaker;
+pub mod close_pool;
+pub mod dca;
+pub mod emergency_withdraw;
+pub mod fee_config;
+pub mod flash_swap;
+pub mod initialize_pool;
+pub mod limit_order;
+pub mod migrate_pool;
+pub mod observations;
+pub mod oracle_guard;
+pub mod pause;
+pub mod proof_of_reserves;
+pub mod ramp_amp;
+pub mod reconcile;
+pub mod referral;
+pub mod remove_liquidity;
+pub mod settle_payout;
+pub mod swap_exact_in;
+pub mod swap_route;
+pub mod unwrap;
+pub mod update_pool_config;
+
+pub use add_liquidity::*;
+pub use add_liquidity_single::*;
+pub use add_liquidity_wrapped::*;
+pub use batch::*;
+pub use circuit_breaker::*;
+pub use close_pool::*;
+pub use dca::*;
+pub use emergency_withdraw::*;
+pub use fee_config::*;
+pub use flash_swap::*;
+pub use initialize_pool::*;
+pub use limit_order::*;
+pub use migrate_pool::*;
+pub use observations::*;
+pub use oracle_guard::*;
+pub use pause::*;
+pub use proof_of_reserves::*;
+pub use ramp_amp::*;
+pub use reconcile::*;
+pub use referral::*;
+pub use remove_liquidity::*;
+pub use settle_payout::*;
+pub use swap_exact_in::*;
+pub use swap_route::*;
+pub use unwrap::*;
+pub use update_pool_config::*;
@@ -0,0 +1,215 @@
+//! Encrypted price observations for TWAP queries
+
+use anchor_lang::prelude::*;
+use inco_lightning::cpi::accounts::Allow;
+use inco_lightning::cpi::allow;
+use inco_lightning::ID as INCO_LIGHTNING_ID;
+use light_sdk::{
+    account::LightAccount,
+    address::v2::derive_address,
+    cpi::v2::{CpiAccounts, LightSystemProgramCpi},
+    cpi::{InvokeLightSystemProgram, LightCpiInstruction},
+    instruction::{
+        PackedAddressTreeInfo, PackedAddressTreeInfoExt, ValidityProof as SdkValidityProof,
+    },
+};
+
+use crate::constants::*;
+use crate::curve::*;
+use crate::errors::ErrorCode;
+use crate::state::*;
+
+#[derive(Accounts)]
+pub struct InitializeObservations<'info> {
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RecordObservation<'info> {
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+    /// CHECK: Inco Lightning program for encrypted operations
+    #[account(address = INCO_LIGHTNING_ID)]
+    pub inco_lightning_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct GrantPriceAccess<'info> {
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+    pub authority: Signer<'info>,
+    /// CHECK: Address granted decryption access to the observation
+    pub reader: AccountInfo<'info>,
+    /// CHECK: Inco allowance PDA for (price_cumulative_a handle, reader)
+    #[account(mut)]
+    pub allowance_a: AccountInfo<'info>,
+    /// CHECK: Inco allowance PDA for (price_cumulative_b handle, reader)
+    #[account(mut)]
+    pub allowance_b: AccountInfo<'info>,
+    /// CHECK: Inco Lightning program for encrypted operations
+    #[account(address = INCO_LIGHTNING_ID)]
+    pub inco_lightning_program: AccountInfo<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handle_initialize_observations<'info>(
+    ctx: Context<'_, '_, '_, 'info, InitializeObservations<'info>>,
+    proof: SdkValidityProof,
+    address_tree_info: PackedAddressTreeInfo,
+    output_tree_index: u8,
+    pool_address: [u8; 32],
+) -> Result<()> {
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.fee_payer.as_ref(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|error| ProgramError::Custom(error.into()))?;
+    let (address, address_seed) =
+        derive_address(&[OBSERVATIONS_SEED, pool_address.as_ref()], &tree_pubkey, &crate::ID);
+    let new_address_params =
+        address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0));
+
+    let mut observations_account = LightAccount::<PriceObservations>::new_init(
+        &crate::ID,
+        Some(address),
+        output_tree_index,
+    );
+    observations_account.pool_address = pool_address;
+
+    LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
+        .with_light_account(observations_account)?
+        .with_new_addresses(&[new_address_params])
+        .invoke(light_cpi_accounts)?;
+    Ok(())
+}
+
+pub fn handle_record_observation<'info>(
+    ctx: Context<'_, '_, '_, 'info, RecordObservation<'info>>,
+    proof: SdkValidityProof,
+    pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+    pool_data: Vec<u8>,
+    observations_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+    observations_data: Vec<u8>,
+) -> Result<()> {
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.fee_payer.as_ref(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let pool_state = SwapPool::load(&pool_data)?;
+    let mut pool_account = LightAccount::<SwapPool>::new_mut(
+        &crate::ID,
+        &pool_meta,
+        pool_state,
+    )?;
+
+    let observations_state = PriceObservations::try_from_slice(&observations_data)?;
+    let mut observations_account = LightAccount::<PriceObservations>::new_mut(
+        &crate::ID,
+        &observations_meta,
+        observations_state,
+    )?;
+    require!(
+        observations_account.pool_address == pool_meta.address,
+        ErrorCode::InvalidObservations
+    );
+
+    let inco_program = ctx.accounts.inco_lightning_program.to_account_info();
+    let signer = ctx.accounts.fee_payer.to_account_info();
+
+    let now = Clock::get()?.unix_timestamp;
+    accumulate_prices(&inco_program, &signer, &mut pool_account, now)?;
+
+    let index = observations_account.next_index as usize % OBSERVATION_SLOTS;
+    observations_account.observations[index] = PriceObservation {
+        timestamp: now,
+        price_cumulative_a: pool_account.price_cumulative_a,
+        price_cumulative_b: pool_account.price_cumulative_b,
+    };
+    observations_account.next_index = ((index + 1) % OBSERVATION_SLOTS) as u8;
+
+    // Commit pool and observation updates
+    LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
+        .with_light_account(pool_account)?
+        .with_light_account(observations_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn handle_grant_price_access<'info>(
+    ctx: Context<'_, '_, '_, 'info, GrantPriceAccess<'info>>,
+    proof: SdkValidityProof,
+    pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+    pool_data: Vec<u8>,
+    observations_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+    observations_data: Vec<u8>,
+    slot: u8,
+) -> Result<()> {
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.fee_payer.as_ref(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    // Both accounts are re-committed unchanged so the validity proof covers the data read here
+    let pool_state = SwapPool::load(&pool_data)?;
+    let pool_account = LightAccount::<SwapPool>::new_mut(
+        &crate::ID,
+        &pool_meta,
+        pool_state,
+    )?;
+
+    let observations_state = PriceObservations::try_from_slice(&observations_data)?;
+    let observations_account = LightAccount::<PriceObservations>::new_mut(
+        &crate::ID,
+        &observations_meta,
+        observations_state,
+    )?;
+
+    require_keys_eq!(pool_account.authority, ctx.accounts.authority.key(), ErrorCode::Unauthorized);
+    require!(
+        observations_account.pool_address == pool_meta.address,
+        ErrorCode::InvalidObservations
+    );
+    require!((slot as usize) < OBSERVATION_SLOTS, ErrorCode::InvalidObservations);
+
+    let observation = observations_account.observations[slot as usize];
+    let inco_program = ctx.accounts.inco_lightning_program.to_account_info();
+    let reader = ctx.accounts.reader.key();
+
+    let cpi_ctx = CpiContext::new(
+        inco_program.clone(),
+        Allow {
+            allowance_account: ctx.accounts.allowance_a.to_account_info(),
+            signer: ctx.accounts.fee_payer.to_account_info(),
+            allowed_address: ctx.accounts.reader.to_account_info(),
+            system_program: ctx.accounts.system_program.to_account_info(),
+        },
+    );
+    allow(cpi_ctx, observation.price_cumulative_a.0, true, reader)?;
+
+    let cpi_ctx = CpiContext::new(
+        inco_program,
+        Allow {
+            allowance_account: ctx.accounts.allowance_b.to_account_info(),
+            signer: ctx.accounts.fee_payer.to_account_info(),
+            allowed_address: ctx.accounts.reader.to_account_info(),
+            system_program: ctx.accounts.system_program.to_account_info(),
+        },
+    );
+    allow(cpi_ctx, observation.price_cumulative_b.0, true, reader)?;
+
+    LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
+        .with_light_account(pool_account)?
+        .with_light_account(observations_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
@@ -0,0 +1,54 @@
+//! Oracle price band configuration
+
+use anchor_lang::prelude::*;
+use light_sdk::{
+    account::LightAccount,
+    cpi::v2::{CpiAccounts, LightSystemProgramCpi},
+    cpi::{InvokeLightSystemProgram, LightCpiInstruction},
+    instruction::ValidityProof as SdkValidityProof,
+};
+
+use super::update_pool_config::UpdatePoolConfig;
+use crate::constants::*;
+use crate::errors::ErrorCode;
+use crate::state::*;
+
+pub fn handle_set_oracle_guard<'info>(
+    ctx: Context<'_, '_, '_, 'info, UpdatePoolConfig<'info>>,
+    proof: SdkValidityProof,
+    pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+    pool_data: Vec<u8>,
+    oracle_feed: Pubkey,
+    max_staleness_secs: u32,
+    max_deviation_bps: u16,
+) -> Result<()> {
+    require!(
+        max_deviation_bps as u128 <= BPS_DENOMINATOR,
+        ErrorCode::InvalidOracle
+    );
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.fee_payer.as_ref(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let pool_state = SwapPool::load(&pool_data)?;
+    let mut pool_account = LightAccount::<SwapPool>::new_mut(
+        &crate::ID,
+        &pool_meta,
+        pool_state,
+    )?;
+
+    require_keys_eq!(pool_account.authority, ctx.accounts.authority.key(), ErrorCode::Unauthorized);
+    pool_account.oracle_feed = oracle_feed;
+    pool_account.oracle_max_staleness_secs = max_staleness_secs;
+    pool_account.oracle_max_deviation_bps = max_deviation_bps;
+
+    // Commit pool state update
+    LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
+        .with_light_account(pool_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
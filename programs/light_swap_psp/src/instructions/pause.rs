@@ -0,0 +1,142 @@
+//! Guardian-controlled pause and unpause
+
+use anchor_lang::prelude::*;
+use light_sdk::{
+    account::LightAccount,
+    cpi::v2::{CpiAccounts, LightSystemProgramCpi},
+    cpi::{InvokeLightSystemProgram, LightCpiInstruction},
+    instruction::ValidityProof as SdkValidityProof,
+};
+
+use super::update_pool_config::UpdatePoolConfig;
+use crate::constants::*;
+use crate::errors::ErrorCode;
+use crate::events::*;
+use crate::state::*;
+
+#[derive(Accounts)]
+pub struct PausePool<'info> {
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+    /// Pool guardian or authority
+    pub caller: Signer<'info>,
+}
+
+pub fn handle_set_guardian<'info>(
+    ctx: Context<'_, '_, '_, 'info, UpdatePoolConfig<'info>>,
+    proof: SdkValidityProof,
+    pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+    pool_data: Vec<u8>,
+    guardian: Pubkey,
+) -> Result<()> {
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.fee_payer.as_ref(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let pool_state = SwapPool::load(&pool_data)?;
+    let mut pool_account = LightAccount::<SwapPool>::new_mut(
+        &crate::ID,
+        &pool_meta,
+        pool_state,
+    )?;
+
+    require_keys_eq!(pool_account.authority, ctx.accounts.authority.key(), ErrorCode::Unauthorized);
+    pool_account.guardian = guardian;
+
+    // Commit pool state update
+    LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
+        .with_light_account(pool_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn handle_pause_pool<'info>(
+    ctx: Context<'_, '_, '_, 'info, PausePool<'info>>,
+    proof: SdkValidityProof,
+    pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+    pool_data: Vec<u8>,
+) -> Result<()> {
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.fee_payer.as_ref(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let pool_state = SwapPool::load(&pool_data)?;
+    let mut pool_account = LightAccount::<SwapPool>::new_mut(
+        &crate::ID,
+        &pool_meta,
+        pool_state,
+    )?;
+
+    let caller = ctx.accounts.caller.key();
+    let by_guardian = pool_account.guardian != Pubkey::default() && caller == pool_account.guardian;
+    require!(
+        by_guardian || caller == pool_account.authority,
+        ErrorCode::Unauthorized
+    );
+    require!(!pool_account.is_paused, ErrorCode::PoolPaused);
+
+    let now = Clock::get()?.unix_timestamp;
+    pool_account.is_paused = true;
+    pool_account.paused_at_ts = now;
+
+    emit!(PoolPausedEvent {
+        pool_address: pool_meta.address,
+        caller,
+        by_guardian,
+        timestamp: now,
+    });
+
+    // Commit pool state update
+    LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
+        .with_light_account(pool_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn handle_unpause_pool<'info>(
+    ctx: Context<'_, '_, '_, 'info, UpdatePoolConfig<'info>>,
+    proof: SdkValidityProof,
+    pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+    pool_data: Vec<u8>,
+) -> Result<()> {
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.fee_payer.as_ref(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let pool_state = SwapPool::load(&pool_data)?;
+    let mut pool_account = LightAccount::<SwapPool>::new_mut(
+        &crate::ID,
+        &pool_meta,
+        pool_state,
+    )?;
+
+    require_keys_eq!(pool_account.authority, ctx.accounts.authority.key(), ErrorCode::Unauthorized);
+    require!(pool_account.is_paused, ErrorCode::PoolNotPaused);
+    let now = Clock::get()?.unix_timestamp;
+    require!(
+        now >= pool_account.paused_at_ts + UNPAUSE_DELAY_SECS,
+        ErrorCode::UnpauseTooEarly
+    );
+    pool_account.is_paused = false;
+
+    emit!(PoolUnpausedEvent {
+        pool_address: pool_meta.address,
+        paused_at_ts: pool_account.paused_at_ts,
+        timestamp: now,
+    });
+
+    // Commit pool state update
+    LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
+        .with_light_account(pool_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
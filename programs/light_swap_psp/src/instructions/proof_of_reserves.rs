@@ -0,0 +1,204 @@
+//! Auditor attestations that vaults back the encrypted reserves
+
+use anchor_lang::prelude::*;
+use inco_lightning::cpi::accounts::Allow;
+use inco_lightning::cpi::{allow, e_add, e_ge};
+use inco_lightning::types::Ebool;
+use inco_lightning::ID as INCO_LIGHTNING_ID;
+use light_sdk::{
+    account::LightAccount,
+    address::v2::derive_address,
+    cpi::v2::{CpiAccounts, LightSystemProgramCpi},
+    cpi::{InvokeLightSystemProgram, LightCpiInstruction},
+    instruction::{
+        PackedAddressTreeInfo, PackedAddressTreeInfoExt, ValidityProof as SdkValidityProof,
+    },
+};
+
+use super::update_pool_config::UpdatePoolConfig;
+use crate::constants::*;
+use crate::errors::ErrorCode;
+use crate::fhe::*;
+use crate::inco_token::IncoAccount;
+use crate::state::*;
+
+#[derive(Accounts)]
+pub struct VerifyReserves<'info> {
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+    /// CHECK: Pool auditor, verified against the pool state; receives decryption access
+    pub auditor: AccountInfo<'info>,
+    /// Pool vault for token A (owned by pool_authority)
+    pub pool_vault_a: Account<'info, IncoAccount>,
+    /// Pool vault for token B (owned by pool_authority)
+    pub pool_vault_b: Account<'info, IncoAccount>,
+    /// CHECK: Inco allowance PDA for (vault_a_covers handle, auditor)
+    #[account(mut)]
+    pub allowance_a: AccountInfo<'info>,
+    /// CHECK: Inco allowance PDA for (vault_b_covers handle, auditor)
+    #[account(mut)]
+    pub allowance_b: AccountInfo<'info>,
+    /// CHECK: Inco Lightning program for encrypted operations
+    #[account(address = INCO_LIGHTNING_ID)]
+    pub inco_lightning_program: AccountInfo<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handle_set_auditor<'info>(
+    ctx: Context<'_, '_, '_, 'info, UpdatePoolConfig<'info>>,
+    proof: SdkValidityProof,
+    pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+    pool_data: Vec<u8>,
+    auditor: Pubkey,
+) -> Result<()> {
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.fee_payer.as_ref(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let pool_state = SwapPool::load(&pool_data)?;
+    let mut pool_account = LightAccount::<SwapPool>::new_mut(
+        &crate::ID,
+        &pool_meta,
+        pool_state,
+    )?;
+
+    require_keys_eq!(pool_account.authority, ctx.accounts.authority.key(), ErrorCode::Unauthorized);
+    pool_account.auditor = auditor;
+
+    // Commit pool state update
+    LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
+        .with_light_account(pool_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn handle_verify_reserves<'info>(
+    ctx: Context<'_, '_, '_, 'info, VerifyReserves<'info>>,
+    proof: SdkValidityProof,
+    address_tree_info: PackedAddressTreeInfo,
+    output_tree_index: u8,
+    pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+    pool_data: Vec<u8>,
+) -> Result<()> {
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.fee_payer.as_ref(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let pool_state = SwapPool::load(&pool_data)?;
+    let mut pool_account = LightAccount::<SwapPool>::new_mut(
+        &crate::ID,
+        &pool_meta,
+        pool_state,
+    )?;
+
+    require!(pool_account.auditor != Pubkey::default(), ErrorCode::Unauthorized);
+    require_keys_eq!(pool_account.auditor, ctx.accounts.auditor.key(), ErrorCode::Unauthorized);
+    require_keys_eq!(ctx.accounts.pool_vault_a.key(), pool_account.vault_a, ErrorCode::InvalidPoolVault);
+    require_keys_eq!(ctx.accounts.pool_vault_b.key(), pool_account.vault_b, ErrorCode::InvalidPoolVault);
+
+    let tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|error| ProgramError::Custom(error.into()))?;
+    let attestation_id = pool_account.next_attestation_id;
+    let (address, address_seed) = derive_address(
+        &[RESERVE_ATTESTATION_SEED, pool_meta.address.as_ref(), &attestation_id.to_le_bytes()],
+        &tree_pubkey,
+        &crate::ID,
+    );
+    let new_address_params =
+        address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0));
+
+    let inco_program = ctx.accounts.inco_lightning_program.to_account_info();
+    let signer = ctx.accounts.fee_payer.to_account_info();
+    let auditor = ctx.accounts.auditor.key();
+
+    // vault >= reserve + protocol_fee + owed, per side
+    let committed_a = e_add(
+        operation(&inco_program, &signer),
+        pool_account.reserve_a,
+        pool_account.protocol_fee_a,
+        SCALAR_BYTE,
+    )?;
+
+    let committed_a = e_add(
+        operation(&inco_program, &signer),
+        committed_a,
+        pool_account.owed_a,
+        SCALAR_BYTE,
+    )?;
+
+    let vault_a_covers: Ebool = e_ge(
+        operation(&inco_program, &signer),
+        ctx.accounts.pool_vault_a.amount,
+        committed_a,
+        SCALAR_BYTE,
+    )?;
+
+    let committed_b = e_add(
+        operation(&inco_program, &signer),
+        pool_account.reserve_b,
+        pool_account.protocol_fee_b,
+        SCALAR_BYTE,
+    )?;
+
+    let committed_b = e_add(
+        operation(&inco_program, &signer),
+        committed_b,
+        pool_account.owed_b,
+        SCALAR_BYTE,
+    )?;
+
+    let vault_b_covers: Ebool = e_ge(
+        operation(&inco_program, &signer),
+        ctx.accounts.pool_vault_b.amount,
+        committed_b,
+        SCALAR_BYTE,
+    )?;
+
+    let cpi_ctx = CpiContext::new(
+        inco_program.clone(),
+        Allow {
+            allowance_account: ctx.accounts.allowance_a.to_account_info(),
+            signer: signer.clone(),
+            allowed_address: ctx.accounts.auditor.to_account_info(),
+            system_program: ctx.accounts.system_program.to_account_info(),
+        },
+    );
+    allow(cpi_ctx, vault_a_covers.0, true, auditor)?;
+
+    let cpi_ctx = CpiContext::new(
+        inco_program,
+        Allow {
+            allowance_account: ctx.accounts.allowance_b.to_account_info(),
+            signer,
+            allowed_address: ctx.accounts.auditor.to_account_info(),
+            system_program: ctx.accounts.system_program.to_account_info(),
+        },
+    );
+    allow(cpi_ctx, vault_b_covers.0, true, auditor)?;
+
+    let mut attestation_account = LightAccount::<ReserveAttestation>::new_init(
+        &crate::ID,
+        Some(address),
+        output_tree_index,
+    );
+    attestation_account.pool_address = pool_meta.address;
+    attestation_account.auditor = auditor;
+    attestation_account.attestation_id = attestation_id;
+    attestation_account.timestamp = Clock::get()?.unix_timestamp;
+    attestation_account.vault_a_covers = vault_a_covers;
+    attestation_account.vault_b_covers = vault_b_covers;
+    pool_account.next_attestation_id += 1;
+
+    LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
+        .with_light_account(pool_account)?
+        .with_light_account(attestation_account)?
+        .with_new_addresses(&[new_address_params])
+        .invoke(light_cpi_accounts)?;
+    Ok(())
+}
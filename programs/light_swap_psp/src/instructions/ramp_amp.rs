@@ -0,0 +1,98 @@
+//! Gradual amplification changes for stable pools
+
+use anchor_lang::prelude::*;
+use light_sdk::{
+    account::LightAccount,
+    cpi::v2::{CpiAccounts, LightSystemProgramCpi},
+    cpi::{InvokeLightSystemProgram, LightCpiInstruction},
+    instruction::ValidityProof as SdkValidityProof,
+};
+
+use super::update_pool_config::UpdatePoolConfig;
+use crate::constants::*;
+use crate::errors::ErrorCode;
+use crate::state::*;
+
+pub fn handle_ramp_amp<'info>(
+    ctx: Context<'_, '_, '_, 'info, UpdatePoolConfig<'info>>,
+    proof: SdkValidityProof,
+    pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+    pool_data: Vec<u8>,
+    future_amp: u64,
+    ramp_end_ts: i64,
+) -> Result<()> {
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.fee_payer.as_ref(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let pool_state = SwapPool::load(&pool_data)?;
+    let mut pool_account = LightAccount::<SwapPool>::new_mut(
+        &crate::ID,
+        &pool_meta,
+        pool_state,
+    )?;
+
+    require_keys_eq!(pool_account.authority, ctx.accounts.authority.key(), ErrorCode::Unauthorized);
+    require!(pool_account.pool_kind == PoolKind::StableSwap, ErrorCode::InvalidPoolKind);
+    require!((MIN_AMP..=MAX_AMP).contains(&future_amp), ErrorCode::InvalidAmp);
+
+    let now = Clock::get()?.unix_timestamp;
+    require!(ramp_end_ts >= now + MIN_AMP_RAMP_SECS, ErrorCode::InvalidAmpRamp);
+
+    let current_amp = pool_account.current_amp(now);
+    require!(
+        future_amp <= current_amp * MAX_AMP_CHANGE && future_amp * MAX_AMP_CHANGE >= current_amp,
+        ErrorCode::InvalidAmpRamp
+    );
+
+    pool_account.amp_initial = current_amp;
+    pool_account.amp_future = future_amp;
+    pool_account.amp_ramp_start_ts = now;
+    pool_account.amp_ramp_end_ts = ramp_end_ts;
+
+    // Commit pool state update
+    LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
+        .with_light_account(pool_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn handle_stop_ramp_amp<'info>(
+    ctx: Context<'_, '_, '_, 'info, UpdatePoolConfig<'info>>,
+    proof: SdkValidityProof,
+    pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+    pool_data: Vec<u8>,
+) -> Result<()> {
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.fee_payer.as_ref(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let pool_state = SwapPool::load(&pool_data)?;
+    let mut pool_account = LightAccount::<SwapPool>::new_mut(
+        &crate::ID,
+        &pool_meta,
+        pool_state,
+    )?;
+
+    require_keys_eq!(pool_account.authority, ctx.accounts.authority.key(), ErrorCode::Unauthorized);
+    require!(pool_account.pool_kind == PoolKind::StableSwap, ErrorCode::InvalidPoolKind);
+
+    let now = Clock::get()?.unix_timestamp;
+    let current_amp = pool_account.current_amp(now);
+    pool_account.amp_initial = current_amp;
+    pool_account.amp_future = current_amp;
+    pool_account.amp_ramp_start_ts = now;
+    pool_account.amp_ramp_end_ts = now;
+
+    // Commit pool state update
+    LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
+        .with_light_account(pool_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
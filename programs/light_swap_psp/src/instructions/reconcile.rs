@@ -0,0 +1,248 @@
+//! Reconciling encrypted reserves with vault balances
+
+use anchor_lang::prelude::*;
+use inco_lightning::cpi::{as_euint128, e_add, e_ge, e_select, e_sub, new_euint128};
+use inco_lightning::types::Ebool;
+use inco_lightning::ID as INCO_LIGHTNING_ID;
+use light_sdk::{
+    account::LightAccount,
+    address::v2::derive_address,
+    cpi::v2::{CpiAccounts, LightSystemProgramCpi},
+    cpi::{InvokeLightSystemProgram, LightCpiInstruction},
+    instruction::{
+        PackedAddressTreeInfo, PackedAddressTreeInfoExt, ValidityProof as SdkValidityProof,
+    },
+};
+
+use crate::constants::*;
+use crate::curve::*;
+use crate::errors::ErrorCode;
+use crate::fhe::*;
+use crate::inco_token::IncoAccount;
+use crate::payout::*;
+use crate::state::*;
+use crate::vault::*;
+
+#[derive(Accounts)]
+pub struct SyncReserves<'info> {
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+    pub authority: Signer<'info>,
+    /// Pool vault for token A (owned by pool_authority)
+    pub pool_vault_a: Account<'info, IncoAccount>,
+    /// Pool vault for token B (owned by pool_authority)
+    pub pool_vault_b: Account<'info, IncoAccount>,
+    /// CHECK: Inco Lightning program for encrypted operations
+    #[account(address = INCO_LIGHTNING_ID)]
+    pub inco_lightning_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct Skim<'info> {
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+    /// Pool authority; decrypts the payout outcome
+    pub authority: Signer<'info>,
+    /// Pool vault for the skimmed mint (owned by pool_authority)
+    pub pool_vault: Account<'info, IncoAccount>,
+    /// Inco token account receiving the excess
+    pub recipient_token: Account<'info, IncoAccount>,
+    /// CHECK: Inco allowance PDA for (payout approved handle, authority)
+    #[account(mut)]
+    pub approved_allowance: AccountInfo<'info>,
+    /// CHECK: Inco Lightning program for encrypted operations
+    #[account(address = INCO_LIGHTNING_ID)]
+    pub inco_lightning_program: AccountInfo<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handle_sync_reserves<'info>(
+    ctx: Context<'_, '_, '_, 'info, SyncReserves<'info>>,
+    proof: SdkValidityProof,
+    pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+    pool_data: Vec<u8>,
+) -> Result<()> {
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.fee_payer.as_ref(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let pool_state = SwapPool::load(&pool_data)?;
+    let mut pool_account = LightAccount::<SwapPool>::new_mut(
+        &crate::ID,
+        &pool_meta,
+        pool_state,
+    )?;
+
+    require_keys_eq!(pool_account.authority, ctx.accounts.authority.key(), ErrorCode::Unauthorized);
+    require!(!pool_account.is_paused, ErrorCode::PoolPaused);
+    require_keys_eq!(ctx.accounts.pool_vault_a.key(), pool_account.vault_a, ErrorCode::InvalidPoolVault);
+    require_keys_eq!(ctx.accounts.pool_vault_b.key(), pool_account.vault_b, ErrorCode::InvalidPoolVault);
+
+    let inco_program = ctx.accounts.inco_lightning_program.to_account_info();
+    let signer = ctx.accounts.fee_payer.to_account_info();
+
+    // Accumulate prices over the elapsed interval before reserves change
+    let now = Clock::get()?.unix_timestamp;
+    accumulate_prices(&inco_program, &signer, &mut pool_account, now)?;
+
+    pool_account.reserve_a = vault_backing(
+        &inco_program,
+        &signer,
+        ctx.accounts.pool_vault_a.amount,
+        pool_account.protocol_fee_a,
+        pool_account.owed_a,
+    )?;
+    pool_account.reserve_b = vault_backing(
+        &inco_program,
+        &signer,
+        ctx.accounts.pool_vault_b.amount,
+        pool_account.protocol_fee_b,
+        pool_account.owed_b,
+    )?;
+
+    pool_account.skimmed_a = as_euint128(operation(&inco_program, &signer), 0)?;
+
+    pool_account.skimmed_b = as_euint128(operation(&inco_program, &signer), 0)?;
+    pool_account.last_update_ts = now;
+    pool_account.bump_state_nonce();
+
+    // Commit pool state update
+    LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
+        .with_light_account(pool_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn handle_skim<'info>(
+    ctx: Context<'_, '_, '_, 'info, Skim<'info>>,
+    proof: SdkValidityProof,
+    pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+    pool_data: Vec<u8>,
+    amount_ciphertext: Vec<u8>,
+    input_type: u8,
+    skim_a: bool,
+    address_tree_info: PackedAddressTreeInfo,
+    output_tree_index: u8,
+    payout_nonce: u64,
+) -> Result<()> {
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.fee_payer.as_ref(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let pool_state = SwapPool::load(&pool_data)?;
+    let mut pool_account = LightAccount::<SwapPool>::new_mut(
+        &crate::ID,
+        &pool_meta,
+        pool_state,
+    )?;
+
+    let authority = ctx.accounts.authority.key();
+    require_keys_eq!(pool_account.authority, authority, ErrorCode::Unauthorized);
+    require!(!pool_account.is_paused, ErrorCode::PoolPaused);
+
+    let mint = if skim_a { pool_account.mint_a } else { pool_account.mint_b };
+    let vault = if skim_a { pool_account.vault_a } else { pool_account.vault_b };
+    require_keys_eq!(ctx.accounts.pool_vault.key(), vault, ErrorCode::InvalidPoolVault);
+    require_keys_eq!(ctx.accounts.recipient_token.mint, mint, ErrorCode::InvalidOutputMint);
+
+    let inco_program = ctx.accounts.inco_lightning_program.to_account_info();
+    let signer = ctx.accounts.fee_payer.to_account_info();
+    let now = Clock::get()?.unix_timestamp;
+
+    let (reserve, protocol_fee, owed, skimmed_before) = if skim_a {
+        (pool_account.reserve_a, pool_account.protocol_fee_a, pool_account.owed_a, pool_account.skimmed_a)
+    } else {
+        (pool_account.reserve_b, pool_account.protocol_fee_b, pool_account.owed_b, pool_account.skimmed_b)
+    };
+
+    // excess = max(vault - protocol_fee - owed - reserve - skimmed, 0)
+    let backing = vault_backing(
+        &inco_program,
+        &signer,
+        ctx.accounts.pool_vault.amount,
+        protocol_fee,
+        owed,
+    )?;
+
+    let committed =
+        e_add(operation(&inco_program, &signer), reserve, skimmed_before, SCALAR_BYTE)?;
+
+    let backs_reserve: Ebool =
+        e_ge(operation(&inco_program, &signer), backing, committed, SCALAR_BYTE)?;
+
+    let surplus = e_sub(operation(&inco_program, &signer), backing, committed, SCALAR_BYTE)?;
+
+    let zero = as_euint128(operation(&inco_program, &signer), 0)?;
+
+    let excess =
+        e_select(operation(&inco_program, &signer), backs_reserve, surplus, zero, SCALAR_BYTE)?;
+
+    let skimmed =
+        new_euint128(operation(&inco_program, &signer), amount_ciphertext.clone(), input_type)?;
+
+    let within_excess: Ebool =
+        e_ge(operation(&inco_program, &signer), excess, skimmed, SCALAR_BYTE)?;
+
+    let skimmed =
+        e_select(operation(&inco_program, &signer), within_excess, skimmed, zero, SCALAR_BYTE)?;
+
+    let skimmed_total =
+        e_add(operation(&inco_program, &signer), skimmed_before, skimmed, SCALAR_BYTE)?;
+    if skim_a {
+        pool_account.skimmed_a = skimmed_total;
+    } else {
+        pool_account.skimmed_b = skimmed_total;
+    }
+
+    let on_approved = vec![PayoutLeg::transfer(
+        pool_account.authority_seeds(),
+        vault,
+        ctx.accounts.recipient_token.key(),
+        amount_ciphertext,
+    )];
+
+    // The authority decrypts the outcome to settle the payout
+    allow_payout_outcome(
+        &inco_program,
+        &signer,
+        &ctx.accounts.system_program.to_account_info(),
+        &ctx.accounts.authority.to_account_info(),
+        &[(within_excess, ctx.accounts.approved_allowance.to_account_info())],
+    )?;
+
+    let tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|error| ProgramError::Custom(error.into()))?;
+    let (address, address_seed) = derive_address(
+        &[PAYOUT_SEED, authority.as_ref(), &payout_nonce.to_le_bytes()],
+        &tree_pubkey,
+        &crate::ID,
+    );
+    let new_address_params =
+        address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(1));
+
+    let mut payout_account = LightAccount::<PendingPayout>::new_init(
+        &crate::ID,
+        Some(address),
+        output_tree_index,
+    );
+    payout_account.beneficiary = authority;
+    payout_account.input_type = input_type;
+    payout_account.approved = within_excess;
+    payout_account.on_approved = on_approved;
+    payout_account.created_at = now;
+
+    // Commit the pool's skimmed total and the payout
+    LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
+        .with_light_account(pool_account)?
+        .with_light_account(payout_account)?
+        .with_new_addresses(&[new_address_params])
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
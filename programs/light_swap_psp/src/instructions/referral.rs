@@ -0,0 +1,265 @@
+//! Referrer registration and referral fee claims
+
+use anchor_lang::prelude::*;
+use inco_lightning::cpi::{as_euint128, e_ge, e_select, e_sub, new_euint128};
+use inco_lightning::types::Ebool;
+use inco_lightning::ID as INCO_LIGHTNING_ID;
+use light_sdk::{
+    account::LightAccount,
+    address::v2::derive_address,
+    cpi::v2::{CpiAccounts, LightSystemProgramCpi},
+    cpi::{InvokeLightSystemProgram, LightCpiInstruction},
+    instruction::{
+        PackedAddressTreeInfo, PackedAddressTreeInfoExt, ValidityProof as SdkValidityProof,
+    },
+};
+
+use super::update_pool_config::UpdatePoolConfig;
+use crate::constants::*;
+use crate::errors::ErrorCode;
+use crate::fhe::*;
+use crate::inco_token::IncoAccount;
+use crate::payout::*;
+use crate::state::*;
+
+#[derive(Accounts)]
+pub struct RegisterReferrer<'info> {
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+    pub referrer: Signer<'info>,
+    /// CHECK: Inco Lightning program for encrypted operations
+    #[account(address = INCO_LIGHTNING_ID)]
+    pub inco_lightning_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimReferralFees<'info> {
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+    pub referrer: Signer<'info>,
+    /// Pool vault for the claimed mint (owned by pool_authority)
+    pub pool_vault: Account<'info, IncoAccount>,
+    /// Referrer's Inco token account for the claimed mint
+    pub referrer_token: Account<'info, IncoAccount>,
+    /// CHECK: Inco allowance PDA for (payout approved handle, referrer)
+    #[account(mut)]
+    pub approved_allowance: AccountInfo<'info>,
+    /// CHECK: Inco Lightning program for encrypted operations
+    #[account(address = INCO_LIGHTNING_ID)]
+    pub inco_lightning_program: AccountInfo<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handle_set_referral_share<'info>(
+    ctx: Context<'_, '_, '_, 'info, UpdatePoolConfig<'info>>,
+    proof: SdkValidityProof,
+    pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+    pool_data: Vec<u8>,
+    referral_share_bps: u16,
+) -> Result<()> {
+    require!(
+        referral_share_bps as u128 <= BPS_DENOMINATOR,
+        ErrorCode::InvalidReferralShare
+    );
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.fee_payer.as_ref(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let pool_state = SwapPool::load(&pool_data)?;
+    let mut pool_account = LightAccount::<SwapPool>::new_mut(
+        &crate::ID,
+        &pool_meta,
+        pool_state,
+    )?;
+
+    require_keys_eq!(pool_account.authority, ctx.accounts.authority.key(), ErrorCode::Unauthorized);
+    pool_account.referral_share_bps = referral_share_bps;
+
+    // Commit pool state update
+    LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
+        .with_light_account(pool_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn handle_register_referrer<'info>(
+    ctx: Context<'_, '_, '_, 'info, RegisterReferrer<'info>>,
+    proof: SdkValidityProof,
+    address_tree_info: PackedAddressTreeInfo,
+    output_tree_index: u8,
+    mint_a: Pubkey,
+    mint_b: Pubkey,
+) -> Result<()> {
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.fee_payer.as_ref(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|error| ProgramError::Custom(error.into()))?;
+    let (pool_address, _) =
+        derive_address(&[b"pool", mint_a.as_ref(), mint_b.as_ref()], &tree_pubkey, &crate::ID);
+    let referrer = ctx.accounts.referrer.key();
+    let (address, address_seed) = derive_address(
+        &[REFERRER_FEES_SEED, pool_address.as_ref(), referrer.as_ref()],
+        &tree_pubkey,
+        &crate::ID,
+    );
+    let new_address_params =
+        address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0));
+
+    let inco_program = ctx.accounts.inco_lightning_program.to_account_info();
+    let signer = ctx.accounts.fee_payer.to_account_info();
+
+    let mut referrer_account = LightAccount::<ReferrerFees>::new_init(
+        &crate::ID,
+        Some(address),
+        output_tree_index,
+    );
+
+    referrer_account.fee_a = as_euint128(operation(&inco_program, &signer), 0)?;
+
+    referrer_account.fee_b = as_euint128(operation(&inco_program, &signer), 0)?;
+
+    referrer_account.referrer = referrer;
+    referrer_account.pool_address = pool_address;
+    referrer_account.mint_a = mint_a;
+    referrer_account.mint_b = mint_b;
+
+    LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
+        .with_light_account(referrer_account)?
+        .with_new_addresses(&[new_address_params])
+        .invoke(light_cpi_accounts)?;
+    Ok(())
+}
+
+pub fn handle_claim_referral_fees<'info>(
+    ctx: Context<'_, '_, '_, 'info, ClaimReferralFees<'info>>,
+    proof: SdkValidityProof,
+    pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+    pool_data: Vec<u8>,
+    referrer_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+    referrer_data: Vec<u8>,
+    amount_ciphertext: Vec<u8>,
+    input_type: u8,
+    claim_a: bool,
+    address_tree_info: PackedAddressTreeInfo,
+    output_tree_index: u8,
+    payout_nonce: u64,
+) -> Result<()> {
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.fee_payer.as_ref(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let pool_state = SwapPool::load(&pool_data)?;
+    let mut pool_account = LightAccount::<SwapPool>::new_mut(
+        &crate::ID,
+        &pool_meta,
+        pool_state,
+    )?;
+
+    let referrer_state = ReferrerFees::try_from_slice(&referrer_data)?;
+    let mut referrer_account = LightAccount::<ReferrerFees>::new_mut(
+        &crate::ID,
+        &referrer_meta,
+        referrer_state,
+    )?;
+
+    let referrer = ctx.accounts.referrer.key();
+    require_keys_eq!(referrer_account.referrer, referrer, ErrorCode::Unauthorized);
+    require!(referrer_account.pool_address == pool_meta.address, ErrorCode::InvalidReferrer);
+    require!(pool_account.claims_open(Clock::get()?.unix_timestamp), ErrorCode::PoolPaused);
+
+    let mint = if claim_a { pool_account.mint_a } else { pool_account.mint_b };
+    let vault = if claim_a { pool_account.vault_a } else { pool_account.vault_b };
+    require_keys_eq!(ctx.accounts.pool_vault.key(), vault, ErrorCode::InvalidPoolVault);
+    require_keys_eq!(ctx.accounts.referrer_token.mint, mint, ErrorCode::InvalidOutputMint);
+
+    let inco_program = ctx.accounts.inco_lightning_program.to_account_info();
+    let signer = ctx.accounts.fee_payer.to_account_info();
+    let accrued = if claim_a { referrer_account.fee_a } else { referrer_account.fee_b };
+
+    let claimed =
+        new_euint128(operation(&inco_program, &signer), amount_ciphertext.clone(), input_type)?;
+
+    let within_balance: Ebool =
+        e_ge(operation(&inco_program, &signer), accrued, claimed, SCALAR_BYTE)?;
+
+    let zero = as_euint128(operation(&inco_program, &signer), 0)?;
+
+    let debit = e_select(
+        operation(&inco_program, &signer),
+        within_balance,
+        claimed,
+        zero,
+        SCALAR_BYTE,
+    )?;
+
+    let remaining = e_sub(operation(&inco_program, &signer), accrued, debit, SCALAR_BYTE)?;
+
+    let owed = if claim_a { pool_account.owed_a } else { pool_account.owed_b };
+    let owed = e_sub(operation(&inco_program, &signer), owed, debit, SCALAR_BYTE)?;
+    if claim_a {
+        referrer_account.fee_a = remaining;
+        pool_account.owed_a = owed;
+    } else {
+        referrer_account.fee_b = remaining;
+        pool_account.owed_b = owed;
+    }
+    pool_account.bump_state_nonce();
+
+    let on_approved = vec![PayoutLeg::transfer(
+        pool_account.authority_seeds(),
+        vault,
+        ctx.accounts.referrer_token.key(),
+        amount_ciphertext,
+    )];
+
+    // The referrer decrypts the outcome to settle the payout
+    allow_payout_outcome(
+        &inco_program,
+        &signer,
+        &ctx.accounts.system_program.to_account_info(),
+        &ctx.accounts.referrer.to_account_info(),
+        &[(within_balance, ctx.accounts.approved_allowance.to_account_info())],
+    )?;
+
+    let tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|error| ProgramError::Custom(error.into()))?;
+    let (address, address_seed) = derive_address(
+        &[PAYOUT_SEED, referrer.as_ref(), &payout_nonce.to_le_bytes()],
+        &tree_pubkey,
+        &crate::ID,
+    );
+    let new_address_params =
+        address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(2));
+
+    let mut payout_account = LightAccount::<PendingPayout>::new_init(
+        &crate::ID,
+        Some(address),
+        output_tree_index,
+    );
+    payout_account.beneficiary = referrer;
+    payout_account.input_type = input_type;
+    payout_account.approved = within_balance;
+    payout_account.on_approved = on_approved;
+    payout_account.created_at = Clock::get()?.unix_timestamp;
+
+    LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
+        .with_light_account(pool_account)?
+        .with_light_account(referrer_account)?
+        .with_light_account(payout_account)?
+        .with_new_addresses(&[new_address_params])
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
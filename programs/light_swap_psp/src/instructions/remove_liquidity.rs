@@ -0,0 +1,92 @@
+//! Withdraw encrypted amounts from both sides of a pool
+
+use anchor_lang::prelude::*;
+use inco_lightning::cpi::{e_sub, new_euint128};
+use inco_lightning::ID as INCO_LIGHTNING_ID;
+use light_sdk::{
+    account::LightAccount,
+    cpi::v2::{CpiAccounts, LightSystemProgramCpi},
+    cpi::{InvokeLightSystemProgram, LightCpiInstruction},
+    instruction::ValidityProof as SdkValidityProof,
+};
+
+use crate::constants::*;
+use crate::curve::*;
+use crate::errors::ErrorCode;
+use crate::fhe::*;
+use crate::state::*;
+
+#[derive(Accounts)]
+pub struct RemoveLiquidity<'info> {
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+    pub authority: Signer<'info>,
+    /// CHECK: Inco Lightning program for encrypted operations
+    #[account(address = INCO_LIGHTNING_ID)]
+    pub inco_lightning_program: AccountInfo<'info>,
+}
+
+pub fn handle_remove_liquidity<'info>(
+    ctx: Context<'_, '_, '_, 'info, RemoveLiquidity<'info>>,
+    proof: SdkValidityProof,
+    pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+    pool_data: Vec<u8>,
+    amount_a_ciphertext: Vec<u8>,
+    amount_b_ciphertext: Vec<u8>,
+    input_type: u8,
+) -> Result<()> {
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.fee_payer.as_ref(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let pool_state = SwapPool::load(&pool_data)?;
+    let mut pool_account = LightAccount::<SwapPool>::new_mut(
+        &crate::ID,
+        &pool_meta,
+        pool_state,
+    )?;
+
+    require!(!pool_account.is_paused, ErrorCode::PoolPaused);
+    require_keys_eq!(pool_account.authority, ctx.accounts.authority.key(), ErrorCode::Unauthorized);
+
+    let inco_program = ctx.accounts.inco_lightning_program.to_account_info();
+    let signer = ctx.accounts.fee_payer.to_account_info();
+
+    // Accumulate prices over the elapsed interval before reserves change
+    let now = Clock::get()?.unix_timestamp;
+    accumulate_prices(&inco_program, &signer, &mut pool_account, now)?;
+
+    // Parse encrypted amounts
+    let amount_a =
+        new_euint128(operation(&inco_program, &signer), amount_a_ciphertext, input_type)?;
+
+    let amount_b =
+        new_euint128(operation(&inco_program, &signer), amount_b_ciphertext, input_type)?;
+
+    // Subtract from reserves
+    pool_account.reserve_a = e_sub(
+        operation(&inco_program, &signer),
+        pool_account.reserve_a,
+        amount_a,
+        SCALAR_BYTE,
+    )?;
+
+    pool_account.reserve_b = e_sub(
+        operation(&inco_program, &signer),
+        pool_account.reserve_b,
+        amount_b,
+        SCALAR_BYTE,
+    )?;
+
+    pool_account.last_update_ts = now;
+    pool_account.bump_state_nonce();
+
+    // Commit pool state update
+    LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
+        .with_light_account(pool_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
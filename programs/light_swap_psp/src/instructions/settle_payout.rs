@@ -0,0 +1,156 @@
+//! Settlement of pending payouts once their outcome is decrypted
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::AccountMeta;
+use anchor_spl::token::{transfer as spl_transfer, Token, Transfer as SplTransfer};
+use inco_lightning::ID as INCO_LIGHTNING_ID;
+use light_sdk::{
+    account::LightAccount,
+    cpi::v2::{CpiAccounts, LightSystemProgramCpi},
+    cpi::{InvokeLightSystemProgram, LightCpiInstruction},
+    instruction::ValidityProof as SdkValidityProof,
+};
+
+use crate::constants::*;
+use crate::errors::ErrorCode;
+use crate::inco_token::cpi::accounts::{IncoTransfer, MintTo as IncoMintTo};
+use crate::inco_token::cpi::{mint_to as inco_token_mint_to, transfer as inco_token_transfer};
+use crate::inco_token::ID as INCO_TOKEN_ID;
+use crate::payout::*;
+use crate::state::*;
+
+#[derive(Accounts)]
+pub struct SettlePayout<'info> {
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+    /// CHECK: Instructions sysvar holding the Ed25519 decryption attestations
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: AccountInfo<'info>,
+    /// CHECK: Inco Lightning program for encrypted operations
+    #[account(address = INCO_LIGHTNING_ID)]
+    pub inco_lightning_program: AccountInfo<'info>,
+    /// CHECK: Inco Token program for the payout transfers
+    #[account(address = INCO_TOKEN_ID)]
+    pub inco_token_program: AccountInfo<'info>,
+    /// SPL Token program, required when an executed leg returns a plain deposit
+    pub token_program: Option<Program<'info, Token>>,
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handle_settle_payout<'info>(
+    ctx: Context<'_, '_, '_, 'info, SettlePayout<'info>>,
+    proof: SdkValidityProof,
+    payout_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+    payout_data: Vec<u8>,
+    approved_plaintext: Vec<u8>,
+    refunded_plaintext: Option<Vec<u8>>,
+) -> Result<()> {
+    let payout_state = PendingPayout::try_from_slice(&payout_data)?;
+    let payout = payout_state.clone();
+    let payout_account = LightAccount::<PendingPayout>::new_close(
+        &crate::ID,
+        &payout_meta,
+        payout_state,
+    )?;
+
+    let inco_program = ctx.accounts.inco_lightning_program.to_account_info();
+    let instructions = ctx.accounts.instructions.to_account_info();
+    let signer = ctx.accounts.fee_payer.to_account_info();
+
+    // Pick the legs from the attested outcome
+    let approved =
+        attested_flag(&inco_program, &instructions, &signer, payout.approved, approved_plaintext)?;
+    let legs: &[PayoutLeg] = if approved {
+        &payout.on_approved
+    } else if payout.on_refunded.is_empty() {
+        &[]
+    } else {
+        let refunded_plaintext = refunded_plaintext.ok_or(ErrorCode::InvalidPayout)?;
+        if attested_flag(&inco_program, &instructions, &signer, payout.refunded, refunded_plaintext)? {
+            &payout.on_refunded
+        } else {
+            &[]
+        }
+    };
+
+    require!(
+        ctx.remaining_accounts.len() >= legs.len() * PAYOUT_ACCOUNTS_PER_LEG,
+        ErrorCode::InvalidPayout
+    );
+    let (leg_accounts, light_accounts) =
+        ctx.remaining_accounts.split_at(legs.len() * PAYOUT_ACCOUNTS_PER_LEG);
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.fee_payer.as_ref(),
+        light_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    for (leg, accounts) in legs.iter().zip(leg_accounts.chunks(PAYOUT_ACCOUNTS_PER_LEG)) {
+        // Legs are written by this program, so their seeds derive the signing PDA
+        let seeds: Vec<&[u8]> = leg.authority_seeds.iter().map(Vec::as_slice).collect();
+        let authority = Pubkey::create_program_address(&seeds, &crate::ID)
+            .map_err(|_| error!(ErrorCode::InvalidPayout))?;
+        require_keys_eq!(accounts[0].key(), authority, ErrorCode::InvalidPayout);
+        require_keys_eq!(accounts[1].key(), leg.source, ErrorCode::InvalidPayout);
+        require_keys_eq!(accounts[2].key(), leg.destination, ErrorCode::InvalidPayout);
+        let signer_seeds = &[seeds.as_slice()];
+
+        match leg.kind {
+            PayoutLegKind::Transfer => {
+                // CPI: Inco transfer from a program vault (its PDA owner signs)
+                let transfer_ctx = CpiContext::new_with_signer(
+                    ctx.accounts.inco_token_program.to_account_info(),
+                    IncoTransfer {
+                        source: accounts[1].clone(),
+                        destination: accounts[2].clone(),
+                        authority: accounts[0].clone(),
+                        inco_lightning_program: inco_program.clone(),
+                        system_program: ctx.accounts.system_program.to_account_info(),
+                    },
+                    signer_seeds,
+                );
+                inco_token_transfer(transfer_ctx, leg.ciphertext.clone(), payout.input_type)?;
+            }
+            PayoutLegKind::MintTo => {
+                // CPI: Inco mint of a wrapped deposit (the wrap authority PDA signs)
+                let mint_ctx = CpiContext::new_with_signer(
+                    ctx.accounts.inco_token_program.to_account_info(),
+                    IncoMintTo {
+                        mint: accounts[1].clone(),
+                        account: accounts[2].clone(),
+                        mint_authority: accounts[0].clone(),
+                        inco_lightning_program: inco_program.clone(),
+                        system_program: ctx.accounts.system_program.to_account_info(),
+                    },
+                    signer_seeds,
+                );
+                inco_token_mint_to(mint_ctx, leg.ciphertext.clone(), payout.input_type)?;
+            }
+            PayoutLegKind::SplTransfer => {
+                // CPI: Return a plain deposit from its backing vault (the wrap authority PDA signs)
+                let token_program = ctx
+                    .accounts
+                    .token_program
+                    .as_ref()
+                    .ok_or(ErrorCode::InvalidPayout)?;
+                let transfer_ctx = CpiContext::new_with_signer(
+                    token_program.to_account_info(),
+                    SplTransfer {
+                        from: accounts[1].clone(),
+                        to: accounts[2].clone(),
+                        authority: accounts[0].clone(),
+                    },
+                    signer_seeds,
+                );
+                spl_transfer(transfer_ctx, leg.amount)?;
+            }
+        }
+    }
+
+    // Nullify the payout
+    LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
+        .with_light_account(payout_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
@@ -0,0 +1,445 @@
+//! Single-pool swap with an encrypted input amount
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::AccountMeta;
+use anchor_spl::token::{Token, TokenAccount};
+use inco_lightning::cpi::{as_euint128, e_add, e_ge, e_select, e_sub};
+use inco_lightning::types::Ebool;
+use inco_lightning::ID as INCO_LIGHTNING_ID;
+use light_sdk::{
+    account::LightAccount,
+    address::v2::derive_address,
+    cpi::v2::{CpiAccounts, LightSystemProgramCpi},
+    cpi::{InvokeLightSystemProgram, LightCpiInstruction},
+    instruction::{
+        PackedAddressTreeInfo, PackedAddressTreeInfoExt, ValidityProof as SdkValidityProof,
+    },
+};
+
+use crate::constants::*;
+use crate::curve::*;
+use crate::errors::ErrorCode;
+use crate::events::*;
+use crate::fhe::*;
+use crate::inco_token::cpi::accounts::IncoTransfer;
+use crate::inco_token::cpi::transfer as inco_token_transfer;
+use crate::inco_token::{IncoAccount, IncoMint, ID as INCO_TOKEN_ID};
+use crate::oracle::*;
+use crate::payout::*;
+use crate::state::*;
+use crate::vault::*;
+use crate::wrap::*;
+
+#[derive(Accounts)]
+pub struct SwapExactIn<'info> {
+    /// Pays Light and Inco fees; may be a relayer distinct from the trader
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+    /// Owner or approved delegate of the user's input token account; pays native SOL deposits
+    /// and decrypts the payout outcome
+    #[account(mut)]
+    pub trader: Signer<'info>,
+    /// User's Inco token account for token A
+    #[account(mut)]
+    pub user_token_a: Account<'info, IncoAccount>,
+    /// User's Inco token account for token B  
+    #[account(mut)]
+    pub user_token_b: Account<'info, IncoAccount>,
+    /// Pool vault for token A (owned by pool_authority)
+    #[account(mut)]
+    pub pool_vault_a: Account<'info, IncoAccount>,
+    /// Pool vault for token B (owned by pool_authority)
+    #[account(mut)]
+    pub pool_vault_b: Account<'info, IncoAccount>,
+    /// CHECK: Inco allowance PDA for (payout approved handle, trader)
+    #[account(mut)]
+    pub approved_allowance: AccountInfo<'info>,
+    /// CHECK: Inco allowance PDA for (payout refunded handle, trader)
+    #[account(mut)]
+    pub refunded_allowance: AccountInfo<'info>,
+    /// CHECK: Price feed account, verified against the pool's configured oracle; only
+    /// required when the pool has an oracle price band
+    pub oracle: Option<UncheckedAccount<'info>>,
+    /// Trader's SPL token account for a wrapped input (wrapped SOL for native deposits,
+    /// which are paid in lamports); a refunded deposit returns here
+    #[account(mut)]
+    pub trader_spl_in: Option<Account<'info, TokenAccount>>,
+    /// SPL backing vault of the wrapped input mint (owned by wrap_authority_in)
+    #[account(mut)]
+    pub spl_backing_vault_in: Option<Account<'info, TokenAccount>>,
+    /// CHECK: Wrap authority PDA, verified against the backing vault and Inco mint
+    pub wrap_authority_in: Option<UncheckedAccount<'info>>,
+    /// Inco mint of the input token, minted into the pool vault when the payout settles
+    pub inco_mint_in: Option<Account<'info, IncoMint>>,
+    pub token_program: Option<Program<'info, Token>>,
+    /// CHECK: Inco Lightning program for encrypted operations
+    #[account(address = INCO_LIGHTNING_ID)]
+    pub inco_lightning_program: AccountInfo<'info>,
+    /// CHECK: Inco Token program for token transfers
+    #[account(address = INCO_TOKEN_ID)]
+    pub inco_token_program: AccountInfo<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handle_swap_exact_in<'info>(
+    ctx: Context<'_, '_, '_, 'info, SwapExactIn<'info>>,
+    proof: SdkValidityProof,
+    address_tree_info: PackedAddressTreeInfo,
+    output_tree_index: u8,
+    payout_nonce: u64,
+    pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+    pool_data: Vec<u8>,
+    amount_in_ciphertext: Vec<u8>,
+    amount_out_ciphertext: Vec<u8>,
+    fee_amount_ciphertext: Vec<u8>,
+    invariant_ciphertext: Vec<u8>,
+    input_type: u8,
+    a_to_b: bool,
+    referrer: Option<ReferrerAccountData>,
+    wrap_amount_in: Option<u64>,
+) -> Result<()> {
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.fee_payer.as_ref(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let pool_state = SwapPool::load(&pool_data)?;
+    let mut pool_account = LightAccount::<SwapPool>::new_mut(
+        &crate::ID,
+        &pool_meta,
+        pool_state,
+    )?;
+
+    require!(!pool_account.is_paused, ErrorCode::PoolPaused);
+    require!(!pool_account.batch_mode, ErrorCode::BatchModeActive);
+
+    let mut referrer_account = match referrer {
+        Some(referrer) => {
+            let referrer_state = ReferrerFees::try_from_slice(&referrer.data)?;
+            let mut referrer_account = LightAccount::<ReferrerFees>::new_mut(
+                &crate::ID,
+                &referrer.meta,
+                referrer_state,
+            )?;
+            require!(
+                referrer_account.pool_address == pool_meta.address,
+                ErrorCode::InvalidReferrer
+            );
+            // Claims pay out of these vaults, signed by the pool authority
+            referrer_account.vault_a = pool_account.vault_a;
+            referrer_account.vault_b = pool_account.vault_b;
+            referrer_account.pool_authority = pool_account.pool_authority;
+            referrer_account.pool_authority_bump = pool_account.pool_authority_bump;
+            Some(referrer_account)
+        }
+        None => None,
+    };
+
+    let inco_program = ctx.accounts.inco_lightning_program.to_account_info();
+    let inco_token_program = ctx.accounts.inco_token_program.to_account_info();
+    let signer = ctx.accounts.fee_payer.to_account_info();
+
+    let (mint_in, mint_out) = pool_account.swap_mints(a_to_b);
+    let (user_mint_in, user_mint_out) = if a_to_b {
+        (ctx.accounts.user_token_a.mint, ctx.accounts.user_token_b.mint)
+    } else {
+        (ctx.accounts.user_token_b.mint, ctx.accounts.user_token_a.mint)
+    };
+    require_keys_eq!(user_mint_in, mint_in, ErrorCode::InvalidInputMint);
+    require_keys_eq!(user_mint_out, mint_out, ErrorCode::InvalidOutputMint);
+    require_keys_eq!(ctx.accounts.pool_vault_a.key(), pool_account.vault_a, ErrorCode::InvalidPoolVault);
+    require_keys_eq!(ctx.accounts.pool_vault_b.key(), pool_account.vault_b, ErrorCode::InvalidPoolVault);
+
+    // Accumulate prices over the elapsed interval before reserves change
+    let now = Clock::get()?.unix_timestamp;
+    accumulate_prices(&inco_program, &signer, &mut pool_account, now)?;
+    let fee_bps = pool_account.effective_fee_bps(now);
+
+    // Transfer token_in FROM user TO pool vault, measuring what the vault received
+    let (user_token_in, pool_vault_in, user_token_out, pool_vault_out) = if a_to_b {
+        (
+            ctx.accounts.user_token_a.to_account_info(),
+            ctx.accounts.pool_vault_a.to_account_info(),
+            ctx.accounts.user_token_b.to_account_info(),
+            ctx.accounts.pool_vault_b.to_account_info(),
+        )
+    } else {
+        (
+            ctx.accounts.user_token_b.to_account_info(),
+            ctx.accounts.pool_vault_b.to_account_info(),
+            ctx.accounts.user_token_a.to_account_info(),
+            ctx.accounts.pool_vault_a.to_account_info(),
+        )
+    };
+    let balance_before = if a_to_b {
+        ctx.accounts.pool_vault_a.amount
+    } else {
+        ctx.accounts.pool_vault_b.amount
+    };
+
+    let wrap_legs = if let Some(wrap_amount_in) = wrap_amount_in {
+        let (
+            Some(trader_spl_in),
+            Some(backing_vault),
+            Some(wrap_authority),
+            Some(inco_mint),
+            Some(token_program),
+        ) = (
+            ctx.accounts.trader_spl_in.as_ref(),
+            ctx.accounts.spl_backing_vault_in.as_ref(),
+            ctx.accounts.wrap_authority_in.as_ref(),
+            ctx.accounts.inco_mint_in.as_ref(),
+            ctx.accounts.token_program.as_ref(),
+        ) else {
+            return err!(ErrorCode::InvalidWrapAccounts);
+        };
+        require_keys_eq!(inco_mint.key(), mint_in, ErrorCode::InvalidWrapAccounts);
+        let wrap_bump = verify_wrap_authority(wrap_authority.key, backing_vault, inco_mint)?;
+
+        take_wrap_deposit(
+            &token_program.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            &ctx.accounts.trader.to_account_info(),
+            trader_spl_in,
+            backing_vault,
+            wrap_amount_in,
+        )?;
+
+        // The confidential counterpart is minted into the pool vault when the payout settles
+        Some(wrap_deposit_legs(
+            wrap_bump,
+            backing_vault,
+            inco_mint.key(),
+            pool_vault_in.key(),
+            trader_spl_in.key(),
+            amount_in_ciphertext.clone(),
+            wrap_amount_in,
+        ))
+    } else {
+        // CPI: Transfer amount_in from user to pool vault (trader signs as owner or delegate)
+        let transfer_in_ctx = CpiContext::new(
+            inco_token_program,
+            IncoTransfer {
+                source: user_token_in.clone(),
+                destination: pool_vault_in.clone(),
+                authority: ctx.accounts.trader.to_account_info(),
+                inco_lightning_program: inco_program.clone(),
+                system_program: ctx.accounts.system_program.to_account_info(),
+            },
+        );
+        inco_token_transfer(transfer_in_ctx, amount_in_ciphertext.clone(), input_type)?;
+        None
+    };
+
+    // Get reserves based on swap direction
+    let (reserve_in, reserve_out, protocol_fee_in) = pool_account.swap_side(a_to_b);
+
+    // Compute encrypted swap updates
+    let (mut amount_in, mut amount_out, mut fee_amount) = parse_swap_amounts(
+        &inco_program,
+        &signer,
+        &amount_in_ciphertext,
+        &amount_out_ciphertext,
+        &fee_amount_ciphertext,
+        input_type,
+    )?;
+    let (requested_in, requested_out) = (amount_in, amount_out);
+
+    // A wrapped input counts as received, since it is minted only if the payout is approved,
+    // and approval requires that it encrypts exactly the plain deposit
+    let mut approval_checks = Vec::with_capacity(3);
+    let received = match wrap_amount_in {
+        Some(wrap_amount_in) => {
+            approval_checks.push(matches_deposit(&inco_program, &signer, requested_in, wrap_amount_in)?);
+            requested_in
+        }
+        None => vault_received(&inco_program, &signer, &pool_vault_in, balance_before)?,
+    };
+
+    let arrived: Ebool =
+        e_ge(operation(&inco_program, &signer), received, requested_in, SCALAR_BYTE)?;
+
+    let zero = as_euint128(operation(&inco_program, &signer), 0)?;
+
+    amount_in =
+        e_select(operation(&inco_program, &signer), arrived, amount_in, zero, SCALAR_BYTE)?;
+
+    // Reject executions too far from the reference oracle price
+    if let Some(price_q32) = pool_oracle_price(&pool_account, ctx.accounts.oracle.as_deref(), now)? {
+        enforce_price_band(
+            &inco_program,
+            &signer,
+            price_q32,
+            pool_account.oracle_max_deviation_bps,
+            a_to_b,
+            &mut amount_in,
+            &mut amount_out,
+            &mut fee_amount,
+        )?;
+    }
+    let curve = load_swap_curve(
+        &inco_program,
+        &signer,
+        &pool_account,
+        &invariant_ciphertext,
+        input_type,
+        a_to_b,
+        now,
+    )?;
+    let outflow_limit =
+        load_outflow_limit(&inco_program, &signer, &mut pool_account, a_to_b, now)?;
+    let mut updates = compute_swap_updates(
+        &inco_program,
+        &signer,
+        reserve_in,
+        reserve_out,
+        protocol_fee_in,
+        amount_in,
+        amount_out,
+        fee_amount,
+        fee_bps,
+        curve,
+        pool_account.protocol_fee_share_bps,
+        outflow_limit,
+    )?;
+
+    // The swap settles only if it takes the full input and pays the full requested output;
+    // the breaker and every other check zero the validated amounts otherwise
+    let took_input: Ebool =
+        e_ge(operation(&inco_program, &signer), updates.amount_in, requested_in, SCALAR_BYTE)?;
+
+    let paid_output: Ebool = e_ge(
+        operation(&inco_program, &signer),
+        updates.amount_out,
+        requested_out,
+        SCALAR_BYTE,
+    )?;
+
+    approval_checks.extend([took_input, paid_output]);
+    let approved = e_all(&inco_program, &signer, &approval_checks)?;
+    let refunded = refund_due(&inco_program, &signer, approved, received, requested_in)?;
+    gate_swap_updates(&inco_program, &signer, approved, &pool_account, a_to_b, &mut updates)?;
+
+    // Move the referrer's share of the protocol fee to the referrer
+    if let Some(referrer_account) = referrer_account.as_mut() {
+        let referral_fee = compute_fee(
+            &inco_program,
+            &signer,
+            updates.protocol_fee_amount,
+            pool_account.referral_share_bps,
+        )?;
+
+        let referral_fee = e_select(
+            operation(&inco_program, &signer),
+            approved,
+            referral_fee,
+            zero,
+            SCALAR_BYTE,
+        )?;
+
+        updates.new_protocol_fee = e_sub(
+            operation(&inco_program, &signer),
+            updates.new_protocol_fee,
+            referral_fee,
+            SCALAR_BYTE,
+        )?;
+
+        let cpi_ctx = operation(&inco_program, &signer);
+        if a_to_b {
+            referrer_account.fee_a = e_add(cpi_ctx, referrer_account.fee_a, referral_fee, SCALAR_BYTE)?;
+        } else {
+            referrer_account.fee_b = e_add(cpi_ctx, referrer_account.fee_b, referral_fee, SCALAR_BYTE)?;
+        }
+
+        let cpi_ctx = operation(&inco_program, &signer);
+        if a_to_b {
+            pool_account.owed_a = e_add(cpi_ctx, pool_account.owed_a, referral_fee, SCALAR_BYTE)?;
+        } else {
+            pool_account.owed_b = e_add(cpi_ctx, pool_account.owed_b, referral_fee, SCALAR_BYTE)?;
+        }
+    }
+
+    // Update pool state
+    pool_account.apply_swap_updates(&updates, a_to_b);
+    emit!(SwapEvent {
+        pool_address: pool_meta.address,
+        a_to_b,
+        fee_bps,
+        timestamp: now,
+    });
+
+    pool_account.last_update_ts = now;
+    pool_account.bump_state_nonce();
+
+    // A wrapped input is minted before the output is paid, or its deposit refunded
+    let (mut on_approved, on_refunded) = match wrap_legs {
+        Some((mint_leg, refund_leg)) => (vec![mint_leg], vec![refund_leg]),
+        None => (
+            Vec::with_capacity(1),
+            vec![PayoutLeg::transfer(
+                pool_account.authority_seeds(),
+                pool_vault_in.key(),
+                user_token_in.key(),
+                amount_in_ciphertext,
+            )],
+        ),
+    };
+    on_approved.push(PayoutLeg::transfer(
+        pool_account.authority_seeds(),
+        pool_vault_out.key(),
+        user_token_out.key(),
+        amount_out_ciphertext,
+    ));
+
+    // The trader decrypts the outcome to settle the payout
+    allow_payout_outcome(
+        &inco_program,
+        &signer,
+        &ctx.accounts.system_program.to_account_info(),
+        &ctx.accounts.trader.to_account_info(),
+        &[
+            (approved, ctx.accounts.approved_allowance.to_account_info()),
+            (refunded, ctx.accounts.refunded_allowance.to_account_info()),
+        ],
+    )?;
+
+    let tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|error| ProgramError::Custom(error.into()))?;
+    let trader = ctx.accounts.trader.key();
+    let (address, address_seed) = derive_address(
+        &[PAYOUT_SEED, trader.as_ref(), &payout_nonce.to_le_bytes()],
+        &tree_pubkey,
+        &crate::ID,
+    );
+    // The payout follows the pool and, if present, the referrer account
+    let payout_index = 1 + referrer_account.is_some() as u8;
+    let new_address_params =
+        address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(payout_index));
+
+    let mut payout_account = LightAccount::<PendingPayout>::new_init(
+        &crate::ID,
+        Some(address),
+        output_tree_index,
+    );
+    payout_account.beneficiary = trader;
+    payout_account.input_type = input_type;
+    payout_account.approved = approved;
+    payout_account.on_approved = on_approved;
+    payout_account.refunded = refunded;
+    payout_account.on_refunded = on_refunded;
+    payout_account.created_at = now;
+
+    // Commit pool state, referrer fees (if any) and the payout to Light Protocol
+    let mut cpi = LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
+        .with_light_account(pool_account)?;
+    if let Some(referrer_account) = referrer_account {
+        cpi = cpi.with_light_account(referrer_account)?;
+    }
+    cpi.with_light_account(payout_account)?
+        .with_new_addresses(&[new_address_params])
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
@@ -0,0 +1,326 @@
+//! Multi-hop swap across a route of pools
+
+use anchor_lang::prelude::*;
+use inco_lightning::cpi::{as_euint128, e_ge, e_select, new_euint128};
+use inco_lightning::types::Ebool;
+use inco_lightning::ID as INCO_LIGHTNING_ID;
+use light_sdk::{
+    account::LightAccount,
+    address::v2::derive_address,
+    cpi::v2::{CpiAccounts, LightSystemProgramCpi},
+    cpi::{InvokeLightSystemProgram, LightCpiInstruction},
+    instruction::{
+        PackedAddressTreeInfo, PackedAddressTreeInfoExt, ValidityProof as SdkValidityProof,
+    },
+};
+
+use crate::constants::*;
+use crate::curve::*;
+use crate::errors::ErrorCode;
+use crate::events::*;
+use crate::fhe::*;
+use crate::inco_token::cpi::accounts::IncoTransfer;
+use crate::inco_token::cpi::transfer as inco_token_transfer;
+use crate::inco_token::{IncoAccount, ID as INCO_TOKEN_ID};
+use crate::oracle::*;
+use crate::payout::*;
+use crate::state::*;
+use crate::vault::*;
+
+#[derive(Accounts)]
+pub struct SwapRoute<'info> {
+    /// Pays Light and Inco fees; may be a relayer distinct from the trader
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+    /// Owner or approved delegate of the user's input token account; decrypts the payout outcome
+    pub trader: Signer<'info>,
+    /// User's Inco token account for the route's input mint
+    #[account(mut)]
+    pub user_token_in: Account<'info, IncoAccount>,
+    /// User's Inco token account for the route's output mint
+    #[account(mut)]
+    pub user_token_out: Account<'info, IncoAccount>,
+    /// CHECK: Inco allowance PDA for (payout approved handle, trader)
+    #[account(mut)]
+    pub approved_allowance: AccountInfo<'info>,
+    /// CHECK: Inco allowance PDA for (payout refunded handle, trader)
+    #[account(mut)]
+    pub refunded_allowance: AccountInfo<'info>,
+    /// CHECK: Inco Lightning program for encrypted operations
+    #[account(address = INCO_LIGHTNING_ID)]
+    pub inco_lightning_program: AccountInfo<'info>,
+    /// CHECK: Inco Token program for token transfers
+    #[account(address = INCO_TOKEN_ID)]
+    pub inco_token_program: AccountInfo<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handle_swap_route<'info>(
+    ctx: Context<'_, '_, '_, 'info, SwapRoute<'info>>,
+    proof: SdkValidityProof,
+    address_tree_info: PackedAddressTreeInfo,
+    output_tree_index: u8,
+    payout_nonce: u64,
+    pool_metas: Vec<light_sdk::instruction::account_meta::CompressedAccountMeta>,
+    pool_data: Vec<Vec<u8>>,
+    amount_in_ciphertext: Vec<u8>,
+    amount_out_ciphertexts: Vec<Vec<u8>>,
+    fee_amount_ciphertexts: Vec<Vec<u8>>,
+    invariant_ciphertexts: Vec<Vec<u8>>,
+    input_type: u8,
+    a_to_b: Vec<bool>,
+) -> Result<()> {
+    let hops = pool_metas.len();
+    require!(hops > 0 && hops <= MAX_ROUTE_HOPS, ErrorCode::InvalidRoute);
+    require!(
+        pool_data.len() == hops
+            && amount_out_ciphertexts.len() == hops
+            && fee_amount_ciphertexts.len() == hops
+            && invariant_ciphertexts.len() == hops
+            && a_to_b.len() == hops,
+        ErrorCode::InvalidRoute
+    );
+    require!(
+        ctx.remaining_accounts.len() > hops * ROUTE_ACCOUNTS_PER_HOP,
+        ErrorCode::InvalidRoute
+    );
+    let (hop_accounts, light_accounts) =
+        ctx.remaining_accounts.split_at(hops * ROUTE_ACCOUNTS_PER_HOP);
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.fee_payer.as_ref(),
+        light_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let inco_program = ctx.accounts.inco_lightning_program.to_account_info();
+    let signer = ctx.accounts.fee_payer.to_account_info();
+    let now = Clock::get()?.unix_timestamp;
+
+    // CPI: Transfer amount_in from user to the first pool vault (trader signs),
+    // measuring what the vault actually received
+    let first_vault_in = &hop_accounts[1];
+    let balance_before = inco_balance(first_vault_in)?;
+    let transfer_in_ctx = CpiContext::new(
+        ctx.accounts.inco_token_program.to_account_info(),
+        IncoTransfer {
+            source: ctx.accounts.user_token_in.to_account_info(),
+            destination: first_vault_in.clone(),
+            authority: ctx.accounts.trader.to_account_info(),
+            inco_lightning_program: inco_program.clone(),
+            system_program: ctx.accounts.system_program.to_account_info(),
+        },
+    );
+    inco_token_transfer(transfer_in_ctx, amount_in_ciphertext.clone(), input_type)?;
+    let received = vault_received(&inco_program, &signer, first_vault_in, balance_before)?;
+
+    // First hop consumes the trader's amount, zeroed unless it arrived in full;
+    // later hops consume the previous hop's output
+    let requested_in = new_euint128(
+        operation(&inco_program, &signer),
+        amount_in_ciphertext.clone(),
+        input_type,
+    )?;
+
+    let arrived: Ebool =
+        e_ge(operation(&inco_program, &signer), received, requested_in, SCALAR_BYTE)?;
+
+    let zero = as_euint128(operation(&inco_program, &signer), 0)?;
+
+    let mut amount_in =
+        e_select(operation(&inco_program, &signer), arrived, requested_in, zero, SCALAR_BYTE)?;
+    let mut expected_mint_in = ctx.accounts.user_token_in.mint;
+
+    let mut hop_updates = Vec::with_capacity(hops);
+    let mut hops_ok = Vec::with_capacity(hops + 1);
+    for (hop, (pool_meta, data)) in pool_metas.iter().zip(pool_data.iter()).enumerate() {
+        let pool_state = SwapPool::load(data)?;
+        let mut pool_account = LightAccount::<SwapPool>::new_mut(
+            &crate::ID,
+            pool_meta,
+            pool_state,
+        )?;
+
+        require!(!pool_account.is_paused, ErrorCode::PoolPaused);
+        require!(!pool_account.batch_mode, ErrorCode::BatchModeActive);
+
+        let (mint_in, mint_out) = pool_account.swap_mints(a_to_b[hop]);
+        require_keys_eq!(mint_in, expected_mint_in, ErrorCode::InvalidInputMint);
+
+        // Verify pool authority and vaults supplied for this hop
+        let accounts = &hop_accounts[hop * ROUTE_ACCOUNTS_PER_HOP..(hop + 1) * ROUTE_ACCOUNTS_PER_HOP];
+        require_keys_eq!(accounts[0].key(), pool_account.pool_authority);
+        let (vault_in, vault_out) = pool_account.swap_vaults(a_to_b[hop]);
+        require!(accounts[1].key() == vault_in, ErrorCode::InvalidPoolVault);
+        require!(accounts[2].key() == vault_out, ErrorCode::InvalidPoolVault);
+
+        accumulate_prices(&inco_program, &signer, &mut pool_account, now)?;
+        let fee_bps = pool_account.effective_fee_bps(now);
+        let (reserve_in, reserve_out, protocol_fee_in) = pool_account.swap_side(a_to_b[hop]);
+
+        let requested_out = new_euint128(
+            operation(&inco_program, &signer),
+            amount_out_ciphertexts[hop].clone(),
+            input_type,
+        )?;
+        let mut amount_out = requested_out;
+
+        let mut fee_amount = new_euint128(
+            operation(&inco_program, &signer),
+            fee_amount_ciphertexts[hop].clone(),
+            input_type,
+        )?;
+        if let Some(price_q32) = pool_oracle_price(&pool_account, Some(&accounts[3]), now)? {
+            enforce_price_band(
+                &inco_program,
+                &signer,
+                price_q32,
+                pool_account.oracle_max_deviation_bps,
+                a_to_b[hop],
+                &mut amount_in,
+                &mut amount_out,
+                &mut fee_amount,
+            )?;
+        }
+
+        let curve = load_swap_curve(
+            &inco_program,
+            &signer,
+            &pool_account,
+            &invariant_ciphertexts[hop],
+            input_type,
+            a_to_b[hop],
+            now,
+        )?;
+        let outflow_limit =
+            load_outflow_limit(&inco_program, &signer, &mut pool_account, a_to_b[hop], now)?;
+        let updates = compute_swap_updates(
+            &inco_program,
+            &signer,
+            reserve_in,
+            reserve_out,
+            protocol_fee_in,
+            amount_in,
+            amount_out,
+            fee_amount,
+            fee_bps,
+            curve,
+            pool_account.protocol_fee_share_bps,
+            outflow_limit,
+        )?;
+        emit!(SwapEvent {
+            pool_address: pool_meta.address,
+            a_to_b: a_to_b[hop],
+            fee_bps,
+            timestamp: now,
+        });
+        pool_account.last_update_ts = now;
+        pool_account.bump_state_nonce();
+
+        // The hop passes when it takes the full input and pays the full requested output
+        if hop == 0 {
+            hops_ok.push(e_ge(
+                operation(&inco_program, &signer),
+                updates.amount_in,
+                requested_in,
+                SCALAR_BYTE,
+            )?);
+        }
+        hops_ok.push(e_ge(
+            operation(&inco_program, &signer),
+            updates.amount_out,
+            requested_out,
+            SCALAR_BYTE,
+        )?);
+
+        amount_in = updates.amount_out;
+        expected_mint_in = mint_out;
+        hop_updates.push((pool_account, updates));
+    }
+
+    require_keys_eq!(
+        ctx.accounts.user_token_out.mint,
+        expected_mint_in,
+        ErrorCode::InvalidOutputMint
+    );
+
+    // The route settles all or nothing: undo every hop's pool update unless all hops passed
+    let approved = e_all(&inco_program, &signer, &hops_ok)?;
+    let refunded = refund_due(&inco_program, &signer, approved, received, requested_in)?;
+
+    let mut on_approved = Vec::with_capacity(hops);
+    let mut pool_accounts = Vec::with_capacity(hops);
+    for (hop, (mut pool_account, mut updates)) in hop_updates.into_iter().enumerate() {
+        gate_swap_updates(&inco_program, &signer, approved, &pool_account, a_to_b[hop], &mut updates)?;
+        pool_account.apply_swap_updates(&updates, a_to_b[hop]);
+
+        // Forward each hop's output to the next hop's vault, the last one to the user
+        let destination = if hop + 1 < hops {
+            hop_accounts[(hop + 1) * ROUTE_ACCOUNTS_PER_HOP + 1].key()
+        } else {
+            ctx.accounts.user_token_out.key()
+        };
+        on_approved.push(PayoutLeg::transfer(
+            pool_account.authority_seeds(),
+            hop_accounts[hop * ROUTE_ACCOUNTS_PER_HOP + 2].key(),
+            destination,
+            amount_out_ciphertexts[hop].clone(),
+        ));
+        pool_accounts.push(pool_account);
+    }
+    let on_refunded = vec![PayoutLeg::transfer(
+        pool_accounts[0].authority_seeds(),
+        first_vault_in.key(),
+        ctx.accounts.user_token_in.key(),
+        amount_in_ciphertext,
+    )];
+
+    // The trader decrypts the outcome to settle the payout
+    allow_payout_outcome(
+        &inco_program,
+        &signer,
+        &ctx.accounts.system_program.to_account_info(),
+        &ctx.accounts.trader.to_account_info(),
+        &[
+            (approved, ctx.accounts.approved_allowance.to_account_info()),
+            (refunded, ctx.accounts.refunded_allowance.to_account_info()),
+        ],
+    )?;
+
+    let tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|error| ProgramError::Custom(error.into()))?;
+    let trader = ctx.accounts.trader.key();
+    let (address, address_seed) = derive_address(
+        &[PAYOUT_SEED, trader.as_ref(), &payout_nonce.to_le_bytes()],
+        &tree_pubkey,
+        &crate::ID,
+    );
+    let new_address_params =
+        address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(hops as u8));
+
+    let mut payout_account = LightAccount::<PendingPayout>::new_init(
+        &crate::ID,
+        Some(address),
+        output_tree_index,
+    );
+    payout_account.beneficiary = trader;
+    payout_account.input_type = input_type;
+    payout_account.approved = approved;
+    payout_account.on_approved = on_approved;
+    payout_account.refunded = refunded;
+    payout_account.on_refunded = on_refunded;
+    payout_account.created_at = now;
+
+    // Commit all pool state updates and the payout with a single Light Protocol CPI
+    let mut light_cpi = LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof);
+    for pool_account in pool_accounts {
+        light_cpi = light_cpi.with_light_account(pool_account)?;
+    }
+    light_cpi
+        .with_light_account(payout_account)?
+        .with_new_addresses(&[new_address_params])
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
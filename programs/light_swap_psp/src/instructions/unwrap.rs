@@ -0,0 +1,264 @@
+//! Unwrapping pool mints back into the underlying SPL token or SOL
+
+use anchor_lang::prelude::*;
+use anchor_spl::token::{transfer as spl_transfer, Token, TokenAccount, Transfer as SplTransfer};
+use inco_lightning::cpi::accounts::{Allow, VerifySignature};
+use inco_lightning::cpi::{allow, as_euint128, e_ge, e_sub, is_validsignature};
+use inco_lightning::types::Ebool;
+use inco_lightning::ID as INCO_LIGHTNING_ID;
+use light_sdk::{
+    account::LightAccount,
+    address::v2::derive_address,
+    cpi::v2::{CpiAccounts, LightSystemProgramCpi},
+    cpi::{InvokeLightSystemProgram, LightCpiInstruction},
+    instruction::{
+        PackedAddressTreeInfo, PackedAddressTreeInfoExt, ValidityProof as SdkValidityProof,
+    },
+};
+
+use crate::constants::*;
+use crate::errors::ErrorCode;
+use crate::fhe::*;
+use crate::inco_token::cpi::accounts::Burn as IncoBurn;
+use crate::inco_token::cpi::burn as inco_token_burn;
+use crate::inco_token::{IncoAccount, IncoMint, ID as INCO_TOKEN_ID};
+use crate::state::*;
+use crate::wrap::*;
+
+#[derive(Accounts)]
+pub struct RequestUnwrap<'info> {
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    /// Owner's Inco token account of the wrapped mint
+    #[account(mut)]
+    pub owner_token: Account<'info, IncoAccount>,
+    #[account(mut)]
+    pub inco_mint: Account<'info, IncoMint>,
+    /// CHECK: Wrap authority PDA, verified against the backing vault and Inco mint
+    pub wrap_authority: AccountInfo<'info>,
+    /// SPL backing vault of the wrapped mint (owned by wrap_authority)
+    pub spl_backing_vault: Account<'info, TokenAccount>,
+    /// SPL token account the ticket pays out to
+    pub recipient: Account<'info, TokenAccount>,
+    /// CHECK: Inco allowance PDA for (burn_covers handle, owner)
+    #[account(mut)]
+    pub allowance: AccountInfo<'info>,
+    /// CHECK: Inco Lightning program for encrypted operations
+    #[account(address = INCO_LIGHTNING_ID)]
+    pub inco_lightning_program: AccountInfo<'info>,
+    /// CHECK: Inco Token program for the burn
+    #[account(address = INCO_TOKEN_ID)]
+    pub inco_token_program: AccountInfo<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimUnwrap<'info> {
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+    pub owner: Signer<'info>,
+    pub inco_mint: Account<'info, IncoMint>,
+    /// CHECK: Wrap authority PDA, verified against the backing vault and Inco mint
+    pub wrap_authority: AccountInfo<'info>,
+    /// SPL backing vault of the wrapped mint (owned by wrap_authority)
+    #[account(mut)]
+    pub spl_backing_vault: Account<'info, TokenAccount>,
+    /// SPL token account recorded in the ticket
+    #[account(mut)]
+    pub recipient: Account<'info, TokenAccount>,
+    /// CHECK: Instructions sysvar holding the Ed25519 decryption attestation
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: AccountInfo<'info>,
+    /// CHECK: Inco Lightning program for encrypted operations
+    #[account(address = INCO_LIGHTNING_ID)]
+    pub inco_lightning_program: AccountInfo<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+pub fn handle_request_unwrap<'info>(
+    ctx: Context<'_, '_, '_, 'info, RequestUnwrap<'info>>,
+    proof: SdkValidityProof,
+    address_tree_info: PackedAddressTreeInfo,
+    output_tree_index: u8,
+    ticket_nonce: u64,
+    amount: u64,
+    ciphertext: Vec<u8>,
+    input_type: u8,
+) -> Result<()> {
+    require!(amount > 0, ErrorCode::InvalidUnwrapTicket);
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.fee_payer.as_ref(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    verify_wrap_authority(
+        ctx.accounts.wrap_authority.key,
+        &ctx.accounts.spl_backing_vault,
+        &ctx.accounts.inco_mint,
+    )?;
+    require_keys_eq!(ctx.accounts.owner_token.owner, ctx.accounts.owner.key(), ErrorCode::Unauthorized);
+    require_keys_eq!(ctx.accounts.owner_token.mint, ctx.accounts.inco_mint.key(), ErrorCode::InvalidInputMint);
+    require_keys_eq!(
+        ctx.accounts.recipient.mint,
+        ctx.accounts.spl_backing_vault.mint,
+        ErrorCode::InvalidOutputMint
+    );
+
+    let inco_program = ctx.accounts.inco_lightning_program.to_account_info();
+    let signer = ctx.accounts.fee_payer.to_account_info();
+
+    // CPI: Burn from the owner's account, measuring what was actually burned
+    let balance_before = ctx.accounts.owner_token.amount;
+    let burn_ctx = CpiContext::new(
+        ctx.accounts.inco_token_program.to_account_info(),
+        IncoBurn {
+            account: ctx.accounts.owner_token.to_account_info(),
+            mint: ctx.accounts.inco_mint.to_account_info(),
+            authority: ctx.accounts.owner.to_account_info(),
+            inco_lightning_program: inco_program.clone(),
+            system_program: ctx.accounts.system_program.to_account_info(),
+        },
+    );
+    inco_token_burn(burn_ctx, ciphertext, input_type)?;
+    ctx.accounts.owner_token.reload()?;
+
+    let burned = e_sub(
+        operation(&inco_program, &signer),
+        balance_before,
+        ctx.accounts.owner_token.amount,
+        SCALAR_BYTE,
+    )?;
+
+    let requested = as_euint128(operation(&inco_program, &signer), amount as u128)?;
+
+    let burn_covers: Ebool =
+        e_ge(operation(&inco_program, &signer), burned, requested, SCALAR_BYTE)?;
+
+    let cpi_ctx = CpiContext::new(
+        inco_program,
+        Allow {
+            allowance_account: ctx.accounts.allowance.to_account_info(),
+            signer,
+            allowed_address: ctx.accounts.owner.to_account_info(),
+            system_program: ctx.accounts.system_program.to_account_info(),
+        },
+    );
+    allow(cpi_ctx, burn_covers.0, true, ctx.accounts.owner.key())?;
+
+    let tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|error| ProgramError::Custom(error.into()))?;
+    let owner = ctx.accounts.owner.key();
+    let (address, address_seed) = derive_address(
+        &[UNWRAP_TICKET_SEED, owner.as_ref(), &ticket_nonce.to_le_bytes()],
+        &tree_pubkey,
+        &crate::ID,
+    );
+    let new_address_params =
+        address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0));
+
+    let mut ticket_account = LightAccount::<UnwrapTicket>::new_init(
+        &crate::ID,
+        Some(address),
+        output_tree_index,
+    );
+    ticket_account.owner = owner;
+    ticket_account.spl_mint = ctx.accounts.spl_backing_vault.mint;
+    ticket_account.inco_mint = ctx.accounts.inco_mint.key();
+    ticket_account.recipient = ctx.accounts.recipient.key();
+    ticket_account.amount = amount;
+    ticket_account.burn_covers = burn_covers;
+    ticket_account.created_at = Clock::get()?.unix_timestamp;
+
+    LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
+        .with_light_account(ticket_account)?
+        .with_new_addresses(&[new_address_params])
+        .invoke(light_cpi_accounts)?;
+    Ok(())
+}
+
+pub fn handle_claim_unwrap<'info>(
+    ctx: Context<'_, '_, '_, 'info, ClaimUnwrap<'info>>,
+    proof: SdkValidityProof,
+    ticket_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+    ticket_data: Vec<u8>,
+    burn_covers_plaintext: Vec<u8>,
+) -> Result<()> {
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.fee_payer.as_ref(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let ticket_state = UnwrapTicket::try_from_slice(&ticket_data)?;
+    let ticket = ticket_state.clone();
+    let ticket_account = LightAccount::<UnwrapTicket>::new_close(
+        &crate::ID,
+        &ticket_meta,
+        ticket_state,
+    )?;
+
+    require_keys_eq!(ticket.owner, ctx.accounts.owner.key(), ErrorCode::Unauthorized);
+    require_keys_eq!(ticket.recipient, ctx.accounts.recipient.key(), ErrorCode::InvalidUnwrapTicket);
+    require_keys_eq!(ticket.inco_mint, ctx.accounts.inco_mint.key(), ErrorCode::InvalidUnwrapTicket);
+    require_keys_eq!(
+        ticket.spl_mint,
+        ctx.accounts.spl_backing_vault.mint,
+        ErrorCode::InvalidUnwrapTicket
+    );
+    let wrap_bump = verify_wrap_authority(
+        ctx.accounts.wrap_authority.key,
+        &ctx.accounts.spl_backing_vault,
+        &ctx.accounts.inco_mint,
+    )?;
+    require!(
+        burn_covers_plaintext.iter().any(|byte| *byte != 0),
+        ErrorCode::UnwrapNotAttested
+    );
+
+    // The attested plaintext of burn_covers must be true
+    let cpi_ctx = CpiContext::new(
+        ctx.accounts.inco_lightning_program.to_account_info(),
+        VerifySignature {
+            instructions: ctx.accounts.instructions.to_account_info(),
+            signer: ctx.accounts.fee_payer.to_account_info(),
+        },
+    );
+    is_validsignature(
+        cpi_ctx,
+        1,
+        Some(vec![ticket.burn_covers.0.to_le_bytes().to_vec()]),
+        Some(vec![burn_covers_plaintext]),
+    )?;
+
+    // CPI: Pay out the SPL backing (wrap authority signs)
+    let inco_mint_key = ticket.inco_mint;
+    let wrap_seeds: &[&[u8]] = &[
+        WRAP_AUTHORITY_SEED,
+        ticket.spl_mint.as_ref(),
+        inco_mint_key.as_ref(),
+        &[wrap_bump],
+    ];
+    let signer_seeds = &[wrap_seeds];
+    let payout_ctx = CpiContext::new_with_signer(
+        ctx.accounts.token_program.to_account_info(),
+        SplTransfer {
+            from: ctx.accounts.spl_backing_vault.to_account_info(),
+            to: ctx.accounts.recipient.to_account_info(),
+            authority: ctx.accounts.wrap_authority.to_account_info(),
+        },
+        signer_seeds,
+    );
+    spl_transfer(payout_ctx, ticket.amount)?;
+
+    // Nullify the ticket
+    LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
+        .with_light_account(ticket_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
@@ -0,0 +1,10 @@
+//! Accounts shared by the pool configuration instructions
+
+use anchor_lang::prelude::*;
+
+#[derive(Accounts)]
+pub struct UpdatePoolConfig<'info> {
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+    pub authority: Signer<'info>,
+}
@@ -1,507 +1,1113 @@
+// Anchor's generated IDL resize handler still calls the deprecated `AccountInfo::realloc`
+#![allow(deprecated)]
+// Instruction handlers and FHE helpers take their operands as flat arguments
+#![allow(clippy::too_many_arguments)]
+
 use anchor_lang::prelude::*;
-use inco_lightning::cpi::accounts::Operation;
-use inco_lightning::cpi::{as_euint128, e_add, e_ge, e_mul, e_select, e_sub, new_euint128};
-use inco_lightning::types::{Ebool, Euint128};
-use inco_lightning::ID as INCO_LIGHTNING_ID;
-use inco_token::cpi::accounts::IncoTransfer;
-use inco_token::cpi::transfer as inco_token_transfer;
-use inco_token::{IncoAccount, ID as INCO_TOKEN_ID};
 use light_sdk::{
-    account::LightAccount,
-    address::v2::derive_address,
-    cpi::v2::{CpiAccounts, LightSystemProgramCpi},
-    cpi::{InvokeLightSystemProgram, LightCpiInstruction},
     derive_light_cpi_signer,
-    instruction::{PackedAddressTreeInfo, PackedAddressTreeInfoExt, ValidityProof as SdkValidityProof},
-    CpiSigner, LightDiscriminator,
+    instruction::{PackedAddressTreeInfo, ValidityProof as SdkValidityProof},
+    CpiSigner,
 };
 
+mod constants;
+mod curve;
+pub mod errors;
+pub mod events;
+mod fhe;
+mod inco_token;
+pub mod instructions;
+pub mod oracle;
+mod payout;
+pub mod state;
+mod vault;
+mod wrap;
+
+pub use errors::ErrorCode;
+pub use events::*;
+pub use instructions::*;
+pub use state::*;
+
 declare_id!("4b8jCufu7b4WKXdxFRQHWSks4QdskW62qF7tApSNXuZD");
 
 pub const LIGHT_CPI_SIGNER: CpiSigner =
     derive_light_cpi_signer!("4b8jCufu7b4WKXdxFRQHWSks4QdskW62qF7tApSNXuZD");
 
-const POOL_AUTH_SEED: &[u8] = b"pool_authority";
-const POOL_VAULT_SEED: &[u8] = b"pool_vault";
-const SCALAR_BYTE: u8 = 0;
-
-/// Compute encrypted swap updates using Inco Lightning FHE operations
-#[inline(never)]
-fn compute_swap_updates<'info>(
-    inco_program: &AccountInfo<'info>,
-    signer: &AccountInfo<'info>,
-    reserve_in: Euint128,
-    reserve_out: Euint128,
-    protocol_fee_in: Euint128,
-    amount_in_ciphertext: &[u8],
-    amount_out_ciphertext: &[u8],
-    fee_amount_ciphertext: &[u8],
-    input_type: u8,
-) -> Result<(Euint128, Euint128, Euint128)> {
-    let cpi_ctx = CpiContext::new(inco_program.clone(), Operation { signer: signer.clone() });
-    let mut amount_in = new_euint128(cpi_ctx, amount_in_ciphertext.to_vec(), input_type)?;
-
-    let cpi_ctx = CpiContext::new(inco_program.clone(), Operation { signer: signer.clone() });
-    let mut amount_out = new_euint128(cpi_ctx, amount_out_ciphertext.to_vec(), input_type)?;
-
-    let cpi_ctx = CpiContext::new(inco_program.clone(), Operation { signer: signer.clone() });
-    let mut fee_amount = new_euint128(cpi_ctx, fee_amount_ciphertext.to_vec(), input_type)?;
+#[program]
+pub mod light_swap_psp {
+    use super::*;
 
-    let cpi_ctx = CpiContext::new(inco_program.clone(), Operation { signer: signer.clone() });
-    let zero = as_euint128(cpi_ctx, 0)?;
+    pub fn initialize_pool<'info>(
+        ctx: Context<'_, '_, '_, 'info, InitializePool<'info>>,
+        proof: SdkValidityProof,
+        address_tree_info: PackedAddressTreeInfo,
+        output_tree_index: u8,
+        mint_a: Pubkey,
+        mint_b: Pubkey,
+        fee_bps: u16,
+        pool_kind: PoolKind,
+        amp: u64,
+        weight_a: u8,
+        weight_b: u8,
+        curve_scale_bits: u8,
+    ) -> Result<()> {
+        instructions::handle_initialize_pool(
+            ctx,
+            proof,
+            address_tree_info,
+            output_tree_index,
+            mint_a,
+            mint_b,
+            fee_bps,
+            pool_kind,
+            amp,
+            weight_a,
+            weight_b,
+            curve_scale_bits,
+        )
+    }
 
-    // Check liquidity: reserve_out >= amount_out
-    let cpi_ctx = CpiContext::new(inco_program.clone(), Operation { signer: signer.clone() });
-    let has_liquidity: Ebool = e_ge(cpi_ctx, reserve_out, amount_out, SCALAR_BYTE)?;
+    /// Add liquidity to the pool with encrypted amounts
+    pub fn add_liquidity<'info>(
+        ctx: Context<'_, '_, '_, 'info, AddLiquidity<'info>>,
+        proof: SdkValidityProof,
+        pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+        pool_data: Vec<u8>,
+        amount_a_ciphertext: Vec<u8>,
+        amount_b_ciphertext: Vec<u8>,
+        input_type: u8,
+    ) -> Result<()> {
+        instructions::handle_add_liquidity(
+            ctx,
+            proof,
+            pool_meta,
+            pool_data,
+            amount_a_ciphertext,
+            amount_b_ciphertext,
+            input_type,
+        )
+    }
 
-    // Zero out amounts if no liquidity
-    let cpi_ctx = CpiContext::new(inco_program.clone(), Operation { signer: signer.clone() });
-    amount_in = e_select(cpi_ctx, has_liquidity, amount_in, zero, SCALAR_BYTE)?;
+    /// Add liquidity to one side from a plain SPL/SOL deposit, wrapped through a pending
+    /// payout to the authority. `amount_ciphertext` must encrypt exactly `wrap_amount`: then
+    /// the reserve is credited and the payout mints the wrapped amount into the pool vault;
+    /// otherwise the reserve is unchanged and the payout refunds the deposit.
+    pub fn add_liquidity_wrapped<'info>(
+        ctx: Context<'_, '_, '_, 'info, AddLiquidityWrapped<'info>>,
+        proof: SdkValidityProof,
+        address_tree_info: PackedAddressTreeInfo,
+        output_tree_index: u8,
+        payout_nonce: u64,
+        pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+        pool_data: Vec<u8>,
+        wrap_amount: u64,
+        amount_ciphertext: Vec<u8>,
+        input_type: u8,
+        deposit_a: bool,
+    ) -> Result<()> {
+        instructions::handle_add_liquidity_wrapped(
+            ctx,
+            proof,
+            address_tree_info,
+            output_tree_index,
+            payout_nonce,
+            pool_meta,
+            pool_data,
+            wrap_amount,
+            amount_ciphertext,
+            input_type,
+            deposit_a,
+        )
+    }
 
-    let cpi_ctx = CpiContext::new(inco_program.clone(), Operation { signer: signer.clone() });
-    amount_out = e_select(cpi_ctx, has_liquidity, amount_out, zero, SCALAR_BYTE)?;
+    /// Remove liquidity from the pool with encrypted amounts
+    pub fn remove_liquidity<'info>(
+        ctx: Context<'_, '_, '_, 'info, RemoveLiquidity<'info>>,
+        proof: SdkValidityProof,
+        pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+        pool_data: Vec<u8>,
+        amount_a_ciphertext: Vec<u8>,
+        amount_b_ciphertext: Vec<u8>,
+        input_type: u8,
+    ) -> Result<()> {
+        instructions::handle_remove_liquidity(
+            ctx,
+            proof,
+            pool_meta,
+            pool_data,
+            amount_a_ciphertext,
+            amount_b_ciphertext,
+            input_type,
+        )
+    }
 
-    let cpi_ctx = CpiContext::new(inco_program.clone(), Operation { signer: signer.clone() });
-    fee_amount = e_select(cpi_ctx, has_liquidity, fee_amount, zero, SCALAR_BYTE)?;
+    /// Add liquidity from one side of the pair. `swap_amount` of the deposit, fee included,
+    /// is swapped through the pool curve and the output is deposited
+    /// together with the rest, all in a single pool update. If the swap is rejected the
+    /// whole deposit is added to the input side.
+    pub fn add_liquidity_single<'info>(
+        ctx: Context<'_, '_, '_, 'info, AddLiquidity<'info>>,
+        proof: SdkValidityProof,
+        pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+        pool_data: Vec<u8>,
+        amount_ciphertext: Vec<u8>,
+        swap_amount_ciphertext: Vec<u8>,
+        swap_out_ciphertext: Vec<u8>,
+        fee_amount_ciphertext: Vec<u8>,
+        invariant_ciphertext: Vec<u8>,
+        input_type: u8,
+        deposit_a: bool,
+    ) -> Result<()> {
+        instructions::handle_add_liquidity_single(
+            ctx,
+            proof,
+            pool_meta,
+            pool_data,
+            amount_ciphertext,
+            swap_amount_ciphertext,
+            swap_out_ciphertext,
+            fee_amount_ciphertext,
+            invariant_ciphertext,
+            input_type,
+            deposit_a,
+        )
+    }
 
-    // Calculate new reserves
-    let cpi_ctx = CpiContext::new(inco_program.clone(), Operation { signer: signer.clone() });
-    let temp_reserve_in = e_add(cpi_ctx, reserve_in, amount_in, SCALAR_BYTE)?;
+    /// Start ramping the StableSwap amplification coefficient towards `future_amp`
+    pub fn ramp_amp<'info>(
+        ctx: Context<'_, '_, '_, 'info, UpdatePoolConfig<'info>>,
+        proof: SdkValidityProof,
+        pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+        pool_data: Vec<u8>,
+        future_amp: u64,
+        ramp_end_ts: i64,
+    ) -> Result<()> {
+        instructions::handle_ramp_amp(ctx, proof, pool_meta, pool_data, future_amp, ramp_end_ts)
+    }
 
-    let cpi_ctx = CpiContext::new(inco_program.clone(), Operation { signer: signer.clone() });
-    let temp_reserve_out = e_sub(cpi_ctx, reserve_out, amount_out, SCALAR_BYTE)?;
+    /// Freeze the StableSwap amplification coefficient at its current value
+    pub fn stop_ramp_amp<'info>(
+        ctx: Context<'_, '_, '_, 'info, UpdatePoolConfig<'info>>,
+        proof: SdkValidityProof,
+        pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+        pool_data: Vec<u8>,
+    ) -> Result<()> {
+        instructions::handle_stop_ramp_amp(ctx, proof, pool_meta, pool_data)
+    }
 
-    // Verify constant product invariant: new_k >= old_k
-    let cpi_ctx = CpiContext::new(inco_program.clone(), Operation { signer: signer.clone() });
-    let old_k = e_mul(cpi_ctx, reserve_in, reserve_out, SCALAR_BYTE)?;
+    /// Execute a private swap with encrypted amounts
+    /// The input moves into the pool vault up front; the output is left to a `PendingPayout`
+    /// that pays it only if the swap passes every check, circuit breaker included, and
+    /// otherwise undoes the pool update and refunds the input.
+    /// `fee_payer` may be a relayer; `trader` authorizes the input transfer as owner or delegate
+    /// With `wrap_amount_in`, the input is taken as a plain SPL/SOL deposit instead.
+    /// `amount_in_ciphertext` must encrypt exactly the deposit, which the payout then mints
+    /// into the pool vault; otherwise the swap is rejected and the deposit refunded.
+    pub fn swap_exact_in<'info>(
+        ctx: Context<'_, '_, '_, 'info, SwapExactIn<'info>>,
+        proof: SdkValidityProof,
+        address_tree_info: PackedAddressTreeInfo,
+        output_tree_index: u8,
+        payout_nonce: u64,
+        pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+        pool_data: Vec<u8>,
+        amount_in_ciphertext: Vec<u8>,
+        amount_out_ciphertext: Vec<u8>,
+        fee_amount_ciphertext: Vec<u8>,
+        invariant_ciphertext: Vec<u8>,
+        input_type: u8,
+        a_to_b: bool,
+        referrer: Option<ReferrerAccountData>,
+        wrap_amount_in: Option<u64>,
+    ) -> Result<()> {
+        instructions::handle_swap_exact_in(
+            ctx,
+            proof,
+            address_tree_info,
+            output_tree_index,
+            payout_nonce,
+            pool_meta,
+            pool_data,
+            amount_in_ciphertext,
+            amount_out_ciphertext,
+            fee_amount_ciphertext,
+            invariant_ciphertext,
+            input_type,
+            a_to_b,
+            referrer,
+            wrap_amount_in,
+        )
+    }
 
-    let cpi_ctx = CpiContext::new(inco_program.clone(), Operation { signer: signer.clone() });
-    let new_k = e_mul(cpi_ctx, temp_reserve_in, temp_reserve_out, SCALAR_BYTE)?;
+    /// Execute a private multi-hop swap (e.g. A -> B -> C) in one transaction
+    /// Each hop's validated encrypted output is chained as the next hop's input. The trader's
+    /// input moves into the first pool vault up front; the hop outputs (vault to vault, the
+    /// last one to the trader) are left to a `PendingPayout` that pays them only if every hop
+    /// passes. Otherwise all pool updates are undone and the payout refunds the input.
+    /// remaining_accounts: [pool_authority, pool_vault_in, pool_vault_out, oracle] per hop,
+    /// then Light accounts
+    pub fn swap_route<'info>(
+        ctx: Context<'_, '_, '_, 'info, SwapRoute<'info>>,
+        proof: SdkValidityProof,
+        address_tree_info: PackedAddressTreeInfo,
+        output_tree_index: u8,
+        payout_nonce: u64,
+        pool_metas: Vec<light_sdk::instruction::account_meta::CompressedAccountMeta>,
+        pool_data: Vec<Vec<u8>>,
+        amount_in_ciphertext: Vec<u8>,
+        amount_out_ciphertexts: Vec<Vec<u8>>,
+        fee_amount_ciphertexts: Vec<Vec<u8>>,
+        invariant_ciphertexts: Vec<Vec<u8>>,
+        input_type: u8,
+        a_to_b: Vec<bool>,
+    ) -> Result<()> {
+        instructions::handle_swap_route(
+            ctx,
+            proof,
+            address_tree_info,
+            output_tree_index,
+            payout_nonce,
+            pool_metas,
+            pool_data,
+            amount_in_ciphertext,
+            amount_out_ciphertexts,
+            fee_amount_ciphertexts,
+            invariant_ciphertexts,
+            input_type,
+            a_to_b,
+        )
+    }
 
-    let cpi_ctx = CpiContext::new(inco_program.clone(), Operation { signer: signer.clone() });
-    let k_ok: Ebool = e_ge(cpi_ctx, new_k, old_k, SCALAR_BYTE)?;
+    /// Execute a pending payout once its outcome is attested: the approved legs when
+    /// `approved` decrypts to true, otherwise the refund legs when `refunded` does, then
+    /// close it. Anyone holding the beneficiary's attested decryptions can settle.
+    /// remaining_accounts: [authority, source, destination] per executed leg, then Light accounts
+    pub fn settle_payout<'info>(
+        ctx: Context<'_, '_, '_, 'info, SettlePayout<'info>>,
+        proof: SdkValidityProof,
+        payout_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+        payout_data: Vec<u8>,
+        approved_plaintext: Vec<u8>,
+        refunded_plaintext: Option<Vec<u8>>,
+    ) -> Result<()> {
+        instructions::handle_settle_payout(
+            ctx,
+            proof,
+            payout_meta,
+            payout_data,
+            approved_plaintext,
+            refunded_plaintext,
+        )
+    }
 
-    // Zero out if invariant violated
-    let cpi_ctx = CpiContext::new(inco_program.clone(), Operation { signer: signer.clone() });
-    amount_in = e_select(cpi_ctx, k_ok, amount_in, zero, SCALAR_BYTE)?;
+    /// Set the only borrower allowed to sign `flash_swap`; `Pubkey::default()` disables
+    /// flash swaps
+    pub fn set_flash_borrower<'info>(
+        ctx: Context<'_, '_, '_, 'info, UpdatePoolConfig<'info>>,
+        proof: SdkValidityProof,
+        pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+        pool_data: Vec<u8>,
+        borrower: Pubkey,
+    ) -> Result<()> {
+        instructions::handle_set_flash_borrower(ctx, proof, pool_meta, pool_data, borrower)
+    }
 
-    let cpi_ctx = CpiContext::new(inco_program.clone(), Operation { signer: signer.clone() });
-    amount_out = e_select(cpi_ctx, k_ok, amount_out, zero, SCALAR_BYTE)?;
+    /// Lend `amount_out` from the pool, invoke the borrower's callback program, then settle
+    /// against what the callback repaid into the input vault. The repayment, which includes
+    /// `fee_amount`, must keep the pool invariant. Transfers cannot be reverted from an
+    /// encrypted condition, so only the borrower approved by the authority (`flash_borrower`)
+    /// may borrow, and a rejected repayment books the vault movements into the reserves and
+    /// records the loan as the borrower's flash debt.
+    /// remaining_accounts: callback accounts (`callback_accounts_len`), then Light accounts
+    pub fn flash_swap<'info>(
+        ctx: Context<'_, '_, '_, 'info, FlashSwap<'info>>,
+        proof: SdkValidityProof,
+        pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+        pool_data: Vec<u8>,
+        amount_out_ciphertext: Vec<u8>,
+        fee_amount_ciphertext: Vec<u8>,
+        invariant_ciphertext: Vec<u8>,
+        input_type: u8,
+        a_to_b: bool,
+        callback_data: Vec<u8>,
+        callback_accounts_len: u8,
+    ) -> Result<()> {
+        instructions::handle_flash_swap(
+            ctx,
+            proof,
+            pool_meta,
+            pool_data,
+            amount_out_ciphertext,
+            fee_amount_ciphertext,
+            invariant_ciphertext,
+            input_type,
+            a_to_b,
+            callback_data,
+            callback_accounts_len,
+        )
+    }
 
-    let cpi_ctx = CpiContext::new(inco_program.clone(), Operation { signer: signer.clone() });
-    fee_amount = e_select(cpi_ctx, k_ok, fee_amount, zero, SCALAR_BYTE)?;
+    /// Create the compressed price observation ring buffer for a pool
+    pub fn initialize_observations<'info>(
+        ctx: Context<'_, '_, '_, 'info, InitializeObservations<'info>>,
+        proof: SdkValidityProof,
+        address_tree_info: PackedAddressTreeInfo,
+        output_tree_index: u8,
+        pool_address: [u8; 32],
+    ) -> Result<()> {
+        instructions::handle_initialize_observations(
+            ctx,
+            proof,
+            address_tree_info,
+            output_tree_index,
+            pool_address,
+        )
+    }
 
-    // Final reserve calculations
-    let cpi_ctx = CpiContext::new(inco_program.clone(), Operation { signer: signer.clone() });
-    let new_reserve_in = e_add(cpi_ctx, reserve_in, amount_in, SCALAR_BYTE)?;
+    /// Bring the pool's price accumulators up to date and append them to its observation ring buffer
+    pub fn record_observation<'info>(
+        ctx: Context<'_, '_, '_, 'info, RecordObservation<'info>>,
+        proof: SdkValidityProof,
+        pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+        pool_data: Vec<u8>,
+        observations_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+        observations_data: Vec<u8>,
+    ) -> Result<()> {
+        instructions::handle_record_observation(
+            ctx,
+            proof,
+            pool_meta,
+            pool_data,
+            observations_meta,
+            observations_data,
+        )
+    }
 
-    let cpi_ctx = CpiContext::new(inco_program.clone(), Operation { signer: signer.clone() });
-    let new_reserve_out = e_sub(cpi_ctx, reserve_out, amount_out, SCALAR_BYTE)?;
+    /// Allow `reader` to decrypt the price accumulators stored in one observation slot
+    pub fn grant_price_access<'info>(
+        ctx: Context<'_, '_, '_, 'info, GrantPriceAccess<'info>>,
+        proof: SdkValidityProof,
+        pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+        pool_data: Vec<u8>,
+        observations_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+        observations_data: Vec<u8>,
+        slot: u8,
+    ) -> Result<()> {
+        instructions::handle_grant_price_access(
+            ctx,
+            proof,
+            pool_meta,
+            pool_data,
+            observations_meta,
+            observations_data,
+            slot,
+        )
+    }
 
-    let cpi_ctx = CpiContext::new(inco_program.clone(), Operation { signer: signer.clone() });
-    let new_protocol_fee = e_add(cpi_ctx, protocol_fee_in, fee_amount, SCALAR_BYTE)?;
+    /// Escrow an encrypted amount and record a resting limit order against a pool.
+    /// The order fills at its limit price: `min_amount_out` for the full `amount_in`.
+    pub fn create_limit_order<'info>(
+        ctx: Context<'_, '_, '_, 'info, CreateLimitOrder<'info>>,
+        proof: SdkValidityProof,
+        address_tree_info: PackedAddressTreeInfo,
+        output_tree_index: u8,
+        order_nonce: u64,
+        mint_a: Pubkey,
+        mint_b: Pubkey,
+        a_to_b: bool,
+        amount_in_ciphertext: Vec<u8>,
+        min_amount_out_ciphertext: Vec<u8>,
+        input_type: u8,
+    ) -> Result<()> {
+        instructions::handle_create_limit_order(
+            ctx,
+            proof,
+            address_tree_info,
+            output_tree_index,
+            order_nonce,
+            mint_a,
+            mint_b,
+            a_to_b,
+            amount_in_ciphertext,
+            min_amount_out_ciphertext,
+            input_type,
+        )
+    }
 
-    Ok((new_reserve_in, new_reserve_out, new_protocol_fee))
-}
+    /// Permissionlessly fill a limit order through the pool. The escrowed input, which pays
+    /// the pool fee, is swapped for the order's `min_amount_out`. Both transfers go into a
+    /// pending payout to the owner, approved only if the order is still open and the pool
+    /// pays that price; otherwise the pool update is undone and the order stays open.
+    pub fn fill_limit_order<'info>(
+        ctx: Context<'_, '_, '_, 'info, FillLimitOrder<'info>>,
+        proof: SdkValidityProof,
+        pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+        pool_data: Vec<u8>,
+        order_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+        order_data: Vec<u8>,
+        invariant_ciphertext: Vec<u8>,
+        address_tree_info: PackedAddressTreeInfo,
+        output_tree_index: u8,
+        payout_nonce: u64,
+    ) -> Result<()> {
+        instructions::handle_fill_limit_order(
+            ctx,
+            proof,
+            pool_meta,
+            pool_data,
+            order_meta,
+            order_data,
+            invariant_ciphertext,
+            address_tree_info,
+            output_tree_index,
+            payout_nonce,
+        )
+    }
 
-#[program]
-pub mod light_swap_psp {
-    use super::*;
+    /// Close a limit order and return its escrowed input to the owner through a pending
+    /// payout, approved only if the order was still unfilled
+    pub fn cancel_limit_order<'info>(
+        ctx: Context<'_, '_, '_, 'info, CancelLimitOrder<'info>>,
+        proof: SdkValidityProof,
+        order_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+        order_data: Vec<u8>,
+        address_tree_info: PackedAddressTreeInfo,
+        output_tree_index: u8,
+        payout_nonce: u64,
+    ) -> Result<()> {
+        instructions::handle_cancel_limit_order(
+            ctx,
+            proof,
+            order_meta,
+            order_data,
+            address_tree_info,
+            output_tree_index,
+            payout_nonce,
+        )
+    }
 
-    pub fn initialize_pool<'info>(
-        ctx: Context<'_, '_, '_, 'info, InitializePool<'info>>,
+    /// Escrow an encrypted budget and create a DCA order that buys one slice per interval
+    pub fn create_dca_order<'info>(
+        ctx: Context<'_, '_, '_, 'info, CreateDcaOrder<'info>>,
         proof: SdkValidityProof,
         address_tree_info: PackedAddressTreeInfo,
         output_tree_index: u8,
+        order_nonce: u64,
         mint_a: Pubkey,
         mint_b: Pubkey,
-        fee_bps: u16,
+        a_to_b: bool,
+        budget_ciphertext: Vec<u8>,
+        amount_per_interval_ciphertext: Vec<u8>,
+        min_amount_out_ciphertext: Vec<u8>,
+        input_type: u8,
+        interval_secs: u32,
+        executions: u32,
     ) -> Result<()> {
-        let light_cpi_accounts = CpiAccounts::new(
-            ctx.accounts.fee_payer.as_ref(),
-            ctx.remaining_accounts,
-            crate::LIGHT_CPI_SIGNER,
-        );
-
-        let tree_pubkey = address_tree_info
-            .get_tree_pubkey(&light_cpi_accounts)
-            .map_err(|error| ProgramError::Custom(error.into()))?;
-        let (address, address_seed) =
-            derive_address(&[b"pool", mint_a.as_ref(), mint_b.as_ref()], &tree_pubkey, &crate::ID);
-        let new_address_params =
-            address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0));
-
-        let mut pool_account = LightAccount::<SwapPool>::new_init(
-            &crate::ID,
-            Some(address),
+        instructions::handle_create_dca_order(
+            ctx,
+            proof,
+            address_tree_info,
             output_tree_index,
-        );
-        let inco_program = ctx.accounts.inco_lightning_program.to_account_info();
-        let signer = ctx.accounts.fee_payer.to_account_info();
-        
-        let cpi_ctx = CpiContext::new(inco_program.clone(), Operation { signer: signer.clone() });
-        pool_account.reserve_a = as_euint128(cpi_ctx, 0)?;
-
-        let cpi_ctx = CpiContext::new(inco_program.clone(), Operation { signer: signer.clone() });
-        pool_account.reserve_b = as_euint128(cpi_ctx, 0)?;
-
-        let cpi_ctx = CpiContext::new(inco_program.clone(), Operation { signer: signer.clone() });
-        pool_account.protocol_fee_a = as_euint128(cpi_ctx, 0)?;
-
-        let cpi_ctx = CpiContext::new(inco_program, Operation { signer });
-        pool_account.protocol_fee_b = as_euint128(cpi_ctx, 0)?;
-        
-        let (pool_authority, _) = Pubkey::find_program_address(
-            &[POOL_AUTH_SEED, mint_a.as_ref(), mint_b.as_ref()],
-            &crate::ID,
-        );
-        pool_account.authority = ctx.accounts.authority.key();
-        pool_account.pool_authority = pool_authority;
-        pool_account.mint_a = mint_a;
-        pool_account.mint_b = mint_b;
-        pool_account.fee_bps = fee_bps;
-        pool_account.is_paused = false;
-        pool_account.last_update_ts = Clock::get()?.unix_timestamp;
-
-        LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
-            .with_light_account(pool_account)?
-            .with_new_addresses(&[new_address_params])
-            .invoke(light_cpi_accounts)?;
-        Ok(())
+            order_nonce,
+            mint_a,
+            mint_b,
+            a_to_b,
+            budget_ciphertext,
+            amount_per_interval_ciphertext,
+            min_amount_out_ciphertext,
+            input_type,
+            interval_secs,
+            executions,
+        )
     }
 
-    /// Add liquidity to the pool with encrypted amounts
-    pub fn add_liquidity<'info>(
-        ctx: Context<'_, '_, '_, 'info, AddLiquidity<'info>>,
+    /// Keeper-callable: swap one DCA slice through the pool once the interval has elapsed.
+    /// Both transfers go into a pending payout to the owner, approved only if the remaining
+    /// budget covers the slice and the pool pays the slice's `min_amount_out`; otherwise the
+    /// pool update is undone, the budget is kept and the interval is skipped.
+    pub fn execute_dca<'info>(
+        ctx: Context<'_, '_, '_, 'info, ExecuteDca<'info>>,
         proof: SdkValidityProof,
         pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
         pool_data: Vec<u8>,
-        amount_a_ciphertext: Vec<u8>,
-        amount_b_ciphertext: Vec<u8>,
+        order_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+        order_data: Vec<u8>,
+        invariant_ciphertext: Vec<u8>,
+        address_tree_info: PackedAddressTreeInfo,
+        output_tree_index: u8,
+        payout_nonce: u64,
+    ) -> Result<()> {
+        instructions::handle_execute_dca(
+            ctx,
+            proof,
+            pool_meta,
+            pool_data,
+            order_meta,
+            order_data,
+            invariant_ciphertext,
+            address_tree_info,
+            output_tree_index,
+            payout_nonce,
+        )
+    }
+
+    /// Stop a DCA order and refund `refund` of its unspent budget to the owner through a
+    /// pending payout, approved only if the remaining budget covers it. The order stays, so
+    /// budget left after a partial refund can be refunded by another cancel.
+    pub fn cancel_dca_order<'info>(
+        ctx: Context<'_, '_, '_, 'info, CancelDcaOrder<'info>>,
+        proof: SdkValidityProof,
+        order_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+        order_data: Vec<u8>,
+        refund_ciphertext: Vec<u8>,
         input_type: u8,
+        address_tree_info: PackedAddressTreeInfo,
+        output_tree_index: u8,
+        payout_nonce: u64,
+    ) -> Result<()> {
+        instructions::handle_cancel_dca_order(
+            ctx,
+            proof,
+            order_meta,
+            order_data,
+            refund_ciphertext,
+            input_type,
+            address_tree_info,
+            output_tree_index,
+            payout_nonce,
+        )
+    }
+
+    /// Enable or disable batch auction mode. Only constant-product pools can batch, since
+    /// settlement clears at the closed-form constant-product price.
+    pub fn set_batch_mode<'info>(
+        ctx: Context<'_, '_, '_, 'info, UpdatePoolConfig<'info>>,
+        proof: SdkValidityProof,
+        pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+        pool_data: Vec<u8>,
+        enabled: bool,
+        window_secs: u32,
     ) -> Result<()> {
-        let light_cpi_accounts = CpiAccounts::new(
-            ctx.accounts.fee_payer.as_ref(),
-            ctx.remaining_accounts,
-            crate::LIGHT_CPI_SIGNER,
-        );
+        instructions::handle_set_batch_mode(ctx, proof, pool_meta, pool_data, enabled, window_secs)
+    }
 
-        let pool_state = SwapPool::try_from_slice(&pool_data)?;
-        let mut pool_account = LightAccount::<SwapPool>::new_mut(
-            &crate::ID,
-            &pool_meta,
-            pool_state,
-        )?;
+    /// Open the pool's next batch; intents can be queued until `window_end_ts`
+    pub fn open_swap_batch<'info>(
+        ctx: Context<'_, '_, '_, 'info, OpenSwapBatch<'info>>,
+        proof: SdkValidityProof,
+        address_tree_info: PackedAddressTreeInfo,
+        output_tree_index: u8,
+        pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+        pool_data: Vec<u8>,
+    ) -> Result<()> {
+        instructions::handle_open_swap_batch(
+            ctx,
+            proof,
+            address_tree_info,
+            output_tree_index,
+            pool_meta,
+            pool_data,
+        )
+    }
 
-        require!(!pool_account.is_paused, ErrorCode::PoolPaused);
-        require_keys_eq!(pool_account.authority, ctx.accounts.authority.key(), ErrorCode::Unauthorized);
+    /// Queue an encrypted swap intent into an open batch. The input moves into the pool
+    /// vault now and only what arrived is queued; the pool fee is taken immediately and
+    /// the net amount joins the batch total, owed to the trader until settlement.
+    pub fn queue_swap_intent<'info>(
+        ctx: Context<'_, '_, '_, 'info, QueueSwapIntent<'info>>,
+        proof: SdkValidityProof,
+        pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+        pool_data: Vec<u8>,
+        batch_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+        batch_data: Vec<u8>,
+        amount_in_ciphertext: Vec<u8>,
+        min_amount_out_ciphertext: Vec<u8>,
+        input_type: u8,
+        a_to_b: bool,
+    ) -> Result<()> {
+        instructions::handle_queue_swap_intent(
+            ctx,
+            proof,
+            pool_meta,
+            pool_data,
+            batch_meta,
+            batch_data,
+            amount_in_ciphertext,
+            min_amount_out_ciphertext,
+            input_type,
+            a_to_b,
+        )
+    }
 
-        let inco_program = ctx.accounts.inco_lightning_program.to_account_info();
-        let signer = ctx.accounts.fee_payer.to_account_info();
+    /// Permissionlessly settle a batch once its window has closed. Both directions clear at
+    /// the single price p = (reserve_b + total_in_b) / (reserve_a + total_in_a), which nets
+    /// A->B against B->A and moves the reserves along the constant-product curve by the
+    /// imbalance only. An intent is filled if amount_in * p (or / p) meets its
+    /// `min_amount_out` and the breaker's per-trade cap, and the fills are applied only if
+    /// the vaults cover them, the breaker's window caps hold, reserve_a · reserve_b does not
+    /// shrink and p lies within the oracle price band. Filled intents are credited their
+    /// output, all others their net input, for later claiming.
+    pub fn settle_batch<'info>(
+        ctx: Context<'_, '_, '_, 'info, SettleBatch<'info>>,
+        proof: SdkValidityProof,
+        pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+        pool_data: Vec<u8>,
+        batch_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+        batch_data: Vec<u8>,
+    ) -> Result<()> {
+        instructions::handle_settle_batch(ctx, proof, pool_meta, pool_data, batch_meta, batch_data)
+    }
 
-        // Parse encrypted amounts
-        let cpi_ctx = CpiContext::new(inco_program.clone(), Operation { signer: signer.clone() });
-        let amount_a = new_euint128(cpi_ctx, amount_a_ciphertext, input_type)?;
+    /// Claim part or all of a settled intent's credit, the output (`claim_output`) or the
+    /// refunded net input, through a pending payout to the trader. The claim is approved
+    /// and debited only if the encrypted credit covers it, so the rest stays claimable.
+    /// Claims are blocked while the pool is paused, until its emergency delay has elapsed.
+    pub fn claim_batch_output<'info>(
+        ctx: Context<'_, '_, '_, 'info, ClaimBatchOutput<'info>>,
+        proof: SdkValidityProof,
+        pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+        pool_data: Vec<u8>,
+        batch_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+        batch_data: Vec<u8>,
+        intent_index: u8,
+        claim_output: bool,
+        amount_ciphertext: Vec<u8>,
+        input_type: u8,
+        address_tree_info: PackedAddressTreeInfo,
+        output_tree_index: u8,
+        payout_nonce: u64,
+    ) -> Result<()> {
+        instructions::handle_claim_batch_output(
+            ctx,
+            proof,
+            pool_meta,
+            pool_data,
+            batch_meta,
+            batch_data,
+            intent_index,
+            claim_output,
+            amount_ciphertext,
+            input_type,
+            address_tree_info,
+            output_tree_index,
+            payout_nonce,
+        )
+    }
 
-        let cpi_ctx = CpiContext::new(inco_program.clone(), Operation { signer: signer.clone() });
-        let amount_b = new_euint128(cpi_ctx, amount_b_ciphertext, input_type)?;
+    /// Set the share of the protocol fee paid to referrers, in bps
+    pub fn set_referral_share<'info>(
+        ctx: Context<'_, '_, '_, 'info, UpdatePoolConfig<'info>>,
+        proof: SdkValidityProof,
+        pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+        pool_data: Vec<u8>,
+        referral_share_bps: u16,
+    ) -> Result<()> {
+        instructions::handle_set_referral_share(ctx, proof, pool_meta, pool_data, referral_share_bps)
+    }
 
-        // Add to reserves
-        let cpi_ctx = CpiContext::new(inco_program.clone(), Operation { signer: signer.clone() });
-        pool_account.reserve_a = e_add(cpi_ctx, pool_account.reserve_a, amount_a, SCALAR_BYTE)?;
+    /// Create the compressed fee account a referrer accrues into for one pool
+    pub fn register_referrer<'info>(
+        ctx: Context<'_, '_, '_, 'info, RegisterReferrer<'info>>,
+        proof: SdkValidityProof,
+        address_tree_info: PackedAddressTreeInfo,
+        output_tree_index: u8,
+        mint_a: Pubkey,
+        mint_b: Pubkey,
+    ) -> Result<()> {
+        instructions::handle_register_referrer(
+            ctx,
+            proof,
+            address_tree_info,
+            output_tree_index,
+            mint_a,
+            mint_b,
+        )
+    }
 
-        let cpi_ctx = CpiContext::new(inco_program, Operation { signer });
-        pool_account.reserve_b = e_add(cpi_ctx, pool_account.reserve_b, amount_b, SCALAR_BYTE)?;
+    /// Withdraw accrued referral fees of one mint through a pending payout to the referrer,
+    /// approved only if the encrypted balance covers the claimed amount; only an approved
+    /// claim is debited. Claims are blocked while the pool is paused, until its emergency
+    /// delay has elapsed.
+    pub fn claim_referral_fees<'info>(
+        ctx: Context<'_, '_, '_, 'info, ClaimReferralFees<'info>>,
+        proof: SdkValidityProof,
+        pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+        pool_data: Vec<u8>,
+        referrer_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+        referrer_data: Vec<u8>,
+        amount_ciphertext: Vec<u8>,
+        input_type: u8,
+        claim_a: bool,
+        address_tree_info: PackedAddressTreeInfo,
+        output_tree_index: u8,
+        payout_nonce: u64,
+    ) -> Result<()> {
+        instructions::handle_claim_referral_fees(
+            ctx,
+            proof,
+            pool_meta,
+            pool_data,
+            referrer_meta,
+            referrer_data,
+            amount_ciphertext,
+            input_type,
+            claim_a,
+            address_tree_info,
+            output_tree_index,
+            payout_nonce,
+        )
+    }
 
-        pool_account.last_update_ts = Clock::get()?.unix_timestamp;
+    /// Set the protocol's share of swap fees in bps; the remainder compounds into the reserves
+    pub fn set_protocol_fee_share<'info>(
+        ctx: Context<'_, '_, '_, 'info, UpdatePoolConfig<'info>>,
+        proof: SdkValidityProof,
+        pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+        pool_data: Vec<u8>,
+        protocol_fee_share_bps: u16,
+    ) -> Result<()> {
+        instructions::handle_set_protocol_fee_share(
+            ctx,
+            proof,
+            pool_meta,
+            pool_data,
+            protocol_fee_share_bps,
+        )
+    }
 
-        // Commit pool state update
-        LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
-            .with_light_account(pool_account)?
-            .invoke(light_cpi_accounts)?;
+    /// Configure dynamic fees; `fee_bps` stays the base rate the volatility term is added to
+    pub fn set_dynamic_fee<'info>(
+        ctx: Context<'_, '_, '_, 'info, UpdatePoolConfig<'info>>,
+        proof: SdkValidityProof,
+        pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+        pool_data: Vec<u8>,
+        enabled: bool,
+        min_fee_bps: u16,
+        max_fee_bps: u16,
+        volatility_step_bps: u16,
+        volatility_half_life_secs: u32,
+    ) -> Result<()> {
+        instructions::handle_set_dynamic_fee(
+            ctx,
+            proof,
+            pool_meta,
+            pool_data,
+            enabled,
+            min_fee_bps,
+            max_fee_bps,
+            volatility_step_bps,
+            volatility_half_life_secs,
+        )
+    }
 
-        Ok(())
+    /// Set both reserves to the vaults' encrypted balances minus accrued protocol fees and
+    /// owed referral and batch credits, floored at zero, and reset the skimmed totals.
+    /// Outputs and refunds of unsettled payouts still sit in the vaults and would be counted
+    /// as reserves, so they should be settled before syncing.
+    pub fn sync_reserves<'info>(
+        ctx: Context<'_, '_, '_, 'info, SyncReserves<'info>>,
+        proof: SdkValidityProof,
+        pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+        pool_data: Vec<u8>,
+    ) -> Result<()> {
+        instructions::handle_sync_reserves(ctx, proof, pool_meta, pool_data)
     }
 
-    /// Remove liquidity from the pool with encrypted amounts
-    pub fn remove_liquidity<'info>(
-        ctx: Context<'_, '_, '_, 'info, RemoveLiquidity<'info>>,
+    /// Send vault balance in excess of reserves, protocol fees, owed credits and earlier
+    /// skims to `recipient_token` through a pending payout to the authority, approved only if
+    /// the encrypted excess covers the skimmed amount; an approved skim is added to the
+    /// pool's skimmed total. Reserves are never touched. Outputs and refunds of unsettled
+    /// payouts still sit in the vault, so skim only once they settled.
+    pub fn skim<'info>(
+        ctx: Context<'_, '_, '_, 'info, Skim<'info>>,
         proof: SdkValidityProof,
         pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
         pool_data: Vec<u8>,
-        amount_a_ciphertext: Vec<u8>,
-        amount_b_ciphertext: Vec<u8>,
+        amount_ciphertext: Vec<u8>,
         input_type: u8,
+        skim_a: bool,
+        address_tree_info: PackedAddressTreeInfo,
+        output_tree_index: u8,
+        payout_nonce: u64,
     ) -> Result<()> {
-        let light_cpi_accounts = CpiAccounts::new(
-            ctx.accounts.fee_payer.as_ref(),
-            ctx.remaining_accounts,
-            crate::LIGHT_CPI_SIGNER,
-        );
+        instructions::handle_skim(
+            ctx,
+            proof,
+            pool_meta,
+            pool_data,
+            amount_ciphertext,
+            input_type,
+            skim_a,
+            address_tree_info,
+            output_tree_index,
+            payout_nonce,
+        )
+    }
 
-        let pool_state = SwapPool::try_from_slice(&pool_data)?;
-        let mut pool_account = LightAccount::<SwapPool>::new_mut(
-            &crate::ID,
-            &pool_meta,
-            pool_state,
-        )?;
+    /// Set the address that receives decryption access to proof-of-reserves results
+    pub fn set_auditor<'info>(
+        ctx: Context<'_, '_, '_, 'info, UpdatePoolConfig<'info>>,
+        proof: SdkValidityProof,
+        pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+        pool_data: Vec<u8>,
+        auditor: Pubkey,
+    ) -> Result<()> {
+        instructions::handle_set_auditor(ctx, proof, pool_meta, pool_data, auditor)
+    }
 
-        require!(!pool_account.is_paused, ErrorCode::PoolPaused);
-        require_keys_eq!(pool_account.authority, ctx.accounts.authority.key(), ErrorCode::Unauthorized);
+    /// Check under FHE that each vault covers its reserve plus protocol fees, grant the
+    /// auditor decryption of both results and record them in a `ReserveAttestation`
+    pub fn verify_reserves<'info>(
+        ctx: Context<'_, '_, '_, 'info, VerifyReserves<'info>>,
+        proof: SdkValidityProof,
+        address_tree_info: PackedAddressTreeInfo,
+        output_tree_index: u8,
+        pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+        pool_data: Vec<u8>,
+    ) -> Result<()> {
+        instructions::handle_verify_reserves(
+            ctx,
+            proof,
+            address_tree_info,
+            output_tree_index,
+            pool_meta,
+            pool_data,
+        )
+    }
 
-        let inco_program = ctx.accounts.inco_lightning_program.to_account_info();
-        let signer = ctx.accounts.fee_payer.to_account_info();
+    /// Configure the circuit breaker with encrypted caps; also resets the window and the
+    /// tripped flag
+    pub fn set_circuit_breaker<'info>(
+        ctx: Context<'_, '_, '_, 'info, SetCircuitBreaker<'info>>,
+        proof: SdkValidityProof,
+        pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+        pool_data: Vec<u8>,
+        enabled: bool,
+        auto_pause: bool,
+        window_secs: u32,
+        max_trade_out_a_ciphertext: Vec<u8>,
+        max_trade_out_b_ciphertext: Vec<u8>,
+        window_outflow_cap_a_ciphertext: Vec<u8>,
+        window_outflow_cap_b_ciphertext: Vec<u8>,
+        input_type: u8,
+    ) -> Result<()> {
+        instructions::handle_set_circuit_breaker(
+            ctx,
+            proof,
+            pool_meta,
+            pool_data,
+            enabled,
+            auto_pause,
+            window_secs,
+            max_trade_out_a_ciphertext,
+            max_trade_out_b_ciphertext,
+            window_outflow_cap_a_ciphertext,
+            window_outflow_cap_b_ciphertext,
+            input_type,
+        )
+    }
 
-        // Parse encrypted amounts
-        let cpi_ctx = CpiContext::new(inco_program.clone(), Operation { signer: signer.clone() });
-        let amount_a = new_euint128(cpi_ctx, amount_a_ciphertext, input_type)?;
+    /// Allow the pool authority to decrypt the circuit breaker's tripped flag
+    pub fn grant_breaker_access<'info>(
+        ctx: Context<'_, '_, '_, 'info, GrantBreakerAccess<'info>>,
+        proof: SdkValidityProof,
+        pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+        pool_data: Vec<u8>,
+    ) -> Result<()> {
+        instructions::handle_grant_breaker_access(ctx, proof, pool_meta, pool_data)
+    }
 
-        let cpi_ctx = CpiContext::new(inco_program.clone(), Operation { signer: signer.clone() });
-        let amount_b = new_euint128(cpi_ctx, amount_b_ciphertext, input_type)?;
+    /// Configure the oracle price band; pass `Pubkey::default()` as the feed to disable it
+    pub fn set_oracle_guard<'info>(
+        ctx: Context<'_, '_, '_, 'info, UpdatePoolConfig<'info>>,
+        proof: SdkValidityProof,
+        pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+        pool_data: Vec<u8>,
+        oracle_feed: Pubkey,
+        max_staleness_secs: u32,
+        max_deviation_bps: u16,
+    ) -> Result<()> {
+        instructions::handle_set_oracle_guard(
+            ctx,
+            proof,
+            pool_meta,
+            pool_data,
+            oracle_feed,
+            max_staleness_secs,
+            max_deviation_bps,
+        )
+    }
 
-        // Subtract from reserves
-        let cpi_ctx = CpiContext::new(inco_program.clone(), Operation { signer: signer.clone() });
-        pool_account.reserve_a = e_sub(cpi_ctx, pool_account.reserve_a, amount_a, SCALAR_BYTE)?;
+    /// Set the pool's guardian, which can pause but never unpause or reconfigure the pool
+    pub fn set_guardian<'info>(
+        ctx: Context<'_, '_, '_, 'info, UpdatePoolConfig<'info>>,
+        proof: SdkValidityProof,
+        pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+        pool_data: Vec<u8>,
+        guardian: Pubkey,
+    ) -> Result<()> {
+        instructions::handle_set_guardian(ctx, proof, pool_meta, pool_data, guardian)
+    }
 
-        let cpi_ctx = CpiContext::new(inco_program, Operation { signer });
-        pool_account.reserve_b = e_sub(cpi_ctx, pool_account.reserve_b, amount_b, SCALAR_BYTE)?;
+    /// Pause the pool; callable by the guardian or the authority
+    pub fn pause_pool<'info>(
+        ctx: Context<'_, '_, '_, 'info, PausePool<'info>>,
+        proof: SdkValidityProof,
+        pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+        pool_data: Vec<u8>,
+    ) -> Result<()> {
+        instructions::handle_pause_pool(ctx, proof, pool_meta, pool_data)
+    }
 
-        pool_account.last_update_ts = Clock::get()?.unix_timestamp;
+    /// Unpause the pool; authority only, and not before `UNPAUSE_DELAY_SECS` after the pause
+    pub fn unpause_pool<'info>(
+        ctx: Context<'_, '_, '_, 'info, UpdatePoolConfig<'info>>,
+        proof: SdkValidityProof,
+        pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+        pool_data: Vec<u8>,
+    ) -> Result<()> {
+        instructions::handle_unpause_pool(ctx, proof, pool_meta, pool_data)
+    }
 
-        // Commit pool state update
-        LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
-            .with_light_account(pool_account)?
-            .invoke(light_cpi_accounts)?;
+    /// Set how long a pool must stay paused before emergency withdrawal opens
+    pub fn set_emergency_delay<'info>(
+        ctx: Context<'_, '_, '_, 'info, UpdatePoolConfig<'info>>,
+        proof: SdkValidityProof,
+        pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+        pool_data: Vec<u8>,
+        emergency_delay_secs: u32,
+    ) -> Result<()> {
+        instructions::handle_set_emergency_delay(ctx, proof, pool_meta, pool_data, emergency_delay_secs)
+    }
 
-        Ok(())
+    /// Allow the authority of a pool paused for at least `emergency_delay_secs` to decrypt
+    /// its reserves and protocol fees, the amounts `emergency_withdraw` pays out
+    pub fn grant_withdrawal_access<'info>(
+        ctx: Context<'_, '_, '_, 'info, GrantWithdrawalAccess<'info>>,
+        proof: SdkValidityProof,
+        pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+        pool_data: Vec<u8>,
+    ) -> Result<()> {
+        instructions::handle_grant_withdrawal_access(ctx, proof, pool_meta, pool_data)
     }
 
-    /// Execute a private swap with encrypted amounts
-    /// Includes CPI to Inco Token for actual token transfers
-    pub fn swap_exact_in<'info>(
-        ctx: Context<'_, '_, '_, 'info, SwapExactIn<'info>>,
+    /// Withdraw a pool that has been paused for at least `emergency_delay_secs`. Liquidity is
+    /// provided by the authority alone, so its pro-rata share is the whole pool:
+    /// `reserve + protocol_fee` of each side, decrypted through `grant_withdrawal_access`.
+    /// The payout to the authority is approved only if both amounts equal those totals under
+    /// FHE, in which case reserves and protocol fees are zeroed; otherwise nothing is paid and
+    /// they are kept, so a rejected attempt can be retried. After an approved withdrawal the
+    /// totals are zero, so a repeat call can only pay zero. Owed referral and batch credits
+    /// stay in the vaults for their claims.
+    pub fn emergency_withdraw<'info>(
+        ctx: Context<'_, '_, '_, 'info, EmergencyWithdraw<'info>>,
         proof: SdkValidityProof,
         pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
         pool_data: Vec<u8>,
-        amount_in_ciphertext: Vec<u8>,
-        amount_out_ciphertext: Vec<u8>,
-        fee_amount_ciphertext: Vec<u8>,
+        amount_a_ciphertext: Vec<u8>,
+        amount_b_ciphertext: Vec<u8>,
         input_type: u8,
-        a_to_b: bool,
+        address_tree_info: PackedAddressTreeInfo,
+        output_tree_index: u8,
+        payout_nonce: u64,
     ) -> Result<()> {
-        let light_cpi_accounts = CpiAccounts::new(
-            ctx.accounts.fee_payer.as_ref(),
-            ctx.remaining_accounts,
-            crate::LIGHT_CPI_SIGNER,
-        );
-
-        let pool_state = SwapPool::try_from_slice(&pool_data)?;
-        let mut pool_account = LightAccount::<SwapPool>::new_mut(
-            &crate::ID,
-            &pool_meta,
-            pool_state,
-        )?;
-
-        require!(!pool_account.is_paused, ErrorCode::PoolPaused);
-
-        let inco_program = ctx.accounts.inco_lightning_program.to_account_info();
-        let inco_token_program = ctx.accounts.inco_token_program.to_account_info();
-        let signer = ctx.accounts.fee_payer.to_account_info();
-
-        // Verify pool authority - use bump from Anchor's verified constraint
-        let bump = ctx.bumps.pool_authority;
-        let expected_pool_authority = ctx.accounts.pool_authority.key();
-        require_keys_eq!(pool_account.pool_authority, expected_pool_authority);
-
-        // Get reserves based on swap direction
-        let (reserve_in, reserve_out, protocol_fee_in) = if a_to_b {
-            (pool_account.reserve_a, pool_account.reserve_b, pool_account.protocol_fee_a)
-        } else {
-            (pool_account.reserve_b, pool_account.reserve_a, pool_account.protocol_fee_b)
-        };
-
-        // Compute encrypted swap updates
-        let (new_reserve_in, new_reserve_out, new_protocol_fee) = compute_swap_updates(
-            &inco_program,
-            &signer,
-            reserve_in,
-            reserve_out,
-            protocol_fee_in,
-            &amount_in_ciphertext,
-            &amount_out_ciphertext,
-            &fee_amount_ciphertext,
+        instructions::handle_emergency_withdraw(
+            ctx,
+            proof,
+            pool_meta,
+            pool_data,
+            amount_a_ciphertext,
+            amount_b_ciphertext,
             input_type,
-        )?;
-
-        // Update pool state
-        if a_to_b {
-            pool_account.reserve_a = new_reserve_in;
-            pool_account.reserve_b = new_reserve_out;
-            pool_account.protocol_fee_a = new_protocol_fee;
-        } else {
-            pool_account.reserve_b = new_reserve_in;
-            pool_account.reserve_a = new_reserve_out;
-            pool_account.protocol_fee_b = new_protocol_fee;
-        }
-
-        pool_account.last_update_ts = Clock::get()?.unix_timestamp;
-
-        // === TOKEN TRANSFERS via Inco Token CPI ===
-        
-        // Transfer token_in FROM user TO pool vault
-        let (user_token_in, pool_vault_in, user_token_out, pool_vault_out) = if a_to_b {
-            (
-                ctx.accounts.user_token_a.to_account_info(),
-                ctx.accounts.pool_vault_a.to_account_info(),
-                ctx.accounts.user_token_b.to_account_info(),
-                ctx.accounts.pool_vault_b.to_account_info(),
-            )
-        } else {
-            (
-                ctx.accounts.user_token_b.to_account_info(),
-                ctx.accounts.pool_vault_b.to_account_info(),
-                ctx.accounts.user_token_a.to_account_info(),
-                ctx.accounts.pool_vault_a.to_account_info(),
-            )
-        };
-
-        // CPI: Transfer amount_in from user to pool vault (user signs)
-        let transfer_in_ctx = CpiContext::new(
-            inco_token_program.clone(),
-            IncoTransfer {
-                source: user_token_in,
-                destination: pool_vault_in,
-                authority: signer.clone(),
-                inco_lightning_program: inco_program.clone(),
-                system_program: ctx.accounts.system_program.to_account_info(),
-            },
-        );
-        inco_token_transfer(transfer_in_ctx, amount_in_ciphertext.clone(), input_type)?;
-
-        // CPI: Transfer amount_out from pool vault to user (pool authority PDA signs)
-        // Use the same mints that Anchor verified the pool_authority against
-        let mint_a_key = ctx.accounts.user_token_a.mint;
-        let mint_b_key = ctx.accounts.user_token_b.mint;
-        let pool_auth_seeds: &[&[u8]] = &[
-            POOL_AUTH_SEED,
-            mint_a_key.as_ref(),
-            mint_b_key.as_ref(),
-            &[bump],
-        ];
-        let signer_seeds = &[pool_auth_seeds];
-        let transfer_out_ctx = CpiContext::new_with_signer(
-            inco_token_program,
-            IncoTransfer {
-                source: pool_vault_out,
-                destination: user_token_out,
-                authority: ctx.accounts.pool_authority.to_account_info(),
-                inco_lightning_program: inco_program,
-                system_program: ctx.accounts.system_program.to_account_info(),
-            },
-            signer_seeds,
-        );
-        inco_token_transfer(transfer_out_ctx, amount_out_ciphertext.clone(), input_type)?;
-
-        // Commit pool state update to Light Protocol
-        LightSystemProgramCpi::new_cpi(crate::LIGHT_CPI_SIGNER, proof)
-            .with_light_account(pool_account)?
-            .invoke(light_cpi_accounts)?;
-
-        Ok(())
+            address_tree_info,
+            output_tree_index,
+            payout_nonce,
+        )
     }
-}
 
-#[derive(Accounts)]
-pub struct InitializePool<'info> {
-    #[account(mut)]
-    pub fee_payer: Signer<'info>,
-    #[account(mut)]
-    pub authority: Signer<'info>,
-    /// CHECK: Inco Lightning program for encrypted operations
-    #[account(address = INCO_LIGHTNING_ID)]
-    pub inco_lightning_program: AccountInfo<'info>,
-}
-
-#[derive(Accounts)]
-pub struct AddLiquidity<'info> {
-    #[account(mut)]
-    pub fee_payer: Signer<'info>,
-    pub authority: Signer<'info>,
-    /// CHECK: Inco Lightning program for encrypted operations
-    #[account(address = INCO_LIGHTNING_ID)]
-    pub inco_lightning_program: AccountInfo<'info>,
-}
-
-#[derive(Accounts)]
-pub struct RemoveLiquidity<'info> {
-    #[account(mut)]
-    pub fee_payer: Signer<'info>,
-    pub authority: Signer<'info>,
-    /// CHECK: Inco Lightning program for encrypted operations
-    #[account(address = INCO_LIGHTNING_ID)]
-    pub inco_lightning_program: AccountInfo<'info>,
-}
+    /// First step of closing a paused pool: compute under FHE whether both reserves,
+    /// protocol fees and owed referral and batch credits are zero and let the authority
+    /// decrypt the result
+    pub fn prepare_close_pool<'info>(
+        ctx: Context<'_, '_, '_, 'info, PrepareClosePool<'info>>,
+        proof: SdkValidityProof,
+        pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+        pool_data: Vec<u8>,
+    ) -> Result<()> {
+        instructions::handle_prepare_close_pool(ctx, proof, pool_meta, pool_data)
+    }
 
-#[derive(Accounts)]
-pub struct SwapExactIn<'info> {
-    #[account(mut)]
-    pub fee_payer: Signer<'info>,
-    /// CHECK: Pool authority PDA for signing token transfers (mut required for CPI)
-    #[account(mut, seeds = [POOL_AUTH_SEED, user_token_a.mint.as_ref(), user_token_b.mint.as_ref()], bump)]
-    pub pool_authority: AccountInfo<'info>,
-    /// User's Inco token account for token A
-    #[account(mut)]
-    pub user_token_a: Account<'info, IncoAccount>,
-    /// User's Inco token account for token B  
-    #[account(mut)]
-    pub user_token_b: Account<'info, IncoAccount>,
-    /// Pool vault for token A (owned by pool_authority)
-    #[account(mut, constraint = pool_vault_a.owner == pool_authority.key())]
-    pub pool_vault_a: Account<'info, IncoAccount>,
-    /// Pool vault for token B (owned by pool_authority)
-    #[account(mut, constraint = pool_vault_b.owner == pool_authority.key())]
-    pub pool_vault_b: Account<'info, IncoAccount>,
-    /// CHECK: Inco Lightning program for encrypted operations
-    #[account(address = INCO_LIGHTNING_ID)]
-    pub inco_lightning_program: AccountInfo<'info>,
-    /// CHECK: Inco Token program for token transfers
-    #[account(address = INCO_TOKEN_ID)]
-    pub inco_token_program: AccountInfo<'info>,
-    pub system_program: Program<'info, System>,
-}
+    /// Close a paused, empty pool: verify the Inco attested decryption of `close_check`
+    /// (an Ed25519 instruction earlier in the transaction), nullify the compressed pool
+    /// account and its index entry and close both vaults, returning their rent to the
+    /// authority
+    pub fn close_pool<'info>(
+        ctx: Context<'_, '_, '_, 'info, ClosePool<'info>>,
+        proof: SdkValidityProof,
+        pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+        pool_data: Vec<u8>,
+        index_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+        index_data: Vec<u8>,
+        close_check_plaintext: Vec<u8>,
+    ) -> Result<()> {
+        instructions::handle_close_pool(
+            ctx,
+            proof,
+            pool_meta,
+            pool_data,
+            index_meta,
+            index_data,
+            close_check_plaintext,
+        )
+    }
 
+    /// Rewrite a legacy (pre-versioning) pool in the current `SwapPool` layout, keeping
+    /// its address, and register it in the pool index. New fields get the same defaults
+    /// as `initialize_pool`.
+    pub fn migrate_pool<'info>(
+        ctx: Context<'_, '_, '_, 'info, MigratePool<'info>>,
+        proof: SdkValidityProof,
+        pool_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+        pool_data: Vec<u8>,
+        address_tree_info: PackedAddressTreeInfo,
+    ) -> Result<()> {
+        instructions::handle_migrate_pool(ctx, proof, pool_meta, pool_data, address_tree_info)
+    }
 
-#[derive(
-    Clone,
-    Debug,
-    Default,
-    LightDiscriminator,
-    AnchorSerialize,
-    AnchorDeserialize,
-)]
-pub struct SwapPool {
-    pub authority: Pubkey,
-    pub pool_authority: Pubkey,
-    pub mint_a: Pubkey,
-    pub mint_b: Pubkey,
-    pub reserve_a: Euint128,
-    pub reserve_b: Euint128,
-    pub protocol_fee_a: Euint128,
-    pub protocol_fee_b: Euint128,
-    pub fee_bps: u16,
-    pub is_paused: bool,
-    pub last_update_ts: i64,
-}
+    /// First step of unwrapping: burn an encrypted amount of a wrapped Inco token and open
+    /// a ticket for `amount` of the SPL backing. The ticket stores an encrypted flag that
+    /// the burn covered `amount`, decryptable by the owner.
+    pub fn request_unwrap<'info>(
+        ctx: Context<'_, '_, '_, 'info, RequestUnwrap<'info>>,
+        proof: SdkValidityProof,
+        address_tree_info: PackedAddressTreeInfo,
+        output_tree_index: u8,
+        ticket_nonce: u64,
+        amount: u64,
+        ciphertext: Vec<u8>,
+        input_type: u8,
+    ) -> Result<()> {
+        instructions::handle_request_unwrap(
+            ctx,
+            proof,
+            address_tree_info,
+            output_tree_index,
+            ticket_nonce,
+            amount,
+            ciphertext,
+            input_type,
+        )
+    }
 
-#[error_code]
-pub enum ErrorCode {
-    #[msg("Pool is paused")]
-    PoolPaused,
-    #[msg("Input mint does not match pool")]
-    InvalidInputMint,
-    #[msg("Output mint does not match pool")]
-    InvalidOutputMint,
-    #[msg("Unauthorized - only pool authority can perform this action")]
-    Unauthorized,
+    /// Second step of unwrapping: verify the Inco attested decryption of the ticket's
+    /// `burn_covers` flag, pay `amount` from the SPL backing vault and close the ticket
+    pub fn claim_unwrap<'info>(
+        ctx: Context<'_, '_, '_, 'info, ClaimUnwrap<'info>>,
+        proof: SdkValidityProof,
+        ticket_meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+        ticket_data: Vec<u8>,
+        burn_covers_plaintext: Vec<u8>,
+    ) -> Result<()> {
+        instructions::handle_claim_unwrap(ctx, proof, ticket_meta, ticket_data, burn_covers_plaintext)
+    }
 }
-
@@ -0,0 +1,168 @@
+//! Pyth price feed parsing and the encrypted oracle price band
+
+use anchor_lang::prelude::*;
+use inco_lightning::cpi::{as_euint128, e_ge, e_mul, e_select};
+use inco_lightning::types::{Ebool, Euint128};
+
+use crate::constants::*;
+use crate::errors::ErrorCode;
+use crate::fhe::*;
+use crate::state::*;
+
+/// Encrypted checks that the execution price `amount_out / amount_in` lies within
+/// `price_q32 * (1 ± max_deviation_bps / 10_000)`. `price_q32` is B per A; it is
+/// inverted for B->A swaps.
+pub(crate) fn price_band_checks<'info>(
+    inco_program: &AccountInfo<'info>,
+    signer: &AccountInfo<'info>,
+    price_q32: u128,
+    max_deviation_bps: u16,
+    a_to_b: bool,
+    amount_in: Euint128,
+    amount_out: Euint128,
+) -> Result<[Ebool; 2]> {
+    let price = if a_to_b {
+        price_q32
+    } else {
+        (PRICE_SCALE * PRICE_SCALE)
+            .checked_div(price_q32)
+            .ok_or(ErrorCode::InvalidOracle)?
+    };
+    let deviation = max_deviation_bps as u128;
+    let low = price * (BPS_DENOMINATOR - deviation.min(BPS_DENOMINATOR)) / BPS_DENOMINATOR;
+    let high = price
+        .checked_mul(BPS_DENOMINATOR + deviation)
+        .ok_or(ErrorCode::InvalidOracle)?
+        / BPS_DENOMINATOR;
+
+    let scale = as_euint128(operation(inco_program, signer), PRICE_SCALE)?;
+
+    let low = as_euint128(operation(inco_program, signer), low)?;
+
+    let high = as_euint128(operation(inco_program, signer), high)?;
+
+    // Compare amount_out * 2^32 against amount_in * bound
+    let scaled_out = e_mul(operation(inco_program, signer), amount_out, scale, SCALAR_BYTE)?;
+
+    let low_out = e_mul(operation(inco_program, signer), amount_in, low, SCALAR_BYTE)?;
+
+    let high_out = e_mul(operation(inco_program, signer), amount_in, high, SCALAR_BYTE)?;
+
+    let above_low: Ebool = e_ge(operation(inco_program, signer), scaled_out, low_out, SCALAR_BYTE)?;
+
+    let below_high: Ebool =
+        e_ge(operation(inco_program, signer), high_out, scaled_out, SCALAR_BYTE)?;
+
+    Ok([above_low, below_high])
+}
+
+/// Zero a swap whose execution price lies outside the band of `price_band_checks`
+pub(crate) fn enforce_price_band<'info>(
+    inco_program: &AccountInfo<'info>,
+    signer: &AccountInfo<'info>,
+    price_q32: u128,
+    max_deviation_bps: u16,
+    a_to_b: bool,
+    amount_in: &mut Euint128,
+    amount_out: &mut Euint128,
+    fee_amount: &mut Euint128,
+) -> Result<()> {
+    let checks = price_band_checks(
+        inco_program,
+        signer,
+        price_q32,
+        max_deviation_bps,
+        a_to_b,
+        *amount_in,
+        *amount_out,
+    )?;
+
+    let zero = as_euint128(operation(inco_program, signer), 0)?;
+
+    for in_band in checks {
+        *amount_in =
+            e_select(operation(inco_program, signer), in_band, *amount_in, zero, SCALAR_BYTE)?;
+
+        *amount_out =
+            e_select(operation(inco_program, signer), in_band, *amount_out, zero, SCALAR_BYTE)?;
+
+        *fee_amount =
+            e_select(operation(inco_program, signer), in_band, *fee_amount, zero, SCALAR_BYTE)?;
+    }
+    Ok(())
+}
+
+/// Reference price of a pool with an oracle price band, or `None` when it has none. The
+/// supplied feed account must be the pool's configured feed.
+pub(crate) fn pool_oracle_price(pool: &SwapPool, oracle: Option<&AccountInfo>, now: i64) -> Result<Option<u128>> {
+    if pool.oracle_feed == Pubkey::default() {
+        return Ok(None);
+    }
+    let oracle = oracle.ok_or(ErrorCode::InvalidOracle)?;
+    require_keys_eq!(oracle.key(), pool.oracle_feed, ErrorCode::InvalidOracle);
+    let price_q32 = read_oracle_price(&oracle.try_borrow_data()?, now, pool.oracle_max_staleness_secs)?;
+    Ok(Some(price_q32))
+}
+
+/// Read a Pyth `PriceUpdateV2` account and return its price as Q32 fixed point,
+/// rejecting partially verified, non-positive or stale prices
+pub(crate) fn read_oracle_price(data: &[u8], now: i64, max_staleness_secs: u32) -> Result<u128> {
+    require!(data.len() > 8, ErrorCode::InvalidOracle);
+    let update = PriceUpdateV2::deserialize(&mut &data[8..])
+        .map_err(|_| error!(ErrorCode::InvalidOracle))?;
+    require!(
+        matches!(update.verification_level, VerificationLevel::Full),
+        ErrorCode::InvalidOracle
+    );
+    let message = update.price_message;
+
+    require!(message.price > 0, ErrorCode::InvalidOracle);
+    require!(
+        now.saturating_sub(message.publish_time) <= max_staleness_secs as i64,
+        ErrorCode::StaleOracle
+    );
+
+    let price = message.price as u128;
+    let exponent = message.exponent;
+    let price_q32 = if exponent >= 0 {
+        10u128
+            .checked_pow(exponent as u32)
+            .and_then(|factor| price.checked_mul(factor))
+            .and_then(|value| value.checked_mul(PRICE_SCALE))
+    } else {
+        10u128
+            .checked_pow(exponent.unsigned_abs())
+            .and_then(|factor| price.checked_mul(PRICE_SCALE).map(|value| value / factor))
+    };
+    price_q32
+        .filter(|price| *price > 0)
+        .ok_or_else(|| error!(ErrorCode::InvalidOracle))
+}
+
+/// Pyth receiver `PriceUpdateV2` account layout (after the 8-byte discriminator),
+/// mirrored here so local tests can supply mock feeds
+#[derive(AnchorDeserialize)]
+pub struct PriceUpdateV2 {
+    pub write_authority: Pubkey,
+    pub verification_level: VerificationLevel,
+    pub price_message: PriceFeedMessage,
+    pub posted_slot: u64,
+}
+
+#[derive(AnchorDeserialize)]
+pub enum VerificationLevel {
+    Partial { num_signatures: u8 },
+    Full,
+}
+
+#[derive(AnchorDeserialize)]
+pub struct PriceFeedMessage {
+    pub feed_id: [u8; 32],
+    pub price: i64,
+    pub conf: u64,
+    pub exponent: i32,
+    pub publish_time: i64,
+    pub prev_publish_time: i64,
+    pub ema_price: i64,
+    pub ema_conf: u64,
+}
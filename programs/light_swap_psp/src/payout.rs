@@ -0,0 +1,75 @@
+//! Pending payout outcome flags and their attested decryptions
+
+use anchor_lang::prelude::*;
+use inco_lightning::cpi::accounts::{Allow, VerifySignature};
+use inco_lightning::cpi::{allow, as_euint128, e_ge, e_select, is_validsignature};
+use inco_lightning::types::{Ebool, Euint128};
+
+use crate::constants::*;
+use crate::fhe::*;
+
+/// Refund flag of a payout: the payout was not approved and the vault did receive
+/// the full `amount_in`, so refunding it cannot pay out more than was taken
+pub(crate) fn refund_due<'info>(
+    inco_program: &AccountInfo<'info>,
+    signer: &AccountInfo<'info>,
+    approved: Ebool,
+    received: Euint128,
+    amount_in: Euint128,
+) -> Result<Ebool> {
+    let zero = as_euint128(operation(inco_program, signer), 0)?;
+
+    let refundable =
+        e_select(operation(inco_program, signer), approved, zero, received, SCALAR_BYTE)?;
+
+    e_ge(operation(inco_program, signer), refundable, amount_in, SCALAR_BYTE)
+}
+
+/// Let a payout's beneficiary decrypt its outcome flags, so it can obtain the attested
+/// decryptions `settle_payout` verifies
+pub(crate) fn allow_payout_outcome<'info>(
+    inco_program: &AccountInfo<'info>,
+    signer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    beneficiary: &AccountInfo<'info>,
+    flags: &[(Ebool, AccountInfo<'info>)],
+) -> Result<()> {
+    for (flag, allowance) in flags {
+        let cpi_ctx = CpiContext::new(
+            inco_program.clone(),
+            Allow {
+                allowance_account: allowance.clone(),
+                signer: signer.clone(),
+                allowed_address: beneficiary.clone(),
+                system_program: system_program.clone(),
+            },
+        );
+        allow(cpi_ctx, flag.0, true, beneficiary.key())?;
+    }
+    Ok(())
+}
+
+/// Verify Inco's attested decryption of an encrypted flag and return its value
+pub(crate) fn attested_flag<'info>(
+    inco_program: &AccountInfo<'info>,
+    instructions: &AccountInfo<'info>,
+    signer: &AccountInfo<'info>,
+    flag: Ebool,
+    plaintext: Vec<u8>,
+) -> Result<bool> {
+    let value = plaintext.iter().any(|byte| *byte != 0);
+    let cpi_ctx = CpiContext::new(
+        inco_program.clone(),
+        VerifySignature {
+            instructions: instructions.clone(),
+            signer: signer.clone(),
+        },
+    );
+    is_validsignature(
+        cpi_ctx,
+        1,
+        Some(vec![flag.0.to_le_bytes().to_vec()]),
+        Some(vec![plaintext]),
+    )?;
+    Ok(value)
+}
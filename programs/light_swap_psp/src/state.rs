@@ -0,0 +1,594 @@
+//! Compressed account layouts
+
+use anchor_lang::prelude::*;
+use inco_lightning::types::{Ebool, Euint128};
+use light_sdk::LightDiscriminator;
+
+use crate::constants::*;
+use crate::curve::*;
+use crate::errors::ErrorCode;
+
+#[derive(
+    Clone,
+    Debug,
+    Default,
+    LightDiscriminator,
+    AnchorSerialize,
+    AnchorDeserialize,
+)]
+pub struct SwapPool {
+    pub authority: Pubkey,
+    pub pool_authority: Pubkey,
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    pub reserve_a: Euint128,
+    pub reserve_b: Euint128,
+    pub protocol_fee_a: Euint128,
+    pub protocol_fee_b: Euint128,
+    pub fee_bps: u16,
+    pub is_paused: bool,
+    pub last_update_ts: i64,
+    /// Layout version, `SWAP_POOL_VERSION` for every pool written by this program
+    pub version: u8,
+    /// Inco token accounts holding the pool's liquidity, owned by `pool_authority`
+    pub vault_a: Pubkey,
+    pub vault_b: Pubkey,
+    /// Bump of `pool_authority`, so signer seeds are built without re-deriving it
+    pub pool_authority_bump: u8,
+    pub pool_kind: PoolKind,
+    /// Key that may only pause the pool; `Pubkey::default()` when unset
+    pub guardian: Pubkey,
+    pub paused_at_ts: i64,
+    /// Time a pool must stay paused before `emergency_withdraw` is allowed
+    pub emergency_delay_secs: u32,
+    /// Bumped by every change to reserves, protocol fees or owed credits
+    pub state_nonce: u64,
+    /// Encrypted "reserves, fees and owed credits are all zero" flag from
+    /// `prepare_close_pool`, valid while `state_nonce` still equals `close_check_nonce`
+    pub close_check: Ebool,
+    pub close_check_nonce: u64,
+    /// StableSwap amplification ramp: `amp_initial` at start, `amp_future` from end onwards
+    pub amp_initial: u64,
+    pub amp_future: u64,
+    pub amp_ramp_start_ts: i64,
+    pub amp_ramp_end_ts: i64,
+    /// Weighted pools: integer token weights
+    pub weight_a: u8,
+    pub weight_b: u8,
+    /// Weighted and StableSwap pools: reserve down-scaling in bits applied before the
+    /// invariant check, keeping its products in u128
+    pub curve_scale_bits: u8,
+    /// Encrypted TWAP accumulators: sum of Q32 price * seconds since pool creation
+    pub price_cumulative_a: Euint128,
+    pub price_cumulative_b: Euint128,
+    /// Time the accumulators were last advanced to; unlike `last_update_ts` it is also
+    /// bumped by `record_observation`
+    pub price_cumulative_ts: i64,
+    /// Encrypted referral and batch credits not yet claimed, held in the vaults outside
+    /// reserves and protocol fees
+    pub owed_a: Euint128,
+    pub owed_b: Euint128,
+    /// Encrypted amounts skimmed since the last `sync_reserves`, kept out of later skims so
+    /// an excess is paid at most once
+    pub skimmed_a: Euint128,
+    pub skimmed_b: Euint128,
+    /// Only borrower allowed to take flash swaps, which must sign them; `Pubkey::default()`
+    /// when disabled
+    pub flash_borrower: Pubkey,
+    /// Encrypted flash loans whose repayment failed the pool checks, owed by the borrower
+    pub flash_debt_a: Euint128,
+    pub flash_debt_b: Euint128,
+    /// Share of the protocol fee credited to the referrer passed to `swap_exact_in`, in bps
+    pub referral_share_bps: u16,
+    /// Protocol's share of each swap fee in bps; the rest stays in the reserves for LPs
+    pub protocol_fee_share_bps: u16,
+    /// Dynamic fee mode: the applied rate is `fee_bps + volatility_accumulator`, clamped to
+    /// [min_fee_bps, max_fee_bps]. Each swap adds `volatility_step_bps` to the accumulator,
+    /// which halves every `volatility_half_life_secs` without activity.
+    pub dynamic_fee_enabled: bool,
+    pub min_fee_bps: u16,
+    pub max_fee_bps: u16,
+    pub volatility_step_bps: u16,
+    pub volatility_half_life_secs: u32,
+    pub volatility_accumulator: u32,
+    /// Time the accumulator was last decayed and bumped, only advanced by fee-paying swaps
+    pub volatility_updated_ts: i64,
+    /// Address granted decryption of proof-of-reserves results
+    pub auditor: Pubkey,
+    /// Id of the next reserve attestation; addresses are ["reserve_attestation", pool_address, id]
+    pub next_attestation_id: u64,
+    /// Circuit breaker: encrypted per-trade and per-window output caps for each side.
+    /// `breaker_tripped` is an encrypted 0/1 flag set when a swap hits either cap; with
+    /// `breaker_auto_pause` a tripped breaker rejects all further swaps under FHE.
+    pub breaker_enabled: bool,
+    pub breaker_auto_pause: bool,
+    pub breaker_window_secs: u32,
+    pub breaker_window_start_ts: i64,
+    pub max_trade_out_a: Euint128,
+    pub max_trade_out_b: Euint128,
+    pub window_outflow_cap_a: Euint128,
+    pub window_outflow_cap_b: Euint128,
+    pub window_outflow_a: Euint128,
+    pub window_outflow_b: Euint128,
+    pub breaker_tripped: Euint128,
+    /// Oracle price band for every swap, fill and batch settlement: fully verified Pyth
+    /// `PriceUpdateV2` account quoting B per A in base units; `Pubkey::default()` disables
+    /// the guard
+    pub oracle_feed: Pubkey,
+    pub oracle_max_staleness_secs: u32,
+    pub oracle_max_deviation_bps: u16,
+    /// Batch auction mode: swaps are queued into `SwapBatch` accounts and settled together
+    pub batch_mode: bool,
+    pub batch_window_secs: u32,
+    /// Id of the next batch to open; batch addresses are ["swap_batch", pool_address, id]
+    pub next_batch_id: u64,
+}
+
+/// Snapshot of a pool's encrypted price accumulators
+#[derive(Clone, Copy, Debug, Default, AnchorSerialize, AnchorDeserialize)]
+pub struct PriceObservation {
+    pub timestamp: i64,
+    pub price_cumulative_a: Euint128,
+    pub price_cumulative_b: Euint128,
+}
+
+/// Ring buffer of price observations for one pool, stored as a compressed account
+/// at address ["observations", pool_address]. TWAP over two observations is
+/// (cumulative_new - cumulative_old) / (timestamp_new - timestamp_old) / 2^32.
+#[derive(
+    Clone,
+    Debug,
+    Default,
+    LightDiscriminator,
+    AnchorSerialize,
+    AnchorDeserialize,
+)]
+pub struct PriceObservations {
+    pub pool_address: [u8; 32],
+    /// Slot the next observation is written to
+    pub next_index: u8,
+    pub observations: [PriceObservation; OBSERVATION_SLOTS],
+}
+
+/// Invariant used to validate swaps; all kinds share the same instructions
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, AnchorSerialize, AnchorDeserialize)]
+pub enum PoolKind {
+    #[default]
+    ConstantProduct,
+    StableSwap,
+    Weighted,
+}
+
+/// Encrypted limit order resting against a pool, stored as a compressed account at
+/// address ["limit_order", owner, nonce]. The limit price is min_amount_out / amount_in.
+#[derive(
+    Clone,
+    Debug,
+    Default,
+    LightDiscriminator,
+    AnchorSerialize,
+    AnchorDeserialize,
+)]
+pub struct LimitOrder {
+    pub owner: Pubkey,
+    pub pool_address: [u8; 32],
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    pub a_to_b: bool,
+    pub amount_in: Euint128,
+    pub min_amount_out: Euint128,
+    /// Ciphertexts replayed into the Inco Token transfers on fill or cancel
+    pub amount_in_ciphertext: Vec<u8>,
+    pub min_amount_out_ciphertext: Vec<u8>,
+    pub input_type: u8,
+    pub created_ts: i64,
+}
+
+/// Dollar-cost-averaging order stored as a compressed account at ["dca_order", owner, nonce].
+/// The budget sits in an escrow vault owned by the order's own ["dca_escrow", address] PDA.
+#[derive(
+    Clone,
+    Debug,
+    Default,
+    LightDiscriminator,
+    AnchorSerialize,
+    AnchorDeserialize,
+)]
+pub struct DcaOrder {
+    pub owner: Pubkey,
+    pub pool_address: [u8; 32],
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    pub a_to_b: bool,
+    pub remaining_budget: Euint128,
+    pub amount_per_interval: Euint128,
+    /// Minimum output per slice; each slice fills at exactly this amount
+    pub min_amount_out: Euint128,
+    /// Ciphertexts replayed into the Inco Token transfers of every slice
+    pub amount_per_interval_ciphertext: Vec<u8>,
+    pub min_amount_out_ciphertext: Vec<u8>,
+    pub input_type: u8,
+    pub interval_secs: u32,
+    /// Plaintext cap on slices so an exhausted escrow is never executed again; zeroed by
+    /// `cancel_dca_order`
+    pub executions_remaining: u32,
+    pub last_execution_ts: i64,
+}
+
+/// Swap queued into a batch; `amount_in` is net of the pool fee. Once the batch is
+/// settled, `amount_out` holds the encrypted output credit of a filled intent and
+/// `refund` the net input of an unfilled one; claims debit them.
+#[derive(Clone, Debug, Default, AnchorSerialize, AnchorDeserialize)]
+pub struct SwapIntent {
+    pub trader: Pubkey,
+    pub trader_token_in: Pubkey,
+    pub trader_token_out: Pubkey,
+    pub a_to_b: bool,
+    pub amount_in: Euint128,
+    pub min_amount_out: Euint128,
+    pub amount_out: Euint128,
+    pub refund: Euint128,
+}
+
+/// One batch auction window of a pool, stored as a compressed account at
+/// address ["swap_batch", pool_address, batch_id]
+#[derive(
+    Clone,
+    Debug,
+    Default,
+    LightDiscriminator,
+    AnchorSerialize,
+    AnchorDeserialize,
+)]
+pub struct SwapBatch {
+    pub pool_address: [u8; 32],
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    pub vault_a: Pubkey,
+    pub vault_b: Pubkey,
+    pub pool_authority: Pubkey,
+    pub pool_authority_bump: u8,
+    pub batch_id: u64,
+    pub window_end_ts: i64,
+    pub settled: bool,
+    /// Encrypted sums of queued net inputs per direction
+    pub total_in_a: Euint128,
+    pub total_in_b: Euint128,
+    pub intents: Vec<SwapIntent>,
+}
+
+/// Referral fees accrued by one referrer on one pool, stored as a compressed account at
+/// address ["referrer_fees", pool_address, referrer]
+#[derive(
+    Clone,
+    Debug,
+    Default,
+    LightDiscriminator,
+    AnchorSerialize,
+    AnchorDeserialize,
+)]
+pub struct ReferrerFees {
+    pub referrer: Pubkey,
+    pub pool_address: [u8; 32],
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    /// Pool vaults and authority, recorded when fees are first credited
+    pub vault_a: Pubkey,
+    pub vault_b: Pubkey,
+    pub pool_authority: Pubkey,
+    pub pool_authority_bump: u8,
+    pub fee_a: Euint128,
+    pub fee_b: Euint128,
+}
+
+/// Referrer fee account passed to `swap_exact_in`
+#[derive(Clone, AnchorSerialize, AnchorDeserialize)]
+pub struct ReferrerAccountData {
+    pub meta: light_sdk::instruction::account_meta::CompressedAccountMeta,
+    pub data: Vec<u8>,
+}
+
+/// Proof-of-reserves result, stored as a compressed account at address
+/// ["reserve_attestation", pool_address, attestation_id]. Both flags decrypt to true when
+/// the vault covers reserve + protocol fees; only the auditor can decrypt them.
+#[derive(
+    Clone,
+    Debug,
+    Default,
+    LightDiscriminator,
+    AnchorSerialize,
+    AnchorDeserialize,
+)]
+pub struct ReserveAttestation {
+    pub pool_address: [u8; 32],
+    pub auditor: Pubkey,
+    pub attestation_id: u64,
+    pub timestamp: i64,
+    pub vault_a_covers: Ebool,
+    pub vault_b_covers: Ebool,
+}
+
+/// `SwapPool` as written before the layout was versioned. Shares the `SwapPool`
+/// discriminator so legacy accounts can be read and burned by `migrate_pool`.
+#[derive(Clone, Debug, Default, AnchorSerialize, AnchorDeserialize)]
+pub struct LegacySwapPool {
+    pub authority: Pubkey,
+    pub pool_authority: Pubkey,
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    pub reserve_a: Euint128,
+    pub reserve_b: Euint128,
+    pub protocol_fee_a: Euint128,
+    pub protocol_fee_b: Euint128,
+    pub fee_bps: u16,
+    pub is_paused: bool,
+    pub last_update_ts: i64,
+}
+
+impl LegacySwapPool {
+    /// Serialized size: 4 pubkeys, 4 encrypted handles, fee_bps, is_paused, last_update_ts
+    pub const LEN: usize = 4 * 32 + 4 * 16 + 2 + 1 + 8;
+}
+
+impl LightDiscriminator for LegacySwapPool {
+    const LIGHT_DISCRIMINATOR: [u8; 8] = SwapPool::LIGHT_DISCRIMINATOR;
+    const LIGHT_DISCRIMINATOR_SLICE: &'static [u8] = SwapPool::LIGHT_DISCRIMINATOR_SLICE;
+}
+
+/// Registry entry written by `initialize_pool` and `migrate_pool` at
+/// ["pool_index", mint_a, mint_b, fee_bps], for on-chain pool discovery; closed with the pool
+#[derive(
+    Clone,
+    Debug,
+    Default,
+    LightDiscriminator,
+    AnchorSerialize,
+    AnchorDeserialize,
+)]
+pub struct PoolIndexEntry {
+    pub pool_address: [u8; 32],
+    pub pool_authority: Pubkey,
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    pub fee_bps: u16,
+    pub pool_kind: PoolKind,
+    pub vault_a: Pubkey,
+    pub vault_b: Pubkey,
+    pub created_at: i64,
+}
+
+/// Pending unwrap of a wrapped Inco token into its SPL backing, stored as a compressed
+/// account at ["unwrap_ticket", owner, nonce]. Claimable once `burn_covers` is attested true.
+#[derive(
+    Clone,
+    Debug,
+    Default,
+    LightDiscriminator,
+    AnchorSerialize,
+    AnchorDeserialize,
+)]
+pub struct UnwrapTicket {
+    pub owner: Pubkey,
+    pub spl_mint: Pubkey,
+    pub inco_mint: Pubkey,
+    pub recipient: Pubkey,
+    pub amount: u64,
+    pub burn_covers: Ebool,
+    pub created_at: i64,
+}
+
+/// Token movement made by one payout leg
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, AnchorSerialize, AnchorDeserialize)]
+pub enum PayoutLegKind {
+    /// Inco transfer of `ciphertext` from `source` to `destination`
+    #[default]
+    Transfer,
+    /// Inco mint of `ciphertext` from mint `source` into `destination`
+    MintTo,
+    /// SPL transfer of the plaintext `amount` from `source` to `destination`
+    SplTransfer,
+}
+
+/// One token movement of a pending payout, signed by a program PDA
+#[derive(Clone, Debug, Default, AnchorSerialize, AnchorDeserialize)]
+pub struct PayoutLeg {
+    pub kind: PayoutLegKind,
+    /// Seeds, bump included, of the PDA that signs the leg
+    pub authority_seeds: Vec<Vec<u8>>,
+    pub source: Pubkey,
+    pub destination: Pubkey,
+    /// Client ciphertext replayed into the Inco Token CPI
+    pub ciphertext: Vec<u8>,
+    /// Plaintext amount of an SPL transfer leg
+    pub amount: u64,
+}
+
+impl PayoutLeg {
+    pub(crate) fn transfer(
+        authority_seeds: Vec<Vec<u8>>,
+        source: Pubkey,
+        destination: Pubkey,
+        ciphertext: Vec<u8>,
+    ) -> Self {
+        Self {
+            kind: PayoutLegKind::Transfer,
+            authority_seeds,
+            source,
+            destination,
+            ciphertext,
+            amount: 0,
+        }
+    }
+
+    pub(crate) fn mint_to(
+        authority_seeds: Vec<Vec<u8>>,
+        mint: Pubkey,
+        destination: Pubkey,
+        ciphertext: Vec<u8>,
+    ) -> Self {
+        Self {
+            kind: PayoutLegKind::MintTo,
+            authority_seeds,
+            source: mint,
+            destination,
+            ciphertext,
+            amount: 0,
+        }
+    }
+
+    pub(crate) fn spl_transfer(
+        authority_seeds: Vec<Vec<u8>>,
+        source: Pubkey,
+        destination: Pubkey,
+        amount: u64,
+    ) -> Self {
+        Self {
+            kind: PayoutLegKind::SplTransfer,
+            authority_seeds,
+            source,
+            destination,
+            ciphertext: Vec::new(),
+            amount,
+        }
+    }
+}
+
+/// Transfers decided under FHE, stored as a compressed account at
+/// ["payout", beneficiary, nonce]. Inco Token moves client ciphertexts, not handles, so
+/// outputs are never sent in the transaction that validates them: `settle_payout` runs
+/// `on_approved` once `approved` is attested true, or `on_refunded` once `refunded` is.
+/// `refunded` is unused when there are no refund legs.
+#[derive(
+    Clone,
+    Debug,
+    Default,
+    LightDiscriminator,
+    AnchorSerialize,
+    AnchorDeserialize,
+)]
+pub struct PendingPayout {
+    pub beneficiary: Pubkey,
+    pub input_type: u8,
+    pub approved: Ebool,
+    pub on_approved: Vec<PayoutLeg>,
+    pub refunded: Ebool,
+    pub on_refunded: Vec<PayoutLeg>,
+    pub created_at: i64,
+}
+
+impl SwapPool {
+    /// Deserialize pool data of the current layout. Legacy pools must go through
+    /// `migrate_pool` first, since their account hash covers the old layout.
+    pub(crate) fn load(data: &[u8]) -> Result<Self> {
+        require!(data.len() != LegacySwapPool::LEN, ErrorCode::PoolNeedsMigration);
+        let pool = Self::try_from_slice(data)?;
+        require!(pool.version == SWAP_POOL_VERSION, ErrorCode::UnsupportedPoolVersion);
+        Ok(pool)
+    }
+
+    /// Amplification coefficient at `now`, linearly interpolated over the ramp window
+    pub(crate) fn current_amp(&self, now: i64) -> u64 {
+        if now >= self.amp_ramp_end_ts || self.amp_ramp_end_ts <= self.amp_ramp_start_ts {
+            return self.amp_future;
+        }
+        let elapsed = (now - self.amp_ramp_start_ts).max(0) as u128;
+        let duration = (self.amp_ramp_end_ts - self.amp_ramp_start_ts) as u128;
+        if self.amp_future >= self.amp_initial {
+            let delta = (self.amp_future - self.amp_initial) as u128 * elapsed / duration;
+            self.amp_initial + delta as u64
+        } else {
+            let delta = (self.amp_initial - self.amp_future) as u128 * elapsed / duration;
+            self.amp_initial - delta as u64
+        }
+    }
+
+    /// Reserves and protocol fee for the given swap direction: (reserve_in, reserve_out, protocol_fee_in)
+    pub(crate) fn swap_side(&self, a_to_b: bool) -> (Euint128, Euint128, Euint128) {
+        if a_to_b {
+            (self.reserve_a, self.reserve_b, self.protocol_fee_a)
+        } else {
+            (self.reserve_b, self.reserve_a, self.protocol_fee_b)
+        }
+    }
+
+    /// Record a change to reserves, protocol fees or owed credits, invalidating any
+    /// prepared close check
+    pub(crate) fn bump_state_nonce(&mut self) {
+        self.state_nonce = self.state_nonce.wrapping_add(1);
+    }
+
+    pub(crate) fn apply_swap_updates(&mut self, updates: &SwapUpdates, a_to_b: bool) {
+        if a_to_b {
+            self.reserve_a = updates.new_reserve_in;
+            self.reserve_b = updates.new_reserve_out;
+            self.protocol_fee_a = updates.new_protocol_fee;
+        } else {
+            self.reserve_b = updates.new_reserve_in;
+            self.reserve_a = updates.new_reserve_out;
+            self.protocol_fee_b = updates.new_protocol_fee;
+        }
+        if let Some(breaker) = &updates.breaker {
+            if a_to_b {
+                self.window_outflow_b = breaker.window_outflow;
+            } else {
+                self.window_outflow_a = breaker.window_outflow;
+            }
+            self.breaker_tripped = breaker.tripped;
+        }
+    }
+
+    /// Claims of owed credits pay nothing out of reserves, so they reopen on a paused pool once
+    /// its emergency delay has elapsed
+    pub(crate) fn claims_open(&self, now: i64) -> bool {
+        !self.is_paused || now >= self.paused_at_ts + self.emergency_delay_secs as i64
+    }
+
+    /// Fee rate for a swap at `now`. In dynamic mode this decays the volatility accumulator
+    /// by the time since `volatility_updated_ts` and then bumps it for the swap being priced.
+    pub(crate) fn effective_fee_bps(&mut self, now: i64) -> u16 {
+        if !self.dynamic_fee_enabled {
+            return self.fee_bps;
+        }
+        let elapsed = (now - self.volatility_updated_ts).max(0) as u64;
+        let halvings = elapsed / self.volatility_half_life_secs.max(1) as u64;
+        let volatility = if halvings >= u32::BITS as u64 {
+            0
+        } else {
+            self.volatility_accumulator >> halvings
+        };
+        let fee_bps = (self.fee_bps as u32 + volatility)
+            .clamp(self.min_fee_bps as u32, self.max_fee_bps as u32);
+        self.volatility_accumulator = (volatility + self.volatility_step_bps as u32)
+            .min(self.max_fee_bps as u32);
+        self.volatility_updated_ts = now;
+        fee_bps as u16
+    }
+
+    /// Mints of a swap in the given direction: (mint_in, mint_out)
+    pub(crate) fn swap_mints(&self, a_to_b: bool) -> (Pubkey, Pubkey) {
+        if a_to_b {
+            (self.mint_a, self.mint_b)
+        } else {
+            (self.mint_b, self.mint_a)
+        }
+    }
+
+    /// Seeds of the pool authority PDA, bump included, as stored in payout legs
+    pub(crate) fn authority_seeds(&self) -> Vec<Vec<u8>> {
+        vec![
+            POOL_AUTH_SEED.to_vec(),
+            self.mint_a.to_bytes().to_vec(),
+            self.mint_b.to_bytes().to_vec(),
+            vec![self.pool_authority_bump],
+        ]
+    }
+
+    /// Vaults of a swap in the given direction: (vault_in, vault_out)
+    pub(crate) fn swap_vaults(&self, a_to_b: bool) -> (Pubkey, Pubkey) {
+        if a_to_b {
+            (self.vault_a, self.vault_b)
+        } else {
+            (self.vault_b, self.vault_a)
+        }
+    }
+}
@@ -0,0 +1,49 @@
+//! Encrypted Inco vault balances
+
+use anchor_lang::prelude::*;
+use inco_lightning::cpi::{as_euint128, e_add, e_ge, e_select, e_sub};
+use inco_lightning::types::{Ebool, Euint128};
+
+use crate::constants::*;
+use crate::errors::ErrorCode;
+use crate::fhe::*;
+use crate::inco_token::{IncoAccount, ID as INCO_TOKEN_ID};
+
+/// Vault balance not owed as protocol fees or unclaimed credits, floored at zero
+pub(crate) fn vault_backing<'info>(
+    inco_program: &AccountInfo<'info>,
+    signer: &AccountInfo<'info>,
+    vault_amount: Euint128,
+    protocol_fee: Euint128,
+    owed: Euint128,
+) -> Result<Euint128> {
+    let liabilities = e_add(operation(inco_program, signer), protocol_fee, owed, SCALAR_BYTE)?;
+
+    let covers_fee: Ebool =
+        e_ge(operation(inco_program, signer), vault_amount, liabilities, SCALAR_BYTE)?;
+
+    let backing = e_sub(operation(inco_program, signer), vault_amount, liabilities, SCALAR_BYTE)?;
+
+    let zero = as_euint128(operation(inco_program, signer), 0)?;
+
+    e_select(operation(inco_program, signer), covers_fee, backing, zero, SCALAR_BYTE)
+}
+
+/// Encrypted balance of an Inco token account passed as a raw account
+pub(crate) fn inco_balance(account: &AccountInfo) -> Result<Euint128> {
+    require_keys_eq!(*account.owner, INCO_TOKEN_ID, ErrorCode::InvalidPoolVault);
+    let data = account.try_borrow_data()?;
+    Ok(IncoAccount::try_deserialize(&mut &data[..])?.amount)
+}
+
+/// Encrypted amount an Inco token account gained since `balance_before`
+pub(crate) fn vault_received<'info>(
+    inco_program: &AccountInfo<'info>,
+    signer: &AccountInfo<'info>,
+    vault: &AccountInfo<'info>,
+    balance_before: Euint128,
+) -> Result<Euint128> {
+    let balance_after = inco_balance(vault)?;
+
+    e_sub(operation(inco_program, signer), balance_after, balance_before, SCALAR_BYTE)
+}
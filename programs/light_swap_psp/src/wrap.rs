@@ -0,0 +1,116 @@
+//! SPL deposits backing wrapped Inco mints
+
+use anchor_lang::prelude::*;
+use anchor_lang::system_program::{transfer as system_transfer, Transfer as SystemTransfer};
+use anchor_spl::token::spl_token::native_mint::ID as NATIVE_MINT;
+use anchor_spl::token::{
+    sync_native, transfer as spl_transfer, SyncNative, TokenAccount, Transfer as SplTransfer,
+};
+use inco_lightning::cpi::{as_euint128, e_ge};
+use inco_lightning::types::{Ebool, Euint128};
+
+use crate::constants::*;
+use crate::errors::ErrorCode;
+use crate::fhe::*;
+use crate::inco_token::{COption, IncoMint};
+use crate::state::*;
+
+/// Encrypted flag that an amount to be minted against an SPL deposit is exactly the
+/// deposited plaintext
+pub(crate) fn matches_deposit<'info>(
+    inco_program: &AccountInfo<'info>,
+    signer: &AccountInfo<'info>,
+    amount: Euint128,
+    deposited: u64,
+) -> Result<Ebool> {
+    let deposited = as_euint128(operation(inco_program, signer), deposited as u128)?;
+
+    let within_deposit: Ebool =
+        e_ge(operation(inco_program, signer), deposited, amount, SCALAR_BYTE)?;
+
+    let covers_deposit: Ebool =
+        e_ge(operation(inco_program, signer), amount, deposited, SCALAR_BYTE)?;
+
+    e_all(inco_program, signer, &[within_deposit, covers_deposit])
+}
+
+/// Take a plain deposit into an SPL backing vault: lamports plus a native sync for
+/// wrapped SOL, an SPL transfer out of `depositor_spl` otherwise
+pub(crate) fn take_wrap_deposit<'info>(
+    token_program: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    depositor: &AccountInfo<'info>,
+    depositor_spl: &Account<'info, TokenAccount>,
+    backing_vault: &Account<'info, TokenAccount>,
+    amount: u64,
+) -> Result<()> {
+    require_keys_eq!(depositor_spl.mint, backing_vault.mint, ErrorCode::InvalidWrapAccounts);
+    if backing_vault.mint == NATIVE_MINT {
+        let deposit_ctx = CpiContext::new(
+            system_program.clone(),
+            SystemTransfer {
+                from: depositor.clone(),
+                to: backing_vault.to_account_info(),
+            },
+        );
+        system_transfer(deposit_ctx, amount)?;
+
+        let sync_ctx = CpiContext::new(
+            token_program.clone(),
+            SyncNative { account: backing_vault.to_account_info() },
+        );
+        sync_native(sync_ctx)
+    } else {
+        let deposit_ctx = CpiContext::new(
+            token_program.clone(),
+            SplTransfer {
+                from: depositor_spl.to_account_info(),
+                to: backing_vault.to_account_info(),
+                authority: depositor.clone(),
+            },
+        );
+        spl_transfer(deposit_ctx, amount)
+    }
+}
+
+/// Payout legs of a wrapped deposit: mint `ciphertext` of the Inco mint into `vault` once
+/// approved, or return the plain `amount` to `depositor_spl` once refunded
+pub(crate) fn wrap_deposit_legs(
+    wrap_bump: u8,
+    backing_vault: &Account<TokenAccount>,
+    inco_mint: Pubkey,
+    vault: Pubkey,
+    depositor_spl: Pubkey,
+    ciphertext: Vec<u8>,
+    amount: u64,
+) -> (PayoutLeg, PayoutLeg) {
+    let wrap_seeds = vec![
+        WRAP_AUTHORITY_SEED.to_vec(),
+        backing_vault.mint.to_bytes().to_vec(),
+        inco_mint.to_bytes().to_vec(),
+        vec![wrap_bump],
+    ];
+    (
+        PayoutLeg::mint_to(wrap_seeds.clone(), inco_mint, vault, ciphertext),
+        PayoutLeg::spl_transfer(wrap_seeds, backing_vault.key(), depositor_spl, amount),
+    )
+}
+
+/// Check the wrap authority PDA of an SPL backing vault and Inco mint pair; returns its bump
+pub(crate) fn verify_wrap_authority(
+    wrap_authority: &Pubkey,
+    backing_vault: &TokenAccount,
+    inco_mint: &Account<IncoMint>,
+) -> Result<u8> {
+    let (expected, bump) = Pubkey::find_program_address(
+        &[WRAP_AUTHORITY_SEED, backing_vault.mint.as_ref(), inco_mint.key().as_ref()],
+        &crate::ID,
+    );
+    require_keys_eq!(*wrap_authority, expected, ErrorCode::InvalidWrapAccounts);
+    require_keys_eq!(backing_vault.owner, expected, ErrorCode::InvalidWrapAccounts);
+    require!(
+        inco_mint.mint_authority == COption::Some(expected),
+        ErrorCode::InvalidWrapAccounts
+    );
+    Ok(bump)
+}
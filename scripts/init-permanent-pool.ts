@@ -3,8 +3,11 @@
  * 
  * Creates a permanent SOL/USDC pool on devnet with fixed mints so the frontend
  * can be tested by anyone without needing to initialize the pool first.
+ * The pool trades the Inco mints wrapping the fixed SPL mints; initialize_pool
+ * creates both pool vaults on-chain.
  * 
- * Run with: npx ts-node scripts/init-permanent-pool.ts
+ * Run with: INCO_WSOL_MINT=<inco mint> INCO_USDC_MINT=<inco mint> \
+ *   npx ts-node scripts/init-permanent-pool.ts
  */
 
 import * as anchor from "@coral-xyz/anchor";
@@ -37,8 +40,11 @@ import { LightSwapPsp } from "../target/types/light_swap_psp";
 const DEVNET_WSOL_MINT = new PublicKey("So11111111111111111111111111111111111111112");
 const DEVNET_USDC_MINT = new PublicKey("4zMMC9srt5Ri5X14GAgXhaHii3GnPAEERYPJgZJDncDU");
 
-// Inco Lightning Program
+// Inco Lightning and Inco Token Programs
 const INCO_LIGHTNING_PROGRAM_ID = new PublicKey("5sjEbPiqgZrYwR31ahR6Uk9wf5awoX61YGg7jExQSwaj");
+const INCO_TOKEN_PROGRAM_ID = new PublicKey("CYVSeUyVzHGVcrxsJt3E8tbaPCQT8ASdRR45g5WxUEW7");
+
+const FEE_BPS = 30; // 0.3%
 
 // Light Protocol V2 accounts
 const LIGHT_BATCH_ADDRESS_TREE = new PublicKey(batchAddressTree);
@@ -48,6 +54,15 @@ async function main() {
   console.log("=".repeat(60));
   console.log("Permanent Pool Initialization Script");
   console.log("=".repeat(60));
+
+  // Pool mints must be Inco mints; these wrap DEVNET_WSOL_MINT and DEVNET_USDC_MINT
+  if (!process.env.INCO_WSOL_MINT || !process.env.INCO_USDC_MINT) {
+    console.error("❌ INCO_WSOL_MINT and INCO_USDC_MINT are required");
+    console.error("   Set them to the Inco mints wrapping", DEVNET_WSOL_MINT.toBase58(), "and", DEVNET_USDC_MINT.toBase58());
+    process.exit(1);
+  }
+  const incoWsolMint = new PublicKey(process.env.INCO_WSOL_MINT);
+  const incoUsdcMint = new PublicKey(process.env.INCO_USDC_MINT);
   
   // Configuration - Use Helius RPC with Light Protocol support
   // Same API key as velvet-rope frontend for consistency
@@ -73,19 +88,46 @@ async function main() {
   console.log("  RPC:", rpcUrl.includes("helius") ? "Helius devnet" : "standard devnet");
   console.log("  Authority:", authority.toBase58());
   console.log("  Program ID:", swapProgram.programId.toBase58());
-  console.log("  Mint A (wSOL):", DEVNET_WSOL_MINT.toBase58());
-  console.log("  Mint B (USDC):", DEVNET_USDC_MINT.toBase58());
+  console.log("  Mint A (Inco wSOL):", incoWsolMint.toBase58());
+  console.log("  Mint B (Inco USDC):", incoUsdcMint.toBase58());
 
   // Derive pool address
   const addressTree = LIGHT_BATCH_ADDRESS_TREE;
   const outputQueue = LIGHT_OUTPUT_QUEUE;
   
-  const seeds = [Buffer.from("pool"), DEVNET_WSOL_MINT.toBuffer(), DEVNET_USDC_MINT.toBuffer()];
+  const seeds = [Buffer.from("pool"), incoWsolMint.toBuffer(), incoUsdcMint.toBuffer()];
   const poolAddressSeed = deriveAddressSeedV2(seeds);
   const poolAddress = deriveAddressV2(poolAddressSeed, addressTree, swapProgram.programId);
+
+  const feeBpsBytes = Buffer.alloc(2);
+  feeBpsBytes.writeUInt16LE(FEE_BPS);
+  const indexAddressSeed = deriveAddressSeedV2([
+    Buffer.from("pool_index"),
+    incoWsolMint.toBuffer(),
+    incoUsdcMint.toBuffer(),
+    feeBpsBytes,
+  ]);
+  const indexAddress = deriveAddressV2(indexAddressSeed, addressTree, swapProgram.programId);
+
+  // Vault PDAs created by initialize_pool, owned by the pool authority PDA
+  const [poolAuthority] = PublicKey.findProgramAddressSync(
+    [Buffer.from("pool_authority"), incoWsolMint.toBuffer(), incoUsdcMint.toBuffer()],
+    swapProgram.programId
+  );
+  const [poolVaultA] = PublicKey.findProgramAddressSync(
+    [Buffer.from("pool_vault"), poolAuthority.toBuffer(), incoWsolMint.toBuffer()],
+    swapProgram.programId
+  );
+  const [poolVaultB] = PublicKey.findProgramAddressSync(
+    [Buffer.from("pool_vault"), poolAuthority.toBuffer(), incoUsdcMint.toBuffer()],
+    swapProgram.programId
+  );
   
   console.log("\nPool Details:");
   console.log("  Pool Address:", poolAddress.toBase58());
+  console.log("  Pool Index Address:", indexAddress.toBase58());
+  console.log("  Pool Vault A:", poolVaultA.toBase58());
+  console.log("  Pool Vault B:", poolVaultB.toBase58());
   console.log("  Address Tree:", addressTree.toBase58());
   console.log("  Output Queue:", outputQueue.toBase58());
 
@@ -108,16 +150,16 @@ async function main() {
 
   console.log("   Pool not found. Initializing...");
 
-  // Get validity proof for new address
+  // Get validity proof for the new pool and index addresses
   let proofResult: any;
   try {
     proofResult = await lightRpc.getValidityProofV0(
       [],
-      [{
-        address: bn(poolAddress.toBytes()),
+      [poolAddress, indexAddress].map((address) => ({
+        address: bn(address.toBytes()),
         tree: addressTree,
         queue: addressTree,
-      }]
+      }))
     );
     console.log("   Got validity proof, rootIndex:", proofResult.rootIndices?.[0]);
   } catch (e: any) {
@@ -163,15 +205,26 @@ async function main() {
         validityProof,
         packedAddressTreeInfo,
         outputStateTreeIndex,
-        DEVNET_WSOL_MINT,
-        DEVNET_USDC_MINT,
-        30 // fee_bps (0.3%)
+        incoWsolMint,
+        incoUsdcMint,
+        FEE_BPS,
+        { constantProduct: {} },
+        bn(0), // amp (StableSwap only)
+        0, // weight_a (Weighted only)
+        0, // weight_b (Weighted only)
+        0 // curve_scale_bits (StableSwap and Weighted only)
       )
       .accounts({
         feePayer: authority,
         authority: authority,
+        poolAuthority,
+        mintA: incoWsolMint,
+        mintB: incoUsdcMint,
+        poolVaultA,
+        poolVaultB,
         incoLightningProgram: INCO_LIGHTNING_PROGRAM_ID,
-      })
+        incoTokenProgram: INCO_TOKEN_PROGRAM_ID,
+      } as any)
       .remainingAccounts(remainingAccounts)
       .instruction();
     
@@ -200,8 +253,10 @@ async function main() {
       console.log("PERMANENT POOL CREATED");
       console.log("=".repeat(60));
       console.log("  Pool Address:", poolAddress.toBase58());
-      console.log("  Mint A (wSOL):", DEVNET_WSOL_MINT.toBase58());
-      console.log("  Mint B (USDC):", DEVNET_USDC_MINT.toBase58());
+      console.log("  Mint A (Inco wSOL):", incoWsolMint.toBase58());
+      console.log("  Mint B (Inco USDC):", incoUsdcMint.toBase58());
+      console.log("  Pool Vault A:", poolVaultA.toBase58());
+      console.log("  Pool Vault B:", poolVaultB.toBase58());
       console.log("  Fee: 0.3%");
       console.log("  Authority:", authority.toBase58());
       console.log("\nFrontend can now connect and use this pool!");
@@ -2,7 +2,8 @@
  * Initialize Light Protocol Pool for Inco Token Swap
  * 
  * This creates the compressed pool state in Light Protocol
- * that tracks reserves and enables swaps.
+ * that tracks reserves and enables swaps, together with its pool index entry
+ * and the two pool vault PDAs (created on-chain by initialize_pool).
  * 
  * Usage: npx ts-node scripts/initialize-pool.ts
  */
@@ -53,6 +54,8 @@ const LIGHT_BATCH_ADDRESS_TREE = new PublicKey(batchAddressTree);
 const LIGHT_OUTPUT_QUEUE = new PublicKey("oq1na8gojfdUhsfCpyjNt6h4JaDWtHf1yQj4koBWfto");
 const INCO_LIGHTNING_PROGRAM_ID = new PublicKey("5sjEbPiqgZrYwR31ahR6Uk9wf5awoX61YGg7jExQSwaj");
 
+const FEE_BPS = 30; // 0.3% fee
+
 async function main() {
   console.log("=".repeat(60));
   console.log("Initialize Light Protocol Pool");
@@ -106,6 +109,34 @@ async function main() {
   const poolAddress = deriveAddressV2(poolAddressSeed, addressTree, swapProgram.programId);
   console.log("\nPool Address:", poolAddress.toBase58());
 
+  // Pool index entry, keyed by the mints and fee
+  const feeBpsBytes = Buffer.alloc(2);
+  feeBpsBytes.writeUInt16LE(FEE_BPS);
+  const indexAddressSeed = deriveAddressSeedV2([
+    Buffer.from("pool_index"),
+    mintA.toBuffer(),
+    mintB.toBuffer(),
+    feeBpsBytes,
+  ]);
+  const indexAddress = deriveAddressV2(indexAddressSeed, addressTree, swapProgram.programId);
+  console.log("Pool Index Address:", indexAddress.toBase58());
+
+  // Vault PDAs created by initialize_pool, owned by the pool authority PDA
+  const [poolAuthority] = PublicKey.findProgramAddressSync(
+    [Buffer.from("pool_authority"), mintA.toBuffer(), mintB.toBuffer()],
+    swapProgram.programId
+  );
+  const [poolVaultA] = PublicKey.findProgramAddressSync(
+    [Buffer.from("pool_vault"), poolAuthority.toBuffer(), mintA.toBuffer()],
+    swapProgram.programId
+  );
+  const [poolVaultB] = PublicKey.findProgramAddressSync(
+    [Buffer.from("pool_vault"), poolAuthority.toBuffer(), mintB.toBuffer()],
+    swapProgram.programId
+  );
+  console.log("Pool Vault A:", poolVaultA.toBase58());
+  console.log("Pool Vault B:", poolVaultB.toBase58());
+
   // Check if pool already exists
   try {
     const accounts = await lightRpc.getCompressedAccountsByOwner(swapProgram.programId);
@@ -123,18 +154,18 @@ async function main() {
     console.log("No existing pool found, proceeding with initialization...");
   }
 
-  // Get validity proof from Light RPC for the new address
+  // Get validity proof from Light RPC for the new pool and index addresses
   console.log("\nGetting validity proof from Light RPC...");
   
   let proofResult: any;
   try {
     proofResult = await lightRpc.getValidityProofV0(
       [], // No existing hashes (creating new)
-      [{
-        address: bn(poolAddress.toBytes()),
+      [poolAddress, indexAddress].map((address) => ({
+        address: bn(address.toBytes()),
         tree: addressTree,
         queue: addressTree, // Same as tree per Light Protocol example
-      }]
+      }))
     );
     console.log("Got validity proof, rootIndex:", proofResult.rootIndices?.[0]);
   } catch (e: any) {
@@ -190,12 +221,24 @@ async function main() {
         outputQueueIndex,
         mintA,
         mintB,
-        30 // 0.3% fee
+        FEE_BPS,
+        { constantProduct: {} },
+        bn(0), // amp (StableSwap only)
+        0, // weight_a (Weighted only)
+        0, // weight_b (Weighted only)
+        0 // curve_scale_bits (StableSwap and Weighted only)
       )
       .accounts({
         feePayer: authority,
         authority: authority,
-      })
+        poolAuthority,
+        mintA,
+        mintB,
+        poolVaultA,
+        poolVaultB,
+        incoLightningProgram: INCO_LIGHTNING_PROGRAM_ID,
+        incoTokenProgram: new PublicKey(config.incoTokenProgram),
+      } as any)
       .remainingAccounts(remainingAccounts)
       .instruction();
 
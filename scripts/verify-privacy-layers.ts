@@ -13,6 +13,7 @@ import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
 import {
   Connection,
+  Keypair,
   PublicKey,
   ComputeBudgetProgram,
 } from "@solana/web3.js";
@@ -343,6 +344,8 @@ async function main() {
         packedAccounts.addSystemAccountsV2(SystemAccountMetaConfig.new(swapProgram.programId));
         const stateTreeIndex = packedAccounts.insertOrGet(stateTree);
         const stateQueueIndex = packedAccounts.insertOrGet(stateQueue);
+        const addressTreeIndex = packedAccounts.insertOrGet(LIGHT_BATCH_ADDRESS_TREE);
+        const outputTreeIndex = packedAccounts.insertOrGet(LIGHT_OUTPUT_QUEUE);
         
         const { remainingAccounts: rawAccounts } = packedAccounts.toAccountMetas();
         const remainingAccounts = rawAccounts.map((a: any) => ({
@@ -367,6 +370,12 @@ async function main() {
           outputStateTreeIndex: stateQueueIndex,
         };
 
+        const addressTreeInfo = {
+          addressMerkleTreePubkeyIndex: addressTreeIndex,
+          addressQueuePubkeyIndex: addressTreeIndex,
+          rootIndex: 0,
+        };
+
         const validityProof = {
           0: {
             a: new Array(32).fill(0),
@@ -375,21 +384,51 @@ async function main() {
           }
         };
 
+        const [poolVaultA] = PublicKey.findProgramAddressSync(
+          [Buffer.from("pool_vault"), poolAuthorityPda.toBuffer(), DEVNET_WSOL_MINT.toBuffer()],
+          swapProgram.programId
+        );
+        const [poolVaultB] = PublicKey.findProgramAddressSync(
+          [Buffer.from("pool_vault"), poolAuthorityPda.toBuffer(), DEVNET_USDC_MINT.toBuffer()],
+          swapProgram.programId
+        );
+
         const ix = await swapProgram.methods
           .swapExactIn(
             validityProof,
+            addressTreeInfo,
+            outputTreeIndex,
+            bn(Date.now()),
             poolMeta,
             Buffer.from(acct.data?.data || []),
             amountBuf,
             amountBuf,
             amountBuf,
+            amountBuf,
             0,
-            true
+            true,
+            null,
+            null
           )
           .accounts({
             feePayer: authority,
+            trader: authority,
+            // Only the TEE's access to Light Protocol is probed, so token accounts and
+            // payout allowances are placeholders
+            userTokenA: Keypair.generate().publicKey,
+            userTokenB: Keypair.generate().publicKey,
+            poolVaultA,
+            poolVaultB,
+            approvedAllowance: Keypair.generate().publicKey,
+            refundedAllowance: Keypair.generate().publicKey,
+            oracle: null,
+            traderSplIn: null,
+            splBackingVaultIn: null,
+            wrapAuthorityIn: null,
+            incoMintIn: null,
+            tokenProgram: null,
             incoLightningProgram: INCO_LIGHTNING_PROGRAM_ID,
-          })
+          } as any)
           .remainingAccounts(remainingAccounts)
           .instruction();
 
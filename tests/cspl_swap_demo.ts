@@ -6,6 +6,7 @@
  * 2. Mint tokens to user (ECIES encrypted)
  * 3. Read & decrypt balance BEFORE swap
  * 4. Execute swap_exact_in against deployed pool (Light Protocol + Inco Token CPI)
+ *    and settle its pending payout with an attested decryption of the outcome
  * 5. Read & decrypt balance AFTER swap
  *
  * Run: npx ts-mocha -p ./tsconfig.json -t 600000 tests/cspl_swap_demo.ts
@@ -22,6 +23,7 @@ import {
   TransactionMessage,
   VersionedTransaction,
   AddressLookupTableAccount,
+  TransactionInstruction,
 } from "@solana/web3.js";
 import { expect } from "chai";
 import nacl from "tweetnacl";
@@ -138,6 +140,40 @@ function derivePoolAddress(mintA: PublicKey, mintB: PublicKey): PublicKey {
   return deriveAddressV2(deriveAddressSeedV2(seeds), LIGHT_BATCH_ADDRESS_TREE, SWAP_PROGRAM_ID);
 }
 
+function derivePayoutAddress(beneficiary: PublicKey, nonce: number): PublicKey {
+  const nonceBytes = Buffer.alloc(8);
+  nonceBytes.writeBigUInt64LE(BigInt(nonce));
+  const seeds = [Buffer.from("payout"), beneficiary.toBuffer(), nonceBytes];
+  return deriveAddressV2(deriveAddressSeedV2(seeds), LIGHT_BATCH_ADDRESS_TREE, SWAP_PROGRAM_ID);
+}
+
+/** Approved and refunded handles of a PendingPayout, skipping over its approved legs */
+function decodePayoutOutcome(data: Buffer): { approved: bigint; refunded: bigint } {
+  const readHandle = (offset: number) => data.readBigUInt64LE(offset) + (data.readBigUInt64LE(offset + 8) << 64n);
+  const approved = readHandle(33); // beneficiary (32) + input_type (1)
+  let offset = 49;
+  const legCount = data.readUInt32LE(offset);
+  offset += 4;
+  for (let i = 0; i < legCount; i++) {
+    offset += 1; // kind
+    const seedCount = data.readUInt32LE(offset);
+    offset += 4;
+    for (let j = 0; j < seedCount; j++) offset += 4 + data.readUInt32LE(offset);
+    offset += 64; // source + destination
+    offset += 4 + data.readUInt32LE(offset); // ciphertext
+    offset += 8; // amount
+  }
+  return { approved, refunded: readHandle(offset) };
+}
+
+/** Attested plaintexts are passed on-chain as the little-endian bytes of the value */
+function plaintextBytes(plaintext: string): Buffer {
+  const buf = Buffer.alloc(16);
+  let value = BigInt(plaintext || "0");
+  for (let i = 0; i < 16; i++) { buf[i] = Number(value & 0xffn); value >>= 8n; }
+  return buf;
+}
+
 function formatValidityProof(compressedProof: any, proveByIndex: boolean = false) {
   if (proveByIndex || !compressedProof) return { 0: null };
   return {
@@ -215,6 +251,55 @@ describe("CSPL Swap Demo — Confidential Token Balance Before & After Swap", fu
     return "DECRYPT_FAILED";
   }
 
+  async function attestedDecrypt(handles: string[], retries = 5) {
+    for (let attempt = 1; ; attempt++) {
+      await new Promise((r) => setTimeout(r, 5000 + attempt * 3000));
+      try {
+        const result: any = await decrypt(handles, {
+          address: walletKeypair.publicKey,
+          signMessage: async (msg: Uint8Array) => nacl.sign.detached(msg, walletKeypair.secretKey),
+        });
+        return {
+          plaintexts: result.plaintexts as string[],
+          ed25519Instructions: result.ed25519Instructions as TransactionInstruction[],
+        };
+      } catch (error: any) {
+        if (attempt >= retries) throw error;
+        console.log(`  Retry ${attempt}/${retries} — waiting for covalidator...`);
+      }
+    }
+  }
+
+  async function buildTransaction(ixs: TransactionInstruction[]): Promise<VersionedTransaction> {
+    const { blockhash } = await connection.getLatestBlockhash();
+    const messageV0 = new TransactionMessage({
+      payerKey: authority,
+      recentBlockhash: blockhash,
+      instructions: [...computeBudgetIxs(), ...ixs],
+    }).compileToV0Message(lookupTable ? [lookupTable] : []);
+    const vTx = new VersionedTransaction(messageV0);
+    vTx.sign([walletKeypair]);
+    return vTx;
+  }
+
+  async function sendTransaction(ixs: TransactionInstruction[]): Promise<string> {
+    const vTx = await buildTransaction(ixs);
+    const sig = await connection.sendRawTransaction(vTx.serialize(), { skipPreflight: true });
+    console.log("  ⏳ Waiting for confirmation...");
+    const { value } = await connection.confirmTransaction(sig, "confirmed");
+    expect(value.err, `Transaction ${sig} failed`).to.be.null;
+    return sig;
+  }
+
+  /** Expected Inco allowance PDA, read off the seeds mismatch of a simulation with a placeholder */
+  async function resolveAllowance(ixs: TransactionInstruction[]): Promise<PublicKey> {
+    const sim = await connection.simulateTransaction(await buildTransaction(ixs), { sigVerify: false });
+    const logs = sim.value.logs || [];
+    const right = logs.indexOf("Program log: Right:");
+    expect(right, "Allowance seeds mismatch must be reported").to.be.greaterThan(-1);
+    return new PublicKey(logs[right + 1].replace("Program log: ", ""));
+  }
+
   // ─── Setup ─────────────────────────────────────────────────────────
 
   it("0. Setup — Verify pool & create user accounts", async () => {
@@ -393,11 +478,12 @@ describe("CSPL Swap Demo — Confidential Token Balance Before & After Swap", fu
     console.log("  Fee:", feeAmount.toString());
     console.log("  Amount Out:", amountOut.toString(), "(token B)");
 
-    // Encrypt all 3 amounts
+    // Encrypt the swap amounts and the invariant slack
     console.log("\n  Encrypting amounts (ECIES)...");
     const amountInCiphertext = await encryptAmount(SWAP_AMOUNT_IN);
     const amountOutCiphertext = await encryptAmount(amountOut);
     const feeAmountCiphertext = await encryptAmount(feeAmount);
+    const invariantCiphertext = await encryptAmount(0n);
     console.log("  Ciphertext sizes:", amountInCiphertext.length, amountOutCiphertext.length, feeAmountCiphertext.length, "bytes");
 
     // Fetch pool state
@@ -420,12 +506,17 @@ describe("CSPL Swap Demo — Confidential Token Balance Before & After Swap", fu
     console.log("  Pool tree:", stateTree.toBase58());
     console.log("  Pool leaf index:", leafIndex);
 
-    // Get validity proof
+    // The output is paid later from a pending payout at ["payout", trader, nonce]
+    const payoutNonce = Date.now();
+    const payoutAddress = derivePayoutAddress(authority, payoutNonce);
+    console.log("  Payout Address:", payoutAddress.toBase58());
+
+    // Get validity proof for the pool and the new payout address
     console.log("  Fetching validity proof...");
     const hashBn = bn(accountHash);
     const proofResult = await lightRpc.getValidityProofV0(
       [{ hash: hashBn, tree: stateTree, queue: stateQueue }],
-      []
+      [{ address: bn(payoutAddress.toBytes()), tree: LIGHT_BATCH_ADDRESS_TREE, queue: LIGHT_BATCH_ADDRESS_TREE }]
     );
     const rootIndex = proofResult.rootIndices?.[0] || 0;
     console.log("  Root index:", rootIndex);
@@ -435,7 +526,8 @@ describe("CSPL Swap Demo — Confidential Token Balance Before & After Swap", fu
     packedAccounts.addSystemAccountsV2(SystemAccountMetaConfig.new(SWAP_PROGRAM_ID));
     const stateTreeIndex = packedAccounts.insertOrGet(stateTree);
     const stateQueueIndex = packedAccounts.insertOrGet(stateQueue);
-    packedAccounts.insertOrGet(LIGHT_BATCH_ADDRESS_TREE);
+    const addressTreeIndex = packedAccounts.insertOrGet(LIGHT_BATCH_ADDRESS_TREE);
+    const outputTreeIndex = packedAccounts.insertOrGet(LIGHT_OUTPUT_QUEUE);
     const { remainingAccounts: rawAccounts } = packedAccounts.toAccountMetas();
     const remainingAccounts = rawAccounts.map((a: any) => ({
       pubkey: a.pubkey,
@@ -446,7 +538,7 @@ describe("CSPL Swap Demo — Confidential Token Balance Before & After Swap", fu
     const poolMeta = {
       treeInfo: {
         rootIndex,
-        proveByIndex: true,
+        proveByIndex: Boolean(proofResult.proveByIndices?.[0]),
         merkleTreePubkeyIndex: stateTreeIndex,
         queuePubkeyIndex: stateQueueIndex,
         leafIndex,
@@ -454,67 +546,138 @@ describe("CSPL Swap Demo — Confidential Token Balance Before & After Swap", fu
       address: Array.from(poolAddress.toBytes()),
       outputStateTreeIndex: stateQueueIndex,
     };
+    const addressTreeInfo = {
+      addressMerkleTreePubkeyIndex: addressTreeIndex,
+      addressQueuePubkeyIndex: addressTreeIndex,
+      rootIndex: proofResult.rootIndices?.[1] || 0,
+    };
 
-    const validityProof = formatValidityProof(proofResult.compressedProof, true);
+    // A new address always needs the ZK proof
+    const validityProof = formatValidityProof(proofResult.compressedProof);
+
+    // Build swap instruction; the wallet is both trader and fee payer
+    const buildSwapIx = (approvedAllowance: PublicKey, refundedAllowance: PublicKey) =>
+      swapProgram.methods
+        .swapExactIn(
+          validityProof,
+          addressTreeInfo,
+          outputTreeIndex,
+          bn(payoutNonce),
+          poolMeta,
+          poolData,
+          Buffer.from(amountInCiphertext),
+          Buffer.from(amountOutCiphertext),
+          Buffer.from(feeAmountCiphertext),
+          Buffer.from(invariantCiphertext),
+          INPUT_TYPE,
+          true, // a_to_b
+          null, // referrer
+          null // wrap_amount_in
+        )
+        .accounts({
+          feePayer: authority,
+          trader: authority,
+          userTokenA: userTokenAKp.publicKey,
+          userTokenB: userTokenBKp.publicKey,
+          poolVaultA: POOL_VAULT_A,
+          poolVaultB: POOL_VAULT_B,
+          approvedAllowance,
+          refundedAllowance,
+          oracle: null,
+          traderSplIn: null,
+          splBackingVaultIn: null,
+          wrapAuthorityIn: null,
+          incoMintIn: null,
+          tokenProgram: null,
+          incoLightningProgram: INCO_LIGHTNING_PROGRAM_ID,
+          incoTokenProgram: INCO_TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+        } as any)
+        .remainingAccounts(remainingAccounts)
+        .instruction();
+
+    // The payout outcome handles only exist once the swap runs, so each allowance
+    // PDA is read off the seeds mismatch a simulation with a placeholder reports
+    console.log("\n  Resolving payout allowances...");
+    const approvedAllowance = await resolveAllowance([
+      await buildSwapIx(Keypair.generate().publicKey, Keypair.generate().publicKey),
+    ]);
+    const refundedAllowance = await resolveAllowance([
+      await buildSwapIx(approvedAllowance, Keypair.generate().publicKey),
+    ]);
+    console.log("  Approved allowance:", approvedAllowance.toBase58());
+    console.log("  Refunded allowance:", refundedAllowance.toBase58());
 
-    // Build swap instruction
     console.log("\n  Building swap instruction...");
-    const ix = await swapProgram.methods
-      .swapExactIn(
-        validityProof,
-        poolMeta,
-        poolData,
-        Buffer.from(amountInCiphertext),
-        Buffer.from(amountOutCiphertext),
-        Buffer.from(feeAmountCiphertext),
-        INPUT_TYPE,
-        true // a_to_b
-      )
-      .accounts({
-        feePayer: authority,
-        poolAuthority: POOL_AUTHORITY_PDA,
-        userTokenA: userTokenAKp.publicKey,
-        userTokenB: userTokenBKp.publicKey,
-        poolVaultA: POOL_VAULT_A,
-        poolVaultB: POOL_VAULT_B,
-        incoLightningProgram: INCO_LIGHTNING_PROGRAM_ID,
-        incoTokenProgram: INCO_TOKEN_PROGRAM_ID,
-        systemProgram: SystemProgram.programId,
-      } as any)
-      .remainingAccounts(remainingAccounts)
-      .instruction();
-
-    // Build V0 transaction with lookup table
-    const allIxs = [...computeBudgetIxs(), ix];
-    const { blockhash } = await connection.getLatestBlockhash();
-
-    let swapSig: string;
-    if (lookupTable) {
-      console.log("  Using V0 VersionedTransaction with lookup table");
-      const messageV0 = new TransactionMessage({
-        payerKey: authority,
-        recentBlockhash: blockhash,
-        instructions: allIxs,
-      }).compileToV0Message([lookupTable]);
-      const vTx = new VersionedTransaction(messageV0);
-      vTx.sign([walletKeypair]);
-      swapSig = await connection.sendRawTransaction(vTx.serialize(), { skipPreflight: true });
-    } else {
-      console.log("  Using legacy transaction (no lookup table)");
-      const tx = new anchor.web3.Transaction();
-      tx.add(...allIxs);
-      tx.recentBlockhash = blockhash;
-      tx.feePayer = authority;
-      tx.sign(walletKeypair);
-      swapSig = await connection.sendRawTransaction(tx.serialize(), { skipPreflight: true });
-    }
-
-    console.log("\n  ⏳ Waiting for confirmation...");
-    await connection.confirmTransaction(swapSig, "confirmed");
-
+    const swapSig = await sendTransaction([await buildSwapIx(approvedAllowance, refundedAllowance)]);
     txHashes["swap"] = swapSig;
     console.log("  ✅ SWAP TX:", swapSig);
     console.log("  https://explorer.solana.com/tx/" + swapSig + "?cluster=devnet");
+
+    // ─── Settle the payout ───
+    console.log("\n  Settling payout (attested decrypt of the swap outcome)...");
+    let payout: any = null;
+    for (let attempt = 0; !payout && attempt < 10; attempt++) {
+      await new Promise((r) => setTimeout(r, 2000));
+      payout = await lightRpc.getCompressedAccount(bn(payoutAddress.toBytes()));
+    }
+    expect(payout, "Payout must exist").to.not.be.null;
+    const payoutData = Buffer.from(payout.data?.data || []);
+    const { approved, refunded } = decodePayoutOutcome(payoutData);
+
+    const attestation = await attestedDecrypt([approved.toString(), refunded.toString()]);
+    const swapApproved = BigInt(attestation.plaintexts[0] || "0") !== 0n;
+    console.log("  Swap approved:", swapApproved ? "✅ YES" : "❌ NO (input refunded)");
+
+    // Approved: vault B → user B. Refunded: vault A → user A. Both signed by the pool authority.
+    const legAccounts = (swapApproved
+      ? [POOL_AUTHORITY_PDA, POOL_VAULT_B, userTokenBKp.publicKey]
+      : [POOL_AUTHORITY_PDA, POOL_VAULT_A, userTokenAKp.publicKey]
+    ).map((pubkey) => ({ pubkey, isSigner: false, isWritable: true }));
+
+    const settleProof = await lightRpc.getValidityProofV0(
+      [{ hash: bn(payout.hash), tree: new PublicKey(payout.treeInfo.tree), queue: new PublicKey(payout.treeInfo.queue) }],
+      []
+    );
+    const settleAccounts = new PackedAccounts();
+    settleAccounts.addSystemAccountsV2(SystemAccountMetaConfig.new(SWAP_PROGRAM_ID));
+    const payoutTreeIndex = settleAccounts.insertOrGet(new PublicKey(payout.treeInfo.tree));
+    const payoutQueueIndex = settleAccounts.insertOrGet(new PublicKey(payout.treeInfo.queue));
+    const payoutMeta = {
+      treeInfo: {
+        rootIndex: settleProof.rootIndices?.[0] || 0,
+        proveByIndex: Boolean(settleProof.proveByIndices?.[0]),
+        merkleTreePubkeyIndex: payoutTreeIndex,
+        queuePubkeyIndex: payoutQueueIndex,
+        leafIndex: payout.leafIndex || 0,
+      },
+      address: Array.from(payoutAddress.toBytes()),
+      outputStateTreeIndex: payoutQueueIndex,
+    };
+    const settleIx = await swapProgram.methods
+      .settlePayout(
+        formatValidityProof(settleProof.compressedProof, payoutMeta.treeInfo.proveByIndex),
+        payoutMeta,
+        payoutData,
+        plaintextBytes(attestation.plaintexts[0]),
+        plaintextBytes(attestation.plaintexts[1])
+      )
+      .accounts({ feePayer: authority, tokenProgram: null } as any)
+      .remainingAccounts([
+        ...legAccounts,
+        ...settleAccounts.toAccountMetas().remainingAccounts.map((a: any) => ({
+          pubkey: a.pubkey,
+          isWritable: Boolean(a.isWritable),
+          isSigner: Boolean(a.isSigner),
+        })),
+      ])
+      .instruction();
+
+    const settleSig = await sendTransaction([...attestation.ed25519Instructions, settleIx]);
+    txHashes["settle"] = settleSig;
+    console.log("  ✅ SETTLE TX:", settleSig);
+    console.log("  https://explorer.solana.com/tx/" + settleSig + "?cluster=devnet");
+    expect(swapApproved, "Swap must be approved").to.be.true;
   });
 
   // ─── Balance AFTER ─────────────────────────────────────────────────
@@ -575,6 +738,7 @@ describe("CSPL Swap Demo — Confidential Token Balance Before & After Swap", fu
     console.log("═".repeat(70));
     console.log("\n  SWAP TX:", txHashes["swap"]);
     console.log("  Explorer: https://explorer.solana.com/tx/" + txHashes["swap"] + "?cluster=devnet");
+    console.log("  SETTLE TX:", txHashes["settle"]);
     console.log("\n  Balance Changes:");
     console.log("  ┌────────────┬──────────────────┬──────────────────┐");
     console.log("  │   Token    │   BEFORE Swap    │   AFTER Swap     │");
@@ -0,0 +1,2358 @@
+/**
+ * Light Swap PSP — Instruction Behaviour Tests
+ *
+ * Runs every program instruction against devnet on fresh Inco mints and fresh pools:
+ * 1. Guards are checked by simulation: the expected `ErrorCode` must appear in the logs
+ * 2. Plaintext state changes are checked on the decoded compressed accounts
+ * 3. Encrypted state changes are checked through the Inco handles they replace
+ * 4. Payouts, close checks and unwrap tickets are settled with Inco attested decryptions
+ *
+ * Run: npx ts-mocha -p ./tsconfig.json -t 1000000 tests/instruction_behaviour.ts
+ */
+
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import {
+  AccountMeta,
+  AddressLookupTableAccount,
+  ComputeBudgetProgram,
+  Connection,
+  Keypair,
+  PublicKey,
+  SystemProgram,
+  TransactionInstruction,
+  TransactionMessage,
+  VersionedTransaction,
+} from "@solana/web3.js";
+import { expect } from "chai";
+import nacl from "tweetnacl";
+import { encryptValue } from "@inco/solana-sdk/encryption";
+import { decrypt } from "@inco/solana-sdk/attested-decrypt";
+import { hexToBuffer } from "@inco/solana-sdk/utils";
+import {
+  createRpc,
+  bn,
+  deriveAddressSeedV2,
+  deriveAddressV2,
+  PackedAccounts,
+  SystemAccountMetaConfig,
+  featureFlags,
+  VERSION,
+  batchAddressTree,
+  type Rpc,
+} from "@lightprotocol/stateless.js";
+import * as fs from "fs";
+import * as path from "path";
+
+// Force V2 mode for Light Protocol
+(featureFlags as any).version = VERSION.V2;
+
+// ─── Constants ─────────────────────────────────────────────────────────
+
+const INCO_LIGHTNING_PROGRAM_ID = new PublicKey("5sjEbPiqgZrYwR31ahR6Uk9wf5awoX61YGg7jExQSwaj");
+const SWAP_PROGRAM_ID = new PublicKey("4b8jCufu7b4WKXdxFRQHWSks4QdskW62qF7tApSNXuZD");
+const TOKEN_PROGRAM_ID = new PublicKey("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+const NATIVE_MINT = new PublicKey("So11111111111111111111111111111111111111112");
+const LIGHT_BATCH_ADDRESS_TREE = new PublicKey(batchAddressTree);
+const LIGHT_OUTPUT_QUEUE = new PublicKey("oq1na8gojfdUhsfCpyjNt6h4JaDWtHf1yQj4koBWfto");
+const INPUT_TYPE = 0;
+
+const FEE_BPS = 30;
+const STABLE_AMP = 100;
+const UNPAUSE_DELAY_SECS = 3_600;
+const OBSERVATION_SLOTS = 8;
+const PAYOUT_LEG_SPL_TRANSFER = 2;
+
+const VAULT_A_LIQUIDITY = 1_000_000_000_000n; // 1000 token A (9 decimals)
+const VAULT_B_LIQUIDITY = 1_000_000_000_000n; // 1000 token B (9 decimals)
+const TRADER_A_BALANCE = 100_000_000_000n; // 100 token A
+const SWAP_AMOUNT_IN = 1_000_000_000n; // 1 token A
+const WRAP_DEPOSIT = 100_000_000n; // 0.1 SOL
+const WRAP_SWAP_IN = 1_000_000n; // 0.001 SOL
+const UNWRAP_AMOUNT = 100_000n; // 0.0001 SOL
+
+// ─── Helpers ───────────────────────────────────────────────────────────
+
+const u16Le = (value: number) => {
+  const buf = Buffer.alloc(2);
+  buf.writeUInt16LE(value);
+  return buf;
+};
+
+const u64Le = (value: number | bigint) => {
+  const buf = Buffer.alloc(8);
+  buf.writeBigUInt64LE(BigInt(value));
+  return buf;
+};
+
+const handleLe = (handle: bigint) => {
+  const buf = Buffer.alloc(16);
+  let h = handle;
+  for (let i = 0; i < 16; i++) { buf[i] = Number(h & 0xffn); h >>= 8n; }
+  return buf;
+};
+
+/** Attested plaintexts are passed on-chain as the little-endian bytes of the value */
+const plaintextBytes = (plaintext: string) => handleLe(BigInt(plaintext || "0"));
+
+const pda = (...seeds: Buffer[]) => PublicKey.findProgramAddressSync(seeds, SWAP_PROGRAM_ID)[0];
+
+const deriveAddress = (...seeds: Buffer[]) =>
+  deriveAddressV2(deriveAddressSeedV2(seeds), LIGHT_BATCH_ADDRESS_TREE, SWAP_PROGRAM_ID);
+
+function getAllowancePda(handle: bigint, allowedAddress: PublicKey): PublicKey {
+  return PublicKey.findProgramAddressSync(
+    [handleLe(handle), allowedAddress.toBuffer()],
+    INCO_LIGHTNING_PROGRAM_ID
+  )[0];
+}
+
+function extractHandleFromRaw(data: Buffer): bigint {
+  const amountBytes = data.slice(72, 88);
+  let handle = 0n;
+  for (let i = 15; i >= 0; i--) handle = handle * 256n + BigInt(amountBytes[i]);
+  return handle;
+}
+
+const encryptAmount = async (amount: bigint) => Buffer.from(hexToBuffer(await encryptValue(amount)));
+
+const computeBudgetIxs = () => [
+  ComputeBudgetProgram.setComputeUnitLimit({ units: 1_400_000 }),
+  ComputeBudgetProgram.setComputeUnitPrice({ microLamports: 100_000 }),
+];
+
+const sleep = (ms: number) => new Promise((r) => setTimeout(r, ms));
+
+/** Constant-product quote with a 1% haircut, so drift in the tracked reserves cannot fail it */
+function quote(amountIn: bigint, reserveIn: bigint, reserveOut: bigint, feeBps = FEE_BPS) {
+  const feeAmount = (amountIn * BigInt(feeBps)) / 10_000n;
+  const netIn = amountIn - feeAmount;
+  const amountOut = (((reserveOut * netIn) / (reserveIn + netIn)) * 99n) / 100n;
+  return { feeAmount, amountOut };
+}
+
+// ─── Account decoding ──────────────────────────────────────────────────
+
+function reader(data: Buffer) {
+  let offset = 0;
+  const take = (len: number) => data.subarray((offset += len) - len, offset);
+  const r = {
+    pubkey: () => new PublicKey(take(32)),
+    bytes32: () => Buffer.from(take(32)),
+    u8: () => take(1)[0],
+    bool: () => take(1)[0] !== 0,
+    u16: () => take(2).readUInt16LE(0),
+    u32: () => take(4).readUInt32LE(0),
+    u64: () => take(8).readBigUInt64LE(0),
+    i64: () => take(8).readBigInt64LE(0),
+    handle: () => {
+      const buf = take(16);
+      return buf.readBigUInt64LE(0) + (buf.readBigUInt64LE(8) << 64n);
+    },
+    bytes: () => Buffer.from(take(take(4).readUInt32LE(0))),
+    vec: <T>(item: () => T): T[] => Array.from({ length: take(4).readUInt32LE(0) }, item),
+  };
+  return r;
+}
+
+function decodePool(data: Buffer) {
+  const r = reader(data);
+  return {
+    authority: r.pubkey(),
+    poolAuthority: r.pubkey(),
+    mintA: r.pubkey(),
+    mintB: r.pubkey(),
+    reserveA: r.handle(),
+    reserveB: r.handle(),
+    protocolFeeA: r.handle(),
+    protocolFeeB: r.handle(),
+    feeBps: r.u16(),
+    isPaused: r.bool(),
+    lastUpdateTs: r.i64(),
+    version: r.u8(),
+    vaultA: r.pubkey(),
+    vaultB: r.pubkey(),
+    poolAuthorityBump: r.u8(),
+    poolKind: r.u8(),
+    guardian: r.pubkey(),
+    pausedAtTs: r.i64(),
+    emergencyDelaySecs: r.u32(),
+    stateNonce: r.u64(),
+    closeCheck: r.handle(),
+    closeCheckNonce: r.u64(),
+    ampInitial: r.u64(),
+    ampFuture: r.u64(),
+    ampRampStartTs: r.i64(),
+    ampRampEndTs: r.i64(),
+    weightA: r.u8(),
+    weightB: r.u8(),
+    curveScaleBits: r.u8(),
+    priceCumulativeA: r.handle(),
+    priceCumulativeB: r.handle(),
+    priceCumulativeTs: r.i64(),
+    owedA: r.handle(),
+    owedB: r.handle(),
+    skimmedA: r.handle(),
+    skimmedB: r.handle(),
+    flashBorrower: r.pubkey(),
+    flashDebtA: r.handle(),
+    flashDebtB: r.handle(),
+    referralShareBps: r.u16(),
+    protocolFeeShareBps: r.u16(),
+    dynamicFeeEnabled: r.bool(),
+    minFeeBps: r.u16(),
+    maxFeeBps: r.u16(),
+    volatilityStepBps: r.u16(),
+    volatilityHalfLifeSecs: r.u32(),
+    volatilityAccumulator: r.u32(),
+    volatilityUpdatedTs: r.i64(),
+    auditor: r.pubkey(),
+    nextAttestationId: r.u64(),
+    breakerEnabled: r.bool(),
+    breakerAutoPause: r.bool(),
+    breakerWindowSecs: r.u32(),
+    breakerWindowStartTs: r.i64(),
+    maxTradeOutA: r.handle(),
+    maxTradeOutB: r.handle(),
+    windowOutflowCapA: r.handle(),
+    windowOutflowCapB: r.handle(),
+    windowOutflowA: r.handle(),
+    windowOutflowB: r.handle(),
+    breakerTripped: r.handle(),
+    oracleFeed: r.pubkey(),
+    oracleMaxStalenessSecs: r.u32(),
+    oracleMaxDeviationBps: r.u16(),
+    batchMode: r.bool(),
+    batchWindowSecs: r.u32(),
+    nextBatchId: r.u64(),
+  };
+}
+
+function decodePayout(data: Buffer) {
+  const r = reader(data);
+  const leg = () => ({
+    kind: r.u8(),
+    authoritySeeds: r.vec(() => r.bytes()),
+    source: r.pubkey(),
+    destination: r.pubkey(),
+    ciphertext: r.bytes(),
+    amount: r.u64(),
+  });
+  return {
+    beneficiary: r.pubkey(),
+    inputType: r.u8(),
+    approved: r.handle(),
+    onApproved: r.vec(leg),
+    refunded: r.handle(),
+    onRefunded: r.vec(leg),
+    createdAt: r.i64(),
+  };
+}
+
+function decodeDcaOrder(data: Buffer) {
+  const r = reader(data);
+  return {
+    owner: r.pubkey(),
+    poolAddress: r.bytes32(),
+    mintA: r.pubkey(),
+    mintB: r.pubkey(),
+    aToB: r.bool(),
+    remainingBudget: r.handle(),
+    amountPerInterval: r.handle(),
+    minAmountOut: r.handle(),
+    amountPerIntervalCiphertext: r.bytes(),
+    minAmountOutCiphertext: r.bytes(),
+    inputType: r.u8(),
+    intervalSecs: r.u32(),
+    executionsRemaining: r.u32(),
+    lastExecutionTs: r.i64(),
+  };
+}
+
+function decodeSwapBatch(data: Buffer) {
+  const r = reader(data);
+  return {
+    poolAddress: r.bytes32(),
+    mintA: r.pubkey(),
+    mintB: r.pubkey(),
+    vaultA: r.pubkey(),
+    vaultB: r.pubkey(),
+    poolAuthority: r.pubkey(),
+    poolAuthorityBump: r.u8(),
+    batchId: r.u64(),
+    windowEndTs: r.i64(),
+    settled: r.bool(),
+    totalInA: r.handle(),
+    totalInB: r.handle(),
+    intents: r.vec(() => ({
+      trader: r.pubkey(),
+      traderTokenIn: r.pubkey(),
+      traderTokenOut: r.pubkey(),
+      aToB: r.bool(),
+      amountIn: r.handle(),
+      minAmountOut: r.handle(),
+      amountOut: r.handle(),
+      refund: r.handle(),
+    })),
+  };
+}
+
+function decodeReferrerFees(data: Buffer) {
+  const r = reader(data);
+  return {
+    referrer: r.pubkey(),
+    poolAddress: r.bytes32(),
+    mintA: r.pubkey(),
+    mintB: r.pubkey(),
+    vaultA: r.pubkey(),
+    vaultB: r.pubkey(),
+    poolAuthority: r.pubkey(),
+    poolAuthorityBump: r.u8(),
+    feeA: r.handle(),
+    feeB: r.handle(),
+  };
+}
+
+function decodeObservations(data: Buffer) {
+  const r = reader(data);
+  return {
+    poolAddress: r.bytes32(),
+    nextIndex: r.u8(),
+    observations: Array.from({ length: OBSERVATION_SLOTS }, () => ({
+      timestamp: r.i64(),
+      priceCumulativeA: r.handle(),
+      priceCumulativeB: r.handle(),
+    })),
+  };
+}
+
+function decodeReserveAttestation(data: Buffer) {
+  const r = reader(data);
+  return {
+    poolAddress: r.bytes32(),
+    auditor: r.pubkey(),
+    attestationId: r.u64(),
+    timestamp: r.i64(),
+    vaultACovers: r.handle(),
+    vaultBCovers: r.handle(),
+  };
+}
+
+function decodeUnwrapTicket(data: Buffer) {
+  const r = reader(data);
+  return {
+    owner: r.pubkey(),
+    splMint: r.pubkey(),
+    incoMint: r.pubkey(),
+    recipient: r.pubkey(),
+    amount: r.u64(),
+    burnCovers: r.handle(),
+    createdAt: r.i64(),
+  };
+}
+
+// ─── Pools ─────────────────────────────────────────────────────────────
+
+interface PoolRef {
+  mintA: PublicKey;
+  mintB: PublicKey;
+  feeBps: number;
+  poolAuthority: PublicKey;
+  vaultA: PublicKey;
+  vaultB: PublicKey;
+  address: PublicKey;
+  indexAddress: PublicKey;
+}
+
+function poolRef(mintA: PublicKey, mintB: PublicKey, feeBps: number): PoolRef {
+  const poolAuthority = pda(Buffer.from("pool_authority"), mintA.toBuffer(), mintB.toBuffer());
+  return {
+    mintA,
+    mintB,
+    feeBps,
+    poolAuthority,
+    vaultA: pda(Buffer.from("pool_vault"), poolAuthority.toBuffer(), mintA.toBuffer()),
+    vaultB: pda(Buffer.from("pool_vault"), poolAuthority.toBuffer(), mintB.toBuffer()),
+    address: deriveAddress(Buffer.from("pool"), mintA.toBuffer(), mintB.toBuffer()),
+    indexAddress: deriveAddress(
+      Buffer.from("pool_index"),
+      mintA.toBuffer(),
+      mintB.toBuffer(),
+      u16Le(feeBps)
+    ),
+  };
+}
+
+interface Compressed {
+  address: PublicKey;
+  hash: any;
+  tree: PublicKey;
+  queue: PublicKey;
+  leafIndex: number;
+  data: Buffer;
+}
+
+// ─── Test ──────────────────────────────────────────────────────────────
+
+describe("light_swap_psp — instruction behaviour", function () {
+  this.timeout(1_000_000);
+
+  const HELIUS_API_KEY = process.env.HELIUS_DEVNET_API_KEY || "2d8978c6-7067-459f-ae97-7ea035f1a0cb";
+  const rpcUrl = `https://devnet.helius-rpc.com/?api-key=${HELIUS_API_KEY}`;
+  process.env.ANCHOR_PROVIDER_URL = rpcUrl;
+  process.env.ANCHOR_WALLET = process.env.ANCHOR_WALLET || `${process.env.HOME}/.config/solana/id.json`;
+
+  const connection = new Connection(rpcUrl, "confirmed");
+  const provider = new anchor.AnchorProvider(connection, anchor.AnchorProvider.env().wallet, {
+    commitment: "confirmed",
+    preflightCommitment: "confirmed",
+  });
+  anchor.setProvider(provider);
+
+  const incoTokenIdl = JSON.parse(fs.readFileSync(path.join(process.cwd(), "idl/inco_token.json"), "utf8"));
+  const swapIdl = JSON.parse(fs.readFileSync(path.join(process.cwd(), "idl/light_swap_psp.json"), "utf8"));
+  const incoProgram = new Program(incoTokenIdl, provider);
+  const swapProgram = new Program(swapIdl, provider);
+
+  const authority = provider.wallet.publicKey;
+  const walletKeypair = (provider.wallet as any).payer as Keypair;
+  let lightRpc: Rpc;
+  let lookupTable: AddressLookupTableAccount | null = null;
+
+  // Actors: the wallet is the pool authority and relays every transaction
+  const trader = Keypair.generate();
+  const guardian = Keypair.generate();
+  const auditor = Keypair.generate();
+  const referrer = Keypair.generate();
+
+  let mintA: PublicKey;
+  let mintB: PublicKey;
+  let pool: PoolRef;
+  let stablePool: PoolRef;
+
+  // Inco token accounts
+  let traderA: PublicKey;
+  let traderB: PublicKey;
+  let authorityA: PublicKey;
+  let authorityB: PublicKey;
+  let referrerA: PublicKey;
+
+  // Approximate plaintext reserves of `pool`, for quoting swaps
+  let reserveA = 0n;
+  let reserveB = 0n;
+
+  let nonce = Date.now();
+  const nextNonce = () => nonce++;
+
+  // ─── Transactions ────────────────────────────────────────────────────
+
+  async function buildTx(ixs: TransactionInstruction[], signers: Keypair[]) {
+    const { blockhash } = await connection.getLatestBlockhash();
+    const message = new TransactionMessage({
+      payerKey: authority,
+      recentBlockhash: blockhash,
+      instructions: [...computeBudgetIxs(), ...ixs],
+    }).compileToV0Message(lookupTable ? [lookupTable] : []);
+    const tx = new VersionedTransaction(message);
+    tx.sign([walletKeypair, ...signers.filter((s) => !s.publicKey.equals(authority))]);
+    return tx;
+  }
+
+  async function send(ixs: TransactionInstruction[], signers: Keypair[] = []): Promise<string> {
+    const tx = await buildTx(ixs, signers);
+    const sig = await connection.sendRawTransaction(tx.serialize(), { skipPreflight: true });
+    const { value } = await connection.confirmTransaction(sig, "confirmed");
+    if (value.err) {
+      const failed = await connection.getTransaction(sig, {
+        commitment: "confirmed",
+        maxSupportedTransactionVersion: 0,
+      });
+      throw new Error(`${sig} failed: ${JSON.stringify(value.err)}\n${failed?.meta?.logMessages?.join("\n")}`);
+    }
+    // Wait for Photon to index the new compressed state
+    const { context } = await connection.getSignatureStatus(sig);
+    while ((await lightRpc.getIndexerSlot()) < context.slot) await sleep(1000);
+    return sig;
+  }
+
+  async function simulate(ixs: TransactionInstruction[], signers: Keypair[] = []): Promise<string[]> {
+    const tx = await buildTx(ixs, signers);
+    const sim = await connection.simulateTransaction(tx, { sigVerify: false });
+    return sim.value.logs || [];
+  }
+
+  async function expectError(ix: Promise<TransactionInstruction>, code: string, signers: Keypair[] = []) {
+    const logs = await simulate([await ix], signers);
+    expect(logs.join("\n"), `expected ${code}`).to.include(`Error Code: ${code}.`);
+  }
+
+  /**
+   * Resolve the Inco allowance PDAs an instruction grants on handles it computes itself.
+   * The handles are only known once the instruction runs, so each PDA is read off the
+   * seeds mismatch a simulation with a placeholder reports.
+   */
+  async function resolveAllowances(
+    build: (allowances: PublicKey[]) => Promise<TransactionInstruction>,
+    count: number,
+    signers: Keypair[] = []
+  ): Promise<PublicKey[]> {
+    const allowances = Array.from({ length: count }, () => Keypair.generate().publicKey);
+    for (let i = 0; i < count; i++) {
+      const logs = await simulate([await build(allowances)], signers);
+      const right = logs.indexOf("Program log: Right:");
+      expect(right, `allowance ${i} not reported`).to.be.greaterThan(-1);
+      allowances[i] = new PublicKey(logs[right + 1].replace("Program log: ", ""));
+    }
+    return allowances;
+  }
+
+  // ─── Compressed accounts ─────────────────────────────────────────────
+
+  async function fetchCompressed(address: PublicKey): Promise<Compressed | null> {
+    const acct: any = await lightRpc.getCompressedAccount(bn(address.toBytes()));
+    if (!acct) return null;
+    return {
+      address,
+      hash: acct.hash,
+      tree: new PublicKey(acct.treeInfo.tree),
+      queue: new PublicKey(acct.treeInfo.queue),
+      leafIndex: acct.leafIndex,
+      data: Buffer.from(acct.data?.data || []),
+    };
+  }
+
+  async function mustFetch(address: PublicKey): Promise<Compressed> {
+    const account = await fetchCompressed(address);
+    expect(account, `compressed account ${address.toBase58()}`).to.not.be.null;
+    return account!;
+  }
+
+  async function fetchPool(ref: PoolRef) {
+    const account = await mustFetch(ref.address);
+    return { account, state: decodePool(account.data) };
+  }
+
+  async function handleOf(account: PublicKey): Promise<bigint> {
+    const info = await connection.getAccountInfo(account, "confirmed");
+    return info ? extractHandleFromRaw(info.data as Buffer) : 0n;
+  }
+
+  async function splAmount(account: PublicKey): Promise<bigint> {
+    return BigInt((await connection.getTokenAccountBalance(account, "confirmed")).value.amount);
+  }
+
+  /**
+   * Validity proof, packed metas and remaining accounts for a Light CPI over `inputs`
+   * that creates `newAddresses`; `prefix` accounts precede the Light accounts
+   */
+  async function lightArgs(inputs: Compressed[], newAddresses: PublicKey[] = [], prefix: AccountMeta[] = []) {
+    const proofResult: any = await lightRpc.getValidityProofV0(
+      inputs.map((input) => ({ hash: bn(input.hash), tree: input.tree, queue: input.queue })),
+      newAddresses.map((address) => ({
+        address: bn(address.toBytes()),
+        tree: LIGHT_BATCH_ADDRESS_TREE,
+        queue: LIGHT_BATCH_ADDRESS_TREE,
+      }))
+    );
+
+    const packed = new PackedAccounts();
+    packed.addSystemAccountsV2(SystemAccountMetaConfig.new(SWAP_PROGRAM_ID));
+    const outputTreeIndex = packed.insertOrGet(LIGHT_OUTPUT_QUEUE);
+    const addressTreeIndex = packed.insertOrGet(LIGHT_BATCH_ADDRESS_TREE);
+    const metas = inputs.map((input, i) => {
+      const merkleTreePubkeyIndex = packed.insertOrGet(input.tree);
+      const queuePubkeyIndex = packed.insertOrGet(input.queue);
+      return {
+        treeInfo: {
+          rootIndex: proofResult.rootIndices[i] ?? 0,
+          proveByIndex: Boolean(proofResult.proveByIndices?.[i]),
+          merkleTreePubkeyIndex,
+          queuePubkeyIndex,
+          leafIndex: input.leafIndex,
+        },
+        address: Array.from(input.address.toBytes()),
+        outputStateTreeIndex: queuePubkeyIndex,
+      };
+    });
+
+    const { remainingAccounts } = packed.toAccountMetas();
+    return {
+      proof: proofResult.compressedProof
+        ? {
+            0: {
+              a: Array.from(proofResult.compressedProof.a),
+              b: Array.from(proofResult.compressedProof.b),
+              c: Array.from(proofResult.compressedProof.c),
+            },
+          }
+        : { 0: null },
+      metas,
+      addressTreeInfo: {
+        addressMerkleTreePubkeyIndex: addressTreeIndex,
+        addressQueuePubkeyIndex: addressTreeIndex,
+        rootIndex: proofResult.rootIndices[inputs.length] ?? 0,
+      },
+      outputTreeIndex,
+      remainingAccounts: [
+        ...prefix,
+        ...remainingAccounts.map((a: any) => ({
+          pubkey: a.pubkey,
+          isWritable: Boolean(a.isWritable),
+          isSigner: Boolean(a.isSigner),
+        })),
+      ],
+    };
+  }
+
+  /** Instruction of the `(proof, pool_meta, pool_data, ...args)` shape shared by pool settings */
+  async function poolIx(
+    ref: PoolRef,
+    method: string,
+    args: any[],
+    accounts: Record<string, PublicKey | null> = {}
+  ): Promise<TransactionInstruction> {
+    const { account } = await fetchPool(ref);
+    const light = await lightArgs([account]);
+    return (swapProgram.methods as any)
+      [method](light.proof, light.metas[0], account.data, ...args)
+      .accounts({ feePayer: authority, authority, ...accounts })
+      .remainingAccounts(light.remainingAccounts)
+      .instruction();
+  }
+
+  // ─── Inco tokens ─────────────────────────────────────────────────────
+
+  async function createIncoMint(mint: Keypair, mintAuthority: PublicKey): Promise<PublicKey> {
+    await incoProgram.methods
+      .initializeMint(9, mintAuthority, null)
+      .accounts({ mint: mint.publicKey, payer: authority } as any)
+      .preInstructions(computeBudgetIxs())
+      .signers([mint])
+      .rpc();
+    return mint.publicKey;
+  }
+
+  async function createIncoAccount(mint: PublicKey, owner: PublicKey): Promise<PublicKey> {
+    const account = Keypair.generate();
+    await incoProgram.methods
+      .initializeAccount()
+      .accounts({ account: account.publicKey, mint, owner, payer: authority } as any)
+      .preInstructions(computeBudgetIxs())
+      .signers([account])
+      .rpc();
+    return account.publicKey;
+  }
+
+  async function mintTo(mint: PublicKey, account: PublicKey, amount: bigint) {
+    await incoProgram.methods
+      .mintTo(await encryptAmount(amount), INPUT_TYPE)
+      .accounts({ mint, account, mintAuthority: authority } as any)
+      .preInstructions(computeBudgetIxs())
+      .rpc();
+  }
+
+  /** Wrapped SOL token account (InitializeAccount3) owned by `owner` */
+  async function createWsolAccount(owner: PublicKey): Promise<PublicKey> {
+    const account = Keypair.generate();
+    const lamports = await connection.getMinimumBalanceForRentExemption(165);
+    await send(
+      [
+        SystemProgram.createAccount({
+          fromPubkey: authority,
+          newAccountPubkey: account.publicKey,
+          lamports,
+          space: 165,
+          programId: TOKEN_PROGRAM_ID,
+        }),
+        new TransactionInstruction({
+          programId: TOKEN_PROGRAM_ID,
+          keys: [
+            { pubkey: account.publicKey, isSigner: false, isWritable: true },
+            { pubkey: NATIVE_MINT, isSigner: false, isWritable: false },
+          ],
+          data: Buffer.concat([Buffer.from([18]), owner.toBuffer()]),
+        }),
+      ],
+      [account]
+    );
+    return account.publicKey;
+  }
+
+  async function attestedDecrypt(handles: bigint[], owner: Keypair, retries = 5) {
+    for (let attempt = 1; ; attempt++) {
+      await sleep(5000 + attempt * 3000);
+      try {
+        const result: any = await decrypt(handles.map((h) => h.toString()), {
+          address: owner.publicKey,
+          signMessage: async (msg: Uint8Array) => nacl.sign.detached(msg, owner.secretKey),
+        });
+        return {
+          plaintexts: result.plaintexts as string[],
+          ed25519Instructions: result.ed25519Instructions as TransactionInstruction[],
+        };
+      } catch (error: any) {
+        if (attempt >= retries) throw error;
+        console.log(`  Retry ${attempt}/${retries} — waiting for covalidator...`);
+      }
+    }
+  }
+
+  const isTrue = (plaintext: string) => BigInt(plaintext || "0") !== 0n;
+
+  // ─── Payouts ─────────────────────────────────────────────────────────
+
+  const payoutAddress = (beneficiary: PublicKey, payoutNonce: number) =>
+    deriveAddress(Buffer.from("payout"), beneficiary.toBuffer(), u64Le(payoutNonce));
+
+  /** Settle a payout from its beneficiary's attested outcome; returns whether it was approved */
+  async function settlePayout(beneficiary: Keypair, address: PublicKey): Promise<boolean> {
+    const payout = await mustFetch(address);
+    const state = decodePayout(payout.data);
+    expect(state.beneficiary.toBase58()).to.equal(beneficiary.publicKey.toBase58());
+
+    const refundable = state.onRefunded.length > 0;
+    const attestation = await attestedDecrypt(
+      refundable ? [state.approved, state.refunded] : [state.approved],
+      beneficiary
+    );
+    const approved = isTrue(attestation.plaintexts[0]);
+    const legs = approved
+      ? state.onApproved
+      : refundable && isTrue(attestation.plaintexts[1])
+        ? state.onRefunded
+        : [];
+
+    const legAccounts = legs.flatMap((leg) => [
+      {
+        pubkey: PublicKey.createProgramAddressSync(leg.authoritySeeds, SWAP_PROGRAM_ID),
+        isSigner: false,
+        isWritable: true,
+      },
+      { pubkey: leg.source, isSigner: false, isWritable: true },
+      { pubkey: leg.destination, isSigner: false, isWritable: true },
+    ]);
+    const light = await lightArgs([payout], [], legAccounts);
+    const ix = await swapProgram.methods
+      .settlePayout(
+        light.proof as any,
+        light.metas[0],
+        payout.data,
+        plaintextBytes(attestation.plaintexts[0]),
+        refundable ? plaintextBytes(attestation.plaintexts[1]) : null
+      )
+      .accounts({
+        feePayer: authority,
+        tokenProgram: legs.some((leg) => leg.kind === PAYOUT_LEG_SPL_TRANSFER) ? TOKEN_PROGRAM_ID : null,
+      } as any)
+      .remainingAccounts(light.remainingAccounts)
+      .instruction();
+    await send([...attestation.ed25519Instructions, ix]);
+
+    expect(await fetchCompressed(address), "payout closed").to.be.null;
+    return approved;
+  }
+
+  // ─── Swaps ───────────────────────────────────────────────────────────
+
+  interface SwapParams {
+    ref: PoolRef;
+    aToB: boolean;
+    amountIn: bigint;
+    amountOut: bigint;
+    feeAmount: bigint;
+    userTokenA: PublicKey;
+    userTokenB: PublicKey;
+    payoutNonce: number;
+    referrerFees?: Compressed;
+    wrap?: { traderSpl: PublicKey; backingVault: PublicKey; wrapAuthority: PublicKey; incoMint: PublicKey };
+  }
+
+  async function swapIx(params: SwapParams, allowances: PublicKey[]): Promise<TransactionInstruction> {
+    const { account } = await fetchPool(params.ref);
+    const inputs = params.referrerFees ? [account, params.referrerFees] : [account];
+    const light = await lightArgs(inputs, [payoutAddress(trader.publicKey, params.payoutNonce)]);
+    return swapProgram.methods
+      .swapExactIn(
+        light.proof as any,
+        light.addressTreeInfo,
+        light.outputTreeIndex,
+        bn(params.payoutNonce),
+        light.metas[0],
+        account.data,
+        await encryptAmount(params.amountIn),
+        await encryptAmount(params.amountOut),
+        await encryptAmount(params.feeAmount),
+        await encryptAmount(0n),
+        INPUT_TYPE,
+        params.aToB,
+        params.referrerFees ? { meta: light.metas[1], data: params.referrerFees.data } : null,
+        params.wrap ? bn(params.amountIn.toString()) : null
+      )
+      .accounts({
+        feePayer: authority,
+        trader: trader.publicKey,
+        userTokenA: params.userTokenA,
+        userTokenB: params.userTokenB,
+        poolVaultA: params.ref.vaultA,
+        poolVaultB: params.ref.vaultB,
+        approvedAllowance: allowances[0],
+        refundedAllowance: allowances[1],
+        oracle: null,
+        traderSplIn: params.wrap?.traderSpl ?? null,
+        splBackingVaultIn: params.wrap?.backingVault ?? null,
+        wrapAuthorityIn: params.wrap?.wrapAuthority ?? null,
+        incoMintIn: params.wrap?.incoMint ?? null,
+        tokenProgram: params.wrap ? TOKEN_PROGRAM_ID : null,
+      } as any)
+      .remainingAccounts(light.remainingAccounts)
+      .instruction();
+  }
+
+  /** Run a swap relayed by the wallet and signed by the trader; returns its payout address */
+  async function swap(params: SwapParams): Promise<PublicKey> {
+    const allowances = await resolveAllowances((a) => swapIx(params, a), 2, [trader]);
+    await send([await swapIx(params, allowances)], [trader]);
+    return payoutAddress(trader.publicKey, params.payoutNonce);
+  }
+
+  // ─── Setup ───────────────────────────────────────────────────────────
+
+  before(async () => {
+    lightRpc = createRpc(rpcUrl, rpcUrl);
+
+    const configPath = path.join(process.cwd(), "devnet-config.json");
+    if (fs.existsSync(configPath)) {
+      const config = JSON.parse(fs.readFileSync(configPath, "utf8"));
+      if (config.lookupTable) {
+        lookupTable = (await connection.getAddressLookupTable(new PublicKey(config.lookupTable))).value;
+      }
+    }
+
+    mintA = await createIncoMint(Keypair.generate(), authority);
+    mintB = await createIncoMint(Keypair.generate(), authority);
+    pool = poolRef(mintA, mintB, FEE_BPS);
+    stablePool = poolRef(mintB, mintA, FEE_BPS);
+
+    traderA = await createIncoAccount(mintA, trader.publicKey);
+    traderB = await createIncoAccount(mintB, trader.publicKey);
+    authorityA = await createIncoAccount(mintA, authority);
+    authorityB = await createIncoAccount(mintB, authority);
+    referrerA = await createIncoAccount(mintA, referrer.publicKey);
+    await mintTo(mintA, traderA, TRADER_A_BALANCE);
+
+    console.log("  Mint A:", mintA.toBase58());
+    console.log("  Mint B:", mintB.toBase58());
+    console.log("  Trader:", trader.publicKey.toBase58());
+  });
+
+  // ─── initialize_pool / migrate_pool ──────────────────────────────────
+
+  async function initializePoolIx(
+    ref: PoolRef,
+    kind: object,
+    amp: number,
+    overrides: Record<string, PublicKey> = {}
+  ): Promise<TransactionInstruction> {
+    const light = await lightArgs([], [ref.address, ref.indexAddress]);
+    return swapProgram.methods
+      .initializePool(
+        light.proof as any,
+        light.addressTreeInfo,
+        light.outputTreeIndex,
+        ref.mintA,
+        ref.mintB,
+        ref.feeBps,
+        kind as any,
+        bn(amp),
+        0,
+        0,
+        0
+      )
+      .accounts({
+        feePayer: authority,
+        authority,
+        poolAuthority: ref.poolAuthority,
+        mintA: ref.mintA,
+        mintB: ref.mintB,
+        poolVaultA: ref.vaultA,
+        poolVaultB: ref.vaultB,
+        ...overrides,
+      } as any)
+      .remainingAccounts(light.remainingAccounts)
+      .instruction();
+  }
+
+  describe("initialize_pool", () => {
+    it("rejects pool vaults other than the derived PDAs", async () => {
+      await expectError(
+        initializePoolIx(pool, { constantProduct: {} }, 0, { poolVaultA: Keypair.generate().publicKey }),
+        "InvalidPoolVault"
+      );
+    });
+
+    it("rejects a stable pool with an amplification out of range", async () => {
+      await expectError(initializePoolIx(stablePool, { stableSwap: {} }, 0), "InvalidAmp");
+    });
+
+    it("creates the pool, its index entry and both vaults", async () => {
+      await send([await initializePoolIx(pool, { constantProduct: {} }, 0)]);
+
+      const { state } = await fetchPool(pool);
+      expect(state.authority.toBase58()).to.equal(authority.toBase58());
+      expect(state.poolAuthority.toBase58()).to.equal(pool.poolAuthority.toBase58());
+      expect(state.mintA.toBase58()).to.equal(mintA.toBase58());
+      expect(state.mintB.toBase58()).to.equal(mintB.toBase58());
+      expect(state.vaultA.toBase58()).to.equal(pool.vaultA.toBase58());
+      expect(state.vaultB.toBase58()).to.equal(pool.vaultB.toBase58());
+      expect(state.feeBps).to.equal(FEE_BPS);
+      expect(state.poolKind).to.equal(0);
+      expect(state.isPaused).to.be.false;
+      expect(state.stateNonce).to.equal(0n);
+
+      await mustFetch(pool.indexAddress);
+      for (const [vault, mint] of [[pool.vaultA, mintA], [pool.vaultB, mintB]]) {
+        const account: any = await incoProgram.account.incoAccount.fetch(vault);
+        expect(account.owner.toBase58()).to.equal(pool.poolAuthority.toBase58());
+        expect(account.mint.toBase58()).to.equal(mint.toBase58());
+      }
+    });
+
+    it("creates a stable pool over the same mints in reverse order", async () => {
+      await send([await initializePoolIx(stablePool, { stableSwap: {} }, STABLE_AMP)]);
+
+      const { state } = await fetchPool(stablePool);
+      expect(state.poolKind).to.equal(1);
+      expect(state.ampInitial).to.equal(BigInt(STABLE_AMP));
+      expect(state.ampFuture).to.equal(BigInt(STABLE_AMP));
+    });
+  });
+
+  describe("migrate_pool", () => {
+    it("rejects a pool already on the current layout", async () => {
+      const { account } = await fetchPool(pool);
+      const light = await lightArgs([account], [pool.indexAddress]);
+      const ix = swapProgram.methods
+        .migratePool(light.proof as any, light.metas[0], account.data, light.addressTreeInfo)
+        .accounts({ feePayer: authority, authority, poolVaultA: pool.vaultA, poolVaultB: pool.vaultB } as any)
+        .remainingAccounts(light.remainingAccounts)
+        .instruction();
+      await expectError(ix, "PoolAlreadyMigrated");
+    });
+  });
+
+  // ─── Liquidity ───────────────────────────────────────────────────────
+
+  async function liquidityIx(method: string, amountA: bigint, amountB: bigint, signer: PublicKey) {
+    return poolIx(
+      pool,
+      method,
+      [await encryptAmount(amountA), await encryptAmount(amountB), INPUT_TYPE],
+      { authority: signer, oracle: null }
+    );
+  }
+
+  describe("liquidity", () => {
+    before(async () => {
+      await mintTo(mintA, pool.vaultA, VAULT_A_LIQUIDITY);
+      await mintTo(mintB, pool.vaultB, VAULT_B_LIQUIDITY);
+    });
+
+    it("add_liquidity rejects a caller other than the pool authority", async () => {
+      await expectError(
+        liquidityIx("addLiquidity", VAULT_A_LIQUIDITY, VAULT_B_LIQUIDITY, trader.publicKey),
+        "Unauthorized",
+        [trader]
+      );
+    });
+
+    it("add_liquidity credits both reserves and bumps the state nonce", async () => {
+      const before = (await fetchPool(pool)).state;
+      await send([await liquidityIx("addLiquidity", VAULT_A_LIQUIDITY, VAULT_B_LIQUIDITY, authority)]);
+      const after = (await fetchPool(pool)).state;
+
+      expect(after.reserveA).to.not.equal(before.reserveA);
+      expect(after.reserveB).to.not.equal(before.reserveB);
+      expect(after.stateNonce).to.equal(before.stateNonce + 1n);
+      reserveA = VAULT_A_LIQUIDITY;
+      reserveB = VAULT_B_LIQUIDITY;
+    });
+
+    it("remove_liquidity debits both reserves", async () => {
+      const before = (await fetchPool(pool)).state;
+      await send([await liquidityIx("removeLiquidity", 1_000_000n, 1_000_000n, authority)]);
+      const after = (await fetchPool(pool)).state;
+
+      expect(after.reserveA).to.not.equal(before.reserveA);
+      expect(after.reserveB).to.not.equal(before.reserveB);
+      expect(after.stateNonce).to.equal(before.stateNonce + 1n);
+    });
+
+    it("add_liquidity_single swaps part of a one-sided deposit", async () => {
+      const amount = 2_000_000n;
+      const { feeAmount, amountOut } = quote(amount / 2n, reserveA, reserveB);
+      const before = (await fetchPool(pool)).state;
+      const ix = await poolIx(
+        pool,
+        "addLiquiditySingle",
+        [
+          await encryptAmount(amount),
+          await encryptAmount(amount / 2n),
+          await encryptAmount(amountOut),
+          await encryptAmount(feeAmount),
+          await encryptAmount(0n),
+          INPUT_TYPE,
+          true,
+        ],
+        { oracle: null }
+      );
+      await send([ix]);
+      const after = (await fetchPool(pool)).state;
+
+      expect(after.reserveA).to.not.equal(before.reserveA);
+      expect(after.reserveB).to.not.equal(before.reserveB);
+      expect(after.stateNonce).to.equal(before.stateNonce + 1n);
+    });
+
+    it("sync_reserves re-bases the reserves on the vault balances", async () => {
+      const before = (await fetchPool(pool)).state;
+      await send([await poolIx(pool, "syncReserves", [], { poolVaultA: pool.vaultA, poolVaultB: pool.vaultB })]);
+      const after = (await fetchPool(pool)).state;
+
+      expect(after.reserveA).to.not.equal(before.reserveA);
+      expect(after.reserveB).to.not.equal(before.reserveB);
+      expect(after.stateNonce).to.equal(before.stateNonce + 1n);
+      reserveA = VAULT_A_LIQUIDITY;
+      reserveB = VAULT_B_LIQUIDITY;
+    });
+
+    it("skim leaves the reserves untouched and refuses more than the vault excess", async () => {
+      const payoutNonce = nextNonce();
+      const build = async (allowances: PublicKey[]) => {
+        const { account } = await fetchPool(pool);
+        const light = await lightArgs([account], [payoutAddress(authority, payoutNonce)]);
+        return swapProgram.methods
+          .skim(
+            light.proof as any,
+            light.metas[0],
+            account.data,
+            await encryptAmount(1_000_000n),
+            INPUT_TYPE,
+            true,
+            light.addressTreeInfo,
+            light.outputTreeIndex,
+            bn(payoutNonce)
+          )
+          .accounts({
+            feePayer: authority,
+            authority,
+            poolVault: pool.vaultA,
+            recipientToken: authorityA,
+            approvedAllowance: allowances[0],
+          } as any)
+          .remainingAccounts(light.remainingAccounts)
+          .instruction();
+      };
+      const before = (await fetchPool(pool)).state;
+      await send([await build(await resolveAllowances(build, 1))]);
+      const after = (await fetchPool(pool)).state;
+
+      expect(after.reserveA).to.equal(before.reserveA);
+      expect(after.reserveB).to.equal(before.reserveB);
+      expect(after.skimmedA).to.not.equal(before.skimmedA);
+      expect(after.skimmedB).to.equal(before.skimmedB);
+
+      // Reserves match the vault after sync_reserves, so there is no excess to pay out
+      const recipientBefore = await handleOf(authorityA);
+      expect(await settlePayout(walletKeypair, payoutAddress(authority, payoutNonce))).to.be.false;
+      expect(await handleOf(authorityA)).to.equal(recipientBefore);
+    });
+  });
+
+  // ─── Swaps ───────────────────────────────────────────────────────────
+
+  describe("swap_exact_in / settle_payout", () => {
+    it("rejects pool vaults that do not belong to the pool", async () => {
+      const params: SwapParams = {
+        ref: { ...pool, vaultA: stablePool.vaultB, vaultB: stablePool.vaultA },
+        aToB: true,
+        amountIn: SWAP_AMOUNT_IN,
+        amountOut: 0n,
+        feeAmount: 0n,
+        userTokenA: traderA,
+        userTokenB: traderB,
+        payoutNonce: nextNonce(),
+      };
+      const placeholder = [Keypair.generate().publicKey, Keypair.generate().publicKey];
+      await expectError(swapIx(params, placeholder), "InvalidPoolVault", [trader]);
+    });
+
+    it("takes the input from the trader while a relayer pays the fees", async () => {
+      const { feeAmount, amountOut } = quote(SWAP_AMOUNT_IN, reserveA, reserveB);
+      const traderLamports = await connection.getBalance(trader.publicKey);
+      const traderABefore = await handleOf(traderA);
+      const before = (await fetchPool(pool)).state;
+
+      const payout = await swap({
+        ref: pool,
+        aToB: true,
+        amountIn: SWAP_AMOUNT_IN,
+        amountOut,
+        feeAmount,
+        userTokenA: traderA,
+        userTokenB: traderB,
+        payoutNonce: nextNonce(),
+      });
+
+      const after = (await fetchPool(pool)).state;
+      expect(await handleOf(traderA)).to.not.equal(traderABefore);
+      expect(await connection.getBalance(trader.publicKey)).to.equal(traderLamports);
+      expect(after.stateNonce).to.equal(before.stateNonce + 1n);
+      expect(decodePayout((await mustFetch(payout)).data).beneficiary.toBase58()).to.equal(
+        trader.publicKey.toBase58()
+      );
+
+      // settle_payout pays the output once the trader's decryption attests approval
+      const traderBBefore = await handleOf(traderB);
+      expect(await settlePayout(trader, payout)).to.be.true;
+      expect(await handleOf(traderB)).to.not.equal(traderBBefore);
+      reserveA += SWAP_AMOUNT_IN;
+      reserveB -= amountOut;
+    });
+  });
+
+  describe("swap_route", () => {
+    async function routeIx(hops: number, aToB: boolean[], payoutNonce: number, allowances: PublicKey[]) {
+      const { account } = await fetchPool(pool);
+      const hopAccounts = [
+        { pubkey: pool.poolAuthority, isSigner: false, isWritable: true },
+        { pubkey: pool.vaultA, isSigner: false, isWritable: true },
+        { pubkey: pool.vaultB, isSigner: false, isWritable: true },
+        { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+      ];
+      const light = await lightArgs([account], [payoutAddress(trader.publicKey, payoutNonce)], hopAccounts);
+      const { feeAmount, amountOut } = quote(SWAP_AMOUNT_IN, reserveA, reserveB);
+      return swapProgram.methods
+        .swapRoute(
+          light.proof as any,
+          light.addressTreeInfo,
+          light.outputTreeIndex,
+          bn(payoutNonce),
+          Array(hops).fill(light.metas[0]),
+          Array(hops).fill(account.data),
+          await encryptAmount(SWAP_AMOUNT_IN),
+          [await encryptAmount(amountOut)],
+          [await encryptAmount(feeAmount)],
+          [await encryptAmount(0n)],
+          INPUT_TYPE,
+          aToB
+        )
+        .accounts({
+          feePayer: authority,
+          trader: trader.publicKey,
+          userTokenIn: traderA,
+          userTokenOut: traderB,
+          approvedAllowance: allowances[0],
+          refundedAllowance: allowances[1],
+        } as any)
+        .remainingAccounts(light.remainingAccounts)
+        .instruction();
+    }
+
+    it("rejects hop vectors of different lengths", async () => {
+      const placeholder = [Keypair.generate().publicKey, Keypair.generate().publicKey];
+      await expectError(routeIx(1, [true, true], nextNonce(), placeholder), "InvalidRoute", [trader]);
+    });
+
+    it("pays a single-hop route through a payout", async () => {
+      const payoutNonce = nextNonce();
+      const traderABefore = await handleOf(traderA);
+      const allowances = await resolveAllowances((a) => routeIx(1, [true], payoutNonce, a), 2, [trader]);
+      await send([await routeIx(1, [true], payoutNonce, allowances)], [trader]);
+
+      expect(await handleOf(traderA)).to.not.equal(traderABefore);
+      const traderBBefore = await handleOf(traderB);
+      expect(await settlePayout(trader, payoutAddress(trader.publicKey, payoutNonce))).to.be.true;
+      expect(await handleOf(traderB)).to.not.equal(traderBBefore);
+      const { amountOut } = quote(SWAP_AMOUNT_IN, reserveA, reserveB);
+      reserveA += SWAP_AMOUNT_IN;
+      reserveB -= amountOut;
+    });
+  });
+
+  describe("flash_swap / set_flash_borrower", () => {
+    const stranger = Keypair.generate();
+
+    async function flashIx(borrower: PublicKey, callbackProgram: PublicKey) {
+      const { account } = await fetchPool(pool);
+      const light = await lightArgs([account]);
+      return swapProgram.methods
+        .flashSwap(
+          light.proof as any,
+          light.metas[0],
+          account.data,
+          await encryptAmount(1_000_000n),
+          await encryptAmount(3_000n),
+          await encryptAmount(0n),
+          INPUT_TYPE,
+          true,
+          Buffer.alloc(0),
+          0
+        )
+        .accounts({
+          feePayer: authority,
+          poolAuthority: pool.poolAuthority,
+          borrower,
+          borrowerTokenOut: authorityB,
+          poolVaultIn: pool.vaultA,
+          poolVaultOut: pool.vaultB,
+          callbackProgram,
+          oracle: null,
+        } as any)
+        .remainingAccounts(light.remainingAccounts)
+        .instruction();
+    }
+
+    it("flash_swap is disabled while no borrower is set", async () => {
+      await expectError(flashIx(authority, INCO_LIGHTNING_PROGRAM_ID), "FlashSwapsDisabled");
+    });
+
+    it("set_flash_borrower rejects this program as the borrower", async () => {
+      await expectError(poolIx(pool, "setFlashBorrower", [SWAP_PROGRAM_ID]), "InvalidFlashCallback");
+    });
+
+    it("set_flash_borrower records the borrower", async () => {
+      await send([await poolIx(pool, "setFlashBorrower", [authority])]);
+      const { state } = await fetchPool(pool);
+      expect(state.flashBorrower.toBase58()).to.equal(authority.toBase58());
+    });
+
+    it("flash_swap rejects a signer other than the borrower", async () => {
+      await expectError(flashIx(stranger.publicKey, INCO_LIGHTNING_PROGRAM_ID), "Unauthorized", [stranger]);
+    });
+
+    it("flash_swap rejects this program as the callback", async () => {
+      await expectError(flashIx(authority, SWAP_PROGRAM_ID), "InvalidFlashCallback");
+    });
+  });
+
+  // ─── Fee settings ────────────────────────────────────────────────────
+
+  describe("fee settings", () => {
+    it("set_dynamic_fee rejects a minimum fee above the maximum", async () => {
+      await expectError(poolIx(pool, "setDynamicFee", [true, 100, 50, 10, 60]), "InvalidDynamicFee");
+    });
+
+    it("set_dynamic_fee stores the fee band and can be turned off again", async () => {
+      await send([await poolIx(pool, "setDynamicFee", [true, 10, 100, 5, 600])]);
+      let { state } = await fetchPool(pool);
+      expect(state.dynamicFeeEnabled).to.be.true;
+      expect([state.minFeeBps, state.maxFeeBps, state.volatilityStepBps]).to.deep.equal([10, 100, 5]);
+      expect(state.volatilityHalfLifeSecs).to.equal(600);
+
+      await send([await poolIx(pool, "setDynamicFee", [false, 0, 0, 0, 0])]);
+      ({ state } = await fetchPool(pool));
+      expect(state.dynamicFeeEnabled).to.be.false;
+    });
+
+    it("swap_exact_in refunds a fee below the dynamic minimum", async () => {
+      await send([await poolIx(pool, "setDynamicFee", [true, 100, 200, 5, 600])]);
+      // The quote charges FEE_BPS, below the 1% minimum of the dynamic band
+      const { feeAmount, amountOut } = quote(SWAP_AMOUNT_IN, reserveA, reserveB);
+      const payout = await swap({
+        ref: pool,
+        aToB: true,
+        amountIn: SWAP_AMOUNT_IN,
+        amountOut,
+        feeAmount,
+        userTokenA: traderA,
+        userTokenB: traderB,
+        payoutNonce: nextNonce(),
+      });
+
+      const traderABefore = await handleOf(traderA);
+      const traderBBefore = await handleOf(traderB);
+      expect(await settlePayout(trader, payout)).to.be.false;
+      expect(await handleOf(traderA)).to.not.equal(traderABefore);
+      expect(await handleOf(traderB)).to.equal(traderBBefore);
+
+      await send([await poolIx(pool, "setDynamicFee", [false, 0, 0, 0, 0])]);
+    });
+
+    it("set_protocol_fee_share rejects more than 100%", async () => {
+      await expectError(poolIx(pool, "setProtocolFeeShare", [10_001]), "InvalidProtocolFeeShare");
+    });
+
+    it("set_protocol_fee_share stores the share", async () => {
+      await send([await poolIx(pool, "setProtocolFeeShare", [2_000])]);
+      expect((await fetchPool(pool)).state.protocolFeeShareBps).to.equal(2_000);
+    });
+
+    it("set_referral_share rejects more than 100%", async () => {
+      await expectError(poolIx(pool, "setReferralShare", [10_001]), "InvalidReferralShare");
+    });
+
+    it("set_referral_share stores the share", async () => {
+      await send([await poolIx(pool, "setReferralShare", [5_000])]);
+      expect((await fetchPool(pool)).state.referralShareBps).to.equal(5_000);
+    });
+  });
+
+  // ─── Referrals ───────────────────────────────────────────────────────
+
+  describe("register_referrer / claim_referral_fees", () => {
+    const referrerAddress = () =>
+      deriveAddress(Buffer.from("referrer_fees"), pool.address.toBuffer(), referrer.publicKey.toBuffer());
+
+    it("register_referrer opens a fee account for the pool", async () => {
+      const light = await lightArgs([], [referrerAddress()]);
+      const ix = await swapProgram.methods
+        .registerReferrer(light.proof as any, light.addressTreeInfo, light.outputTreeIndex, mintA, mintB)
+        .accounts({ feePayer: authority, referrer: referrer.publicKey } as any)
+        .remainingAccounts(light.remainingAccounts)
+        .instruction();
+      await send([ix], [referrer]);
+
+      const fees = decodeReferrerFees((await mustFetch(referrerAddress())).data);
+      expect(fees.referrer.toBase58()).to.equal(referrer.publicKey.toBase58());
+      expect(fees.poolAddress.equals(pool.address.toBuffer())).to.be.true;
+    });
+
+    it("swap_exact_in credits the referrer's share of the fee", async () => {
+      const { feeAmount, amountOut } = quote(SWAP_AMOUNT_IN, reserveA, reserveB);
+      const before = decodeReferrerFees((await mustFetch(referrerAddress())).data);
+      const payout = await swap({
+        ref: pool,
+        aToB: true,
+        amountIn: SWAP_AMOUNT_IN,
+        amountOut,
+        feeAmount,
+        userTokenA: traderA,
+        userTokenB: traderB,
+        payoutNonce: nextNonce(),
+        referrerFees: await mustFetch(referrerAddress()),
+      });
+      const after = decodeReferrerFees((await mustFetch(referrerAddress())).data);
+
+      expect(after.feeA).to.not.equal(before.feeA);
+      expect(after.vaultA.toBase58()).to.equal(pool.vaultA.toBase58());
+      expect(await settlePayout(trader, payout)).to.be.true;
+      reserveA += SWAP_AMOUNT_IN;
+      reserveB -= amountOut;
+    });
+
+    async function claimIx(signer: PublicKey, payoutNonce: number, allowances: PublicKey[]) {
+      const { account } = await fetchPool(pool);
+      const fees = await mustFetch(referrerAddress());
+      const light = await lightArgs([account, fees], [payoutAddress(signer, payoutNonce)]);
+      return swapProgram.methods
+        .claimReferralFees(
+          light.proof as any,
+          light.metas[0],
+          account.data,
+          light.metas[1],
+          fees.data,
+          await encryptAmount(1_000n),
+          INPUT_TYPE,
+          true,
+          light.addressTreeInfo,
+          light.outputTreeIndex,
+          bn(payoutNonce)
+        )
+        .accounts({
+          feePayer: authority,
+          referrer: signer,
+          poolVault: pool.vaultA,
+          referrerToken: referrerA,
+          approvedAllowance: allowances[0],
+        } as any)
+        .remainingAccounts(light.remainingAccounts)
+        .instruction();
+    }
+
+    it("claim_referral_fees rejects a claim by anyone but the referrer", async () => {
+      await expectError(
+        claimIx(trader.publicKey, nextNonce(), [Keypair.generate().publicKey]),
+        "Unauthorized",
+        [trader]
+      );
+    });
+
+    it("claim_referral_fees pays the claim through a payout", async () => {
+      const payoutNonce = nextNonce();
+      const before = decodeReferrerFees((await mustFetch(referrerAddress())).data);
+      const allowances = await resolveAllowances((a) => claimIx(referrer.publicKey, payoutNonce, a), 1, [referrer]);
+      await send([await claimIx(referrer.publicKey, payoutNonce, allowances)], [referrer]);
+
+      const after = decodeReferrerFees((await mustFetch(referrerAddress())).data);
+      expect(after.feeA).to.not.equal(before.feeA);
+      const referrerABefore = await handleOf(referrerA);
+      expect(await settlePayout(referrer, payoutAddress(referrer.publicKey, payoutNonce))).to.be.true;
+      expect(await handleOf(referrerA)).to.not.equal(referrerABefore);
+    });
+  });
+
+  // ─── Oracle guard ────────────────────────────────────────────────────
+
+  describe("set_oracle_guard", () => {
+    it("rejects a deviation band above 100%", async () => {
+      await expectError(
+        poolIx(pool, "setOracleGuard", [Keypair.generate().publicKey, 60, 10_001]),
+        "InvalidOracle"
+      );
+    });
+
+    it("requires the configured feed on swaps until it is cleared", async () => {
+      const feed = Keypair.generate().publicKey;
+      await send([await poolIx(pool, "setOracleGuard", [feed, 60, 500])]);
+      const { state } = await fetchPool(pool);
+      expect(state.oracleFeed.toBase58()).to.equal(feed.toBase58());
+      expect(state.oracleMaxStalenessSecs).to.equal(60);
+      expect(state.oracleMaxDeviationBps).to.equal(500);
+
+      const params: SwapParams = {
+        ref: pool,
+        aToB: true,
+        amountIn: SWAP_AMOUNT_IN,
+        amountOut: 0n,
+        feeAmount: 0n,
+        userTokenA: traderA,
+        userTokenB: traderB,
+        payoutNonce: nextNonce(),
+      };
+      const placeholder = [Keypair.generate().publicKey, Keypair.generate().publicKey];
+      await expectError(swapIx(params, placeholder), "InvalidOracle", [trader]);
+
+      await send([await poolIx(pool, "setOracleGuard", [PublicKey.default, 0, 0])]);
+      expect((await fetchPool(pool)).state.oracleFeed.toBase58()).to.equal(PublicKey.default.toBase58());
+    });
+  });
+
+  // ─── Circuit breaker ─────────────────────────────────────────────────
+
+  describe("set_circuit_breaker / grant_breaker_access", () => {
+    async function breakerArgs(enabled: boolean, windowSecs: number) {
+      const cap = await encryptAmount(VAULT_B_LIQUIDITY / 10n);
+      return [enabled, true, windowSecs, cap, cap, cap, cap, INPUT_TYPE];
+    }
+
+    it("set_circuit_breaker rejects an empty window", async () => {
+      await expectError(poolIx(pool, "setCircuitBreaker", await breakerArgs(true, 0)), "InvalidCircuitBreaker");
+    });
+
+    it("set_circuit_breaker stores the window and encrypted caps", async () => {
+      await send([await poolIx(pool, "setCircuitBreaker", await breakerArgs(true, 3_600))]);
+      const { state } = await fetchPool(pool);
+      expect(state.breakerEnabled).to.be.true;
+      expect(state.breakerAutoPause).to.be.true;
+      expect(state.breakerWindowSecs).to.equal(3_600);
+      expect(state.maxTradeOutA).to.not.equal(0n);
+      expect(state.windowOutflowCapB).to.not.equal(0n);
+    });
+
+    it("grant_breaker_access lets the authority decrypt the tripped flag", async () => {
+      const { state } = await fetchPool(pool);
+      const allowance = getAllowancePda(state.breakerTripped, authority);
+      await send([await poolIx(pool, "grantBreakerAccess", [], { allowance })]);
+      expect(await connection.getAccountInfo(allowance)).to.not.be.null;
+
+      await send([await poolIx(pool, "setCircuitBreaker", await breakerArgs(false, 0))]);
+      expect((await fetchPool(pool)).state.breakerEnabled).to.be.false;
+    });
+  });
+
+  // ─── TWAP observations ───────────────────────────────────────────────
+
+  describe("initialize_observations / record_observation / grant_price_access", () => {
+    const observationsAddress = () => deriveAddress(Buffer.from("observations"), pool.address.toBuffer());
+
+    async function observationIx(method: string, args: any[], accounts: Record<string, PublicKey> = {}) {
+      const { account } = await fetchPool(pool);
+      const observations = await mustFetch(observationsAddress());
+      const light = await lightArgs([account, observations]);
+      return (swapProgram.methods as any)
+        [method](light.proof, light.metas[0], account.data, light.metas[1], observations.data, ...args)
+        .accounts({ feePayer: authority, ...accounts })
+        .remainingAccounts(light.remainingAccounts)
+        .instruction();
+    }
+
+    it("initialize_observations opens the ring buffer for the pool", async () => {
+      const light = await lightArgs([], [observationsAddress()]);
+      const ix = await swapProgram.methods
+        .initializeObservations(
+          light.proof as any,
+          light.addressTreeInfo,
+          light.outputTreeIndex,
+          Array.from(pool.address.toBytes())
+        )
+        .accounts({ feePayer: authority } as any)
+        .remainingAccounts(light.remainingAccounts)
+        .instruction();
+      await send([ix]);
+
+      const observations = decodeObservations((await mustFetch(observationsAddress())).data);
+      expect(observations.poolAddress.equals(pool.address.toBuffer())).to.be.true;
+      expect(observations.nextIndex).to.equal(0);
+    });
+
+    it("record_observation writes the next slot", async () => {
+      await send([await observationIx("recordObservation", [])]);
+      const observations = decodeObservations((await mustFetch(observationsAddress())).data);
+      expect(observations.nextIndex).to.equal(1);
+      expect(observations.observations[0].timestamp > 0n).to.be.true;
+    });
+
+    it("grant_price_access rejects a slot outside the ring buffer", async () => {
+      const placeholder = Keypair.generate().publicKey;
+      await expectError(
+        observationIx("grantPriceAccess", [OBSERVATION_SLOTS], {
+          authority,
+          reader: auditor.publicKey,
+          allowanceA: placeholder,
+          allowanceB: placeholder,
+        }),
+        "InvalidObservations"
+      );
+    });
+
+    it("grant_price_access lets a reader decrypt an observation", async () => {
+      const observations = decodeObservations((await mustFetch(observationsAddress())).data);
+      const allowanceA = getAllowancePda(observations.observations[0].priceCumulativeA, auditor.publicKey);
+      const allowanceB = getAllowancePda(observations.observations[0].priceCumulativeB, auditor.publicKey);
+      await send([
+        await observationIx("grantPriceAccess", [0], {
+          authority,
+          reader: auditor.publicKey,
+          allowanceA,
+          allowanceB,
+        }),
+      ]);
+      expect(await connection.getAccountInfo(allowanceA)).to.not.be.null;
+      expect(await connection.getAccountInfo(allowanceB)).to.not.be.null;
+    });
+  });
+
+  // ─── Limit orders ────────────────────────────────────────────────────
+
+  describe("create_limit_order / fill_limit_order / cancel_limit_order", () => {
+    let escrowVault: PublicKey;
+    const orderAddress = (orderNonce: number) =>
+      deriveAddress(Buffer.from("limit_order"), trader.publicKey.toBuffer(), u64Le(orderNonce));
+
+    before(async () => {
+      const escrowAuthority = pda(Buffer.from("order_escrow"), mintA.toBuffer(), mintB.toBuffer());
+      escrowVault = await createIncoAccount(mintA, escrowAuthority);
+    });
+
+    async function createOrder(): Promise<PublicKey> {
+      const orderNonce = nextNonce();
+      const light = await lightArgs([], [orderAddress(orderNonce)]);
+      const ix = await swapProgram.methods
+        .createLimitOrder(
+          light.proof as any,
+          light.addressTreeInfo,
+          light.outputTreeIndex,
+          bn(orderNonce),
+          mintA,
+          mintB,
+          true,
+          await encryptAmount(SWAP_AMOUNT_IN),
+          await encryptAmount(1n),
+          INPUT_TYPE
+        )
+        .accounts({
+          feePayer: authority,
+          owner: trader.publicKey,
+          ownerTokenIn: traderA,
+          escrowVaultIn: escrowVault,
+        } as any)
+        .remainingAccounts(light.remainingAccounts)
+        .instruction();
+      await send([ix], [trader]);
+      return orderAddress(orderNonce);
+    }
+
+    it("create_limit_order escrows the input", async () => {
+      const escrowBefore = await handleOf(escrowVault);
+      const address = await createOrder();
+      const order = await mustFetch(address);
+      expect(new PublicKey(order.data.subarray(0, 32)).toBase58()).to.equal(trader.publicKey.toBase58());
+      expect(await handleOf(escrowVault)).to.not.equal(escrowBefore);
+    });
+
+    async function fillIx(address: PublicKey, owner: PublicKey, payoutNonce: number, allowances: PublicKey[]) {
+      const { account } = await fetchPool(pool);
+      const order = await mustFetch(address);
+      const light = await lightArgs([account, order], [payoutAddress(trader.publicKey, payoutNonce)]);
+      return swapProgram.methods
+        .fillLimitOrder(
+          light.proof as any,
+          light.metas[0],
+          account.data,
+          light.metas[1],
+          order.data,
+          await encryptAmount(0n),
+          light.addressTreeInfo,
+          light.outputTreeIndex,
+          bn(payoutNonce)
+        )
+        .accounts({
+          feePayer: authority,
+          owner,
+          escrowVaultIn: escrowVault,
+          poolVaultIn: pool.vaultA,
+          poolVaultOut: pool.vaultB,
+          ownerTokenOut: traderB,
+          approvedAllowance: allowances[0],
+          oracle: null,
+        } as any)
+        .remainingAccounts(light.remainingAccounts)
+        .instruction();
+    }
+
+    it("fill_limit_order rejects an owner other than the order's", async () => {
+      const address = await createOrder();
+      await expectError(
+        fillIx(address, auditor.publicKey, nextNonce(), [Keypair.generate().publicKey]),
+        "Unauthorized"
+      );
+    });
+
+    it("fill_limit_order closes the order into a payout to its owner", async () => {
+      const address = await createOrder();
+      const payoutNonce = nextNonce();
+      const allowances = await resolveAllowances((a) => fillIx(address, trader.publicKey, payoutNonce, a), 1);
+      await send([await fillIx(address, trader.publicKey, payoutNonce, allowances)]);
+
+      expect(await fetchCompressed(address), "order closed").to.be.null;
+      await settlePayout(trader, payoutAddress(trader.publicKey, payoutNonce));
+    });
+
+    it("cancel_limit_order refunds the escrow through a payout", async () => {
+      const address = await createOrder();
+      const payoutNonce = nextNonce();
+      const build = async (allowances: PublicKey[]) => {
+        const order = await mustFetch(address);
+        const light = await lightArgs([order], [payoutAddress(trader.publicKey, payoutNonce)]);
+        return swapProgram.methods
+          .cancelLimitOrder(
+            light.proof as any,
+            light.metas[0],
+            order.data,
+            light.addressTreeInfo,
+            light.outputTreeIndex,
+            bn(payoutNonce)
+          )
+          .accounts({
+            feePayer: authority,
+            owner: trader.publicKey,
+            escrowVaultIn: escrowVault,
+            ownerTokenIn: traderA,
+            approvedAllowance: allowances[0],
+          } as any)
+          .remainingAccounts(light.remainingAccounts)
+          .instruction();
+      };
+      await send([await build(await resolveAllowances(build, 1, [trader]))], [trader]);
+
+      expect(await fetchCompressed(address), "order closed").to.be.null;
+      const traderABefore = await handleOf(traderA);
+      expect(await settlePayout(trader, payoutAddress(trader.publicKey, payoutNonce))).to.be.true;
+      expect(await handleOf(traderA)).to.not.equal(traderABefore);
+    });
+  });
+
+  // ─── DCA orders ──────────────────────────────────────────────────────
+
+  describe("create_dca_order / execute_dca / cancel_dca_order", () => {
+    let orderNonce: number;
+    let orderAddress: PublicKey;
+    let escrowVault: PublicKey;
+
+    async function createIx(intervalSecs: number, executions: number) {
+      const light = await lightArgs([], [orderAddress]);
+      return swapProgram.methods
+        .createDcaOrder(
+          light.proof as any,
+          light.addressTreeInfo,
+          light.outputTreeIndex,
+          bn(orderNonce),
+          mintA,
+          mintB,
+          true,
+          await encryptAmount(2n * SWAP_AMOUNT_IN),
+          await encryptAmount(SWAP_AMOUNT_IN),
+          await encryptAmount(1n),
+          INPUT_TYPE,
+          intervalSecs,
+          executions
+        )
+        .accounts({
+          feePayer: authority,
+          owner: trader.publicKey,
+          ownerTokenIn: traderA,
+          escrowVaultIn: escrowVault,
+        } as any)
+        .remainingAccounts(light.remainingAccounts)
+        .instruction();
+    }
+
+    before(async () => {
+      orderNonce = nextNonce();
+      orderAddress = deriveAddress(Buffer.from("dca_order"), trader.publicKey.toBuffer(), u64Le(orderNonce));
+      const escrowAuthority = pda(Buffer.from("dca_escrow"), orderAddress.toBuffer());
+      escrowVault = await createIncoAccount(mintA, escrowAuthority);
+    });
+
+    it("create_dca_order rejects an order without executions", async () => {
+      await expectError(createIx(3_600, 0), "InvalidDcaOrder", [trader]);
+    });
+
+    it("create_dca_order escrows the budget", async () => {
+      const escrowBefore = await handleOf(escrowVault);
+      await send([await createIx(3_600, 2)], [trader]);
+
+      const order = decodeDcaOrder((await mustFetch(orderAddress)).data);
+      expect(order.owner.toBase58()).to.equal(trader.publicKey.toBase58());
+      expect(order.executionsRemaining).to.equal(2);
+      expect(order.intervalSecs).to.equal(3_600);
+      expect(await handleOf(escrowVault)).to.not.equal(escrowBefore);
+    });
+
+    async function executeIx(payoutNonce: number, allowances: PublicKey[]) {
+      const { account } = await fetchPool(pool);
+      const order = await mustFetch(orderAddress);
+      const light = await lightArgs([account, order], [payoutAddress(trader.publicKey, payoutNonce)]);
+      return swapProgram.methods
+        .executeDca(
+          light.proof as any,
+          light.metas[0],
+          account.data,
+          light.metas[1],
+          order.data,
+          await encryptAmount(0n),
+          light.addressTreeInfo,
+          light.outputTreeIndex,
+          bn(payoutNonce)
+        )
+        .accounts({
+          feePayer: authority,
+          owner: trader.publicKey,
+          escrowVaultIn: escrowVault,
+          poolVaultIn: pool.vaultA,
+          poolVaultOut: pool.vaultB,
+          ownerTokenOut: traderB,
+          approvedAllowance: allowances[0],
+          oracle: null,
+        } as any)
+        .remainingAccounts(light.remainingAccounts)
+        .instruction();
+    }
+
+    it("execute_dca swaps one slice and starts the interval", async () => {
+      const payoutNonce = nextNonce();
+      const allowances = await resolveAllowances((a) => executeIx(payoutNonce, a), 1);
+      await send([await executeIx(payoutNonce, allowances)]);
+
+      const order = decodeDcaOrder((await mustFetch(orderAddress)).data);
+      expect(order.executionsRemaining).to.equal(1);
+      expect(order.lastExecutionTs > 0n).to.be.true;
+      await settlePayout(trader, payoutAddress(trader.publicKey, payoutNonce));
+    });
+
+    it("execute_dca rejects a slice before the interval has elapsed", async () => {
+      await expectError(executeIx(nextNonce(), [Keypair.generate().publicKey]), "DcaIntervalNotElapsed");
+    });
+
+    it("cancel_dca_order refunds the remaining budget through a payout", async () => {
+      const payoutNonce = nextNonce();
+      const build = async (allowances: PublicKey[]) => {
+        const order = await mustFetch(orderAddress);
+        const light = await lightArgs([order], [payoutAddress(trader.publicKey, payoutNonce)]);
+        return swapProgram.methods
+          .cancelDcaOrder(
+            light.proof as any,
+            light.metas[0],
+            order.data,
+            await encryptAmount(SWAP_AMOUNT_IN),
+            INPUT_TYPE,
+            light.addressTreeInfo,
+            light.outputTreeIndex,
+            bn(payoutNonce)
+          )
+          .accounts({
+            feePayer: authority,
+            owner: trader.publicKey,
+            escrowVaultIn: escrowVault,
+            ownerTokenIn: traderA,
+            approvedAllowance: allowances[0],
+          } as any)
+          .remainingAccounts(light.remainingAccounts)
+          .instruction();
+      };
+      await send([await build(await resolveAllowances(build, 1, [trader]))], [trader]);
+
+      expect(await fetchCompressed(orderAddress), "order closed").to.be.null;
+      await settlePayout(trader, payoutAddress(trader.publicKey, payoutNonce));
+    });
+  });
+
+  // ─── Batch auctions ──────────────────────────────────────────────────
+
+  describe("set_batch_mode / open_swap_batch / queue_swap_intent / settle_batch / claim_batch_output", () => {
+    const BATCH_WINDOW_SECS = 20;
+    let batchAddress: PublicKey;
+
+    async function batchIx(method: string, args: any[], accounts: Record<string, PublicKey | null>) {
+      const { account } = await fetchPool(pool);
+      const batch = await mustFetch(batchAddress);
+      const light = await lightArgs([account, batch]);
+      return (swapProgram.methods as any)
+        [method](light.proof, light.metas[0], account.data, light.metas[1], batch.data, ...args)
+        .accounts({ feePayer: authority, ...accounts })
+        .remainingAccounts(light.remainingAccounts)
+        .instruction();
+    }
+
+    it("set_batch_mode rejects an empty window", async () => {
+      await expectError(poolIx(pool, "setBatchMode", [true, 0]), "InvalidSwapBatch");
+    });
+
+    it("set_batch_mode only applies to constant-product pools", async () => {
+      await expectError(poolIx(stablePool, "setBatchMode", [true, BATCH_WINDOW_SECS]), "InvalidPoolKind");
+    });
+
+    it("set_batch_mode routes swaps into batches", async () => {
+      await send([await poolIx(pool, "setBatchMode", [true, BATCH_WINDOW_SECS])]);
+      const { state } = await fetchPool(pool);
+      expect(state.batchMode).to.be.true;
+      expect(state.batchWindowSecs).to.equal(BATCH_WINDOW_SECS);
+
+      const params: SwapParams = {
+        ref: pool,
+        aToB: true,
+        amountIn: SWAP_AMOUNT_IN,
+        amountOut: 0n,
+        feeAmount: 0n,
+        userTokenA: traderA,
+        userTokenB: traderB,
+        payoutNonce: nextNonce(),
+      };
+      const placeholder = [Keypair.generate().publicKey, Keypair.generate().publicKey];
+      await expectError(swapIx(params, placeholder), "BatchModeActive", [trader]);
+    });
+
+    it("open_swap_batch opens the next batch window", async () => {
+      const { account, state } = await fetchPool(pool);
+      batchAddress = deriveAddress(Buffer.from("swap_batch"), pool.address.toBuffer(), u64Le(state.nextBatchId));
+      const light = await lightArgs([account], [batchAddress]);
+      const ix = await swapProgram.methods
+        .openSwapBatch(light.proof as any, light.addressTreeInfo, light.outputTreeIndex, light.metas[0], account.data)
+        .accounts({ feePayer: authority } as any)
+        .remainingAccounts(light.remainingAccounts)
+        .instruction();
+      await send([ix]);
+
+      const batch = decodeSwapBatch((await mustFetch(batchAddress)).data);
+      expect(batch.batchId).to.equal(state.nextBatchId);
+      expect(batch.settled).to.be.false;
+      expect((await fetchPool(pool)).state.nextBatchId).to.equal(state.nextBatchId + 1n);
+    });
+
+    it("queue_swap_intent escrows the trader's input in the pool vault", async () => {
+      const traderABefore = await handleOf(traderA);
+      const ix = await batchIx(
+        "queueSwapIntent",
+        [await encryptAmount(SWAP_AMOUNT_IN), await encryptAmount(1n), INPUT_TYPE, true],
+        {
+          trader: trader.publicKey,
+          traderTokenIn: traderA,
+          traderTokenOut: traderB,
+          poolVaultIn: pool.vaultA,
+        }
+      );
+      await send([ix], [trader]);
+
+      const batch = decodeSwapBatch((await mustFetch(batchAddress)).data);
+      expect(batch.intents).to.have.length(1);
+      expect(batch.intents[0].trader.toBase58()).to.equal(trader.publicKey.toBase58());
+      expect(await handleOf(traderA)).to.not.equal(traderABefore);
+    });
+
+    it("settle_batch waits for the window to close", async () => {
+      await expectError(batchIx("settleBatch", [], { oracle: null }), "SwapBatchOpen");
+    });
+
+    it("settle_batch clears the batch at one price", async () => {
+      const { windowEndTs } = decodeSwapBatch((await mustFetch(batchAddress)).data);
+      while (BigInt(Math.floor(Date.now() / 1000)) <= windowEndTs + 5n) await sleep(2000);
+
+      await send([await batchIx("settleBatch", [], { oracle: null })]);
+      expect(decodeSwapBatch((await mustFetch(batchAddress)).data).settled).to.be.true;
+    });
+
+    it("claim_batch_output pays the intent's output through a payout", async () => {
+      const payoutNonce = nextNonce();
+      const build = async (allowances: PublicKey[]) => {
+        const { account } = await fetchPool(pool);
+        const batch = await mustFetch(batchAddress);
+        const light = await lightArgs([account, batch], [payoutAddress(trader.publicKey, payoutNonce)]);
+        return swapProgram.methods
+          .claimBatchOutput(
+            light.proof as any,
+            light.metas[0],
+            account.data,
+            light.metas[1],
+            batch.data,
+            0,
+            true,
+            await encryptAmount(1n),
+            INPUT_TYPE,
+            light.addressTreeInfo,
+            light.outputTreeIndex,
+            bn(payoutNonce)
+          )
+          .accounts({
+            feePayer: authority,
+            trader: trader.publicKey,
+            poolVault: pool.vaultB,
+            traderToken: traderB,
+            approvedAllowance: allowances[0],
+          } as any)
+          .remainingAccounts(light.remainingAccounts)
+          .instruction();
+      };
+      await send([await build(await resolveAllowances(build, 1, [trader]))], [trader]);
+      await settlePayout(trader, payoutAddress(trader.publicKey, payoutNonce));
+
+      await send([await poolIx(pool, "setBatchMode", [false, BATCH_WINDOW_SECS])]);
+      expect((await fetchPool(pool)).state.batchMode).to.be.false;
+    });
+  });
+
+  // ─── Reserve audits ──────────────────────────────────────────────────
+
+  describe("set_auditor / verify_reserves", () => {
+    async function verifyIx(allowances: PublicKey[]) {
+      const { account, state } = await fetchPool(pool);
+      const attestation = deriveAddress(
+        Buffer.from("reserve_attestation"),
+        pool.address.toBuffer(),
+        u64Le(state.nextAttestationId)
+      );
+      const light = await lightArgs([account], [attestation]);
+      return swapProgram.methods
+        .verifyReserves(light.proof as any, light.addressTreeInfo, light.outputTreeIndex, light.metas[0], account.data)
+        .accounts({
+          feePayer: authority,
+          auditor: auditor.publicKey,
+          poolVaultA: pool.vaultA,
+          poolVaultB: pool.vaultB,
+          allowanceA: allowances[0],
+          allowanceB: allowances[1],
+        } as any)
+        .remainingAccounts(light.remainingAccounts)
+        .instruction();
+    }
+
+    it("verify_reserves requires an auditor to be set", async () => {
+      const placeholder = [Keypair.generate().publicKey, Keypair.generate().publicKey];
+      await expectError(verifyIx(placeholder), "Unauthorized");
+    });
+
+    it("set_auditor records the auditor", async () => {
+      await send([await poolIx(pool, "setAuditor", [auditor.publicKey])]);
+      expect((await fetchPool(pool)).state.auditor.toBase58()).to.equal(auditor.publicKey.toBase58());
+    });
+
+    it("verify_reserves writes an attestation the auditor can decrypt", async () => {
+      const { state } = await fetchPool(pool);
+      const allowances = await resolveAllowances(verifyIx, 2);
+      await send([await verifyIx(allowances)]);
+
+      const address = deriveAddress(
+        Buffer.from("reserve_attestation"),
+        pool.address.toBuffer(),
+        u64Le(state.nextAttestationId)
+      );
+      const attestation = decodeReserveAttestation((await mustFetch(address)).data);
+      expect(attestation.auditor.toBase58()).to.equal(auditor.publicKey.toBase58());
+      expect(attestation.attestationId).to.equal(state.nextAttestationId);
+      expect((await fetchPool(pool)).state.nextAttestationId).to.equal(state.nextAttestationId + 1n);
+
+      const { plaintexts } = await attestedDecrypt([attestation.vaultACovers, attestation.vaultBCovers], auditor);
+      expect(plaintexts.every(isTrue)).to.be.true;
+    });
+  });
+
+  // ─── Wrapped SOL ─────────────────────────────────────────────────────
+
+  describe("add_liquidity_wrapped / wrapped swaps / request_unwrap / claim_unwrap", () => {
+    let wrappedMint: PublicKey;
+    let wrapAuthority: PublicKey;
+    let backingVault: PublicKey;
+    let wrappedPool: PoolRef;
+    let authorityWsol: PublicKey;
+    let traderWsol: PublicKey;
+    let traderW: PublicKey;
+    let reserveW = 0n;
+    let reserveWA = 0n;
+
+    before(async () => {
+      const mint = Keypair.generate();
+      wrapAuthority = pda(Buffer.from("wrap_authority"), NATIVE_MINT.toBuffer(), mint.publicKey.toBuffer());
+      wrappedMint = await createIncoMint(mint, wrapAuthority);
+      backingVault = await createWsolAccount(wrapAuthority);
+      authorityWsol = await createWsolAccount(authority);
+      traderWsol = await createWsolAccount(trader.publicKey);
+      traderW = await createIncoAccount(wrappedMint, trader.publicKey);
+
+      // The trader pays native deposits itself
+      await send([
+        SystemProgram.transfer({ fromPubkey: authority, toPubkey: trader.publicKey, lamports: 10_000_000 }),
+      ]);
+
+      wrappedPool = poolRef(wrappedMint, mintA, FEE_BPS);
+      await send([await initializePoolIx(wrappedPool, { constantProduct: {} }, 0)]);
+
+      await mintTo(mintA, wrappedPool.vaultB, VAULT_A_LIQUIDITY);
+      await send([
+        await poolIx(wrappedPool, "addLiquidity", [await encryptAmount(0n), await encryptAmount(VAULT_A_LIQUIDITY), INPUT_TYPE], {
+          oracle: null,
+        }),
+      ]);
+      reserveWA = VAULT_A_LIQUIDITY;
+    });
+
+    async function wrappedDepositIx(splBackingVault: PublicKey, payoutNonce: number, allowances: PublicKey[]) {
+      const { account } = await fetchPool(wrappedPool);
+      const light = await lightArgs([account], [payoutAddress(authority, payoutNonce)]);
+      return swapProgram.methods
+        .addLiquidityWrapped(
+          light.proof as any,
+          light.addressTreeInfo,
+          light.outputTreeIndex,
+          bn(payoutNonce),
+          light.metas[0],
+          account.data,
+          bn(WRAP_DEPOSIT.toString()),
+          await encryptAmount(WRAP_DEPOSIT),
+          INPUT_TYPE,
+          true
+        )
+        .accounts({
+          feePayer: authority,
+          authority,
+          authoritySpl: authorityWsol,
+          splBackingVault,
+          wrapAuthority,
+          incoMint: wrappedMint,
+          poolVault: wrappedPool.vaultA,
+          approvedAllowance: allowances[0],
+          refundedAllowance: allowances[1],
+        } as any)
+        .remainingAccounts(light.remainingAccounts)
+        .instruction();
+    }
+
+    it("add_liquidity_wrapped rejects a backing vault not owned by the wrap authority", async () => {
+      const placeholder = [Keypair.generate().publicKey, Keypair.generate().publicKey];
+      await expectError(wrappedDepositIx(authorityWsol, nextNonce(), placeholder), "InvalidWrapAccounts");
+    });
+
+    it("add_liquidity_wrapped takes native SOL and mints the wrapped token into the vault", async () => {
+      const payoutNonce = nextNonce();
+      const backingBefore = await splAmount(backingVault);
+      const allowances = await resolveAllowances((a) => wrappedDepositIx(backingVault, payoutNonce, a), 2);
+      await send([await wrappedDepositIx(backingVault, payoutNonce, allowances)]);
+      expect(await splAmount(backingVault)).to.equal(backingBefore + WRAP_DEPOSIT);
+
+      const vaultBefore = await handleOf(wrappedPool.vaultA);
+      expect(await settlePayout(walletKeypair, payoutAddress(authority, payoutNonce))).to.be.true;
+      expect(await handleOf(wrappedPool.vaultA)).to.not.equal(vaultBefore);
+      reserveW = WRAP_DEPOSIT;
+    });
+
+    it("swap_exact_in takes a native SOL deposit as the input", async () => {
+      const { feeAmount, amountOut } = quote(WRAP_SWAP_IN, reserveW, reserveWA);
+      const backingBefore = await splAmount(backingVault);
+      const traderABefore = await handleOf(traderA);
+
+      const payout = await swap({
+        ref: wrappedPool,
+        aToB: true,
+        amountIn: WRAP_SWAP_IN,
+        amountOut,
+        feeAmount,
+        userTokenA: traderW,
+        userTokenB: traderA,
+        payoutNonce: nextNonce(),
+        wrap: { traderSpl: traderWsol, backingVault, wrapAuthority, incoMint: wrappedMint },
+      });
+      expect(await splAmount(backingVault)).to.equal(backingBefore + WRAP_SWAP_IN);
+
+      expect(await settlePayout(trader, payout)).to.be.true;
+      expect(await handleOf(traderA)).to.not.equal(traderABefore);
+      reserveW += WRAP_SWAP_IN;
+      reserveWA -= amountOut;
+    });
+
+    it("request_unwrap burns wrapped tokens into a ticket", async () => {
+      // Buy wrapped tokens to unwrap
+      const amountIn = 1_000_000_000n;
+      const { feeAmount, amountOut } = quote(amountIn, reserveWA, reserveW);
+      expect(amountOut > UNWRAP_AMOUNT).to.be.true;
+      const payout = await swap({
+        ref: wrappedPool,
+        aToB: false,
+        amountIn,
+        amountOut,
+        feeAmount,
+        userTokenA: traderW,
+        userTokenB: traderA,
+        payoutNonce: nextNonce(),
+      });
+      expect(await settlePayout(trader, payout)).to.be.true;
+
+      const ticketNonce = nextNonce();
+      const ticketAddress = deriveAddress(Buffer.from("unwrap_ticket"), trader.publicKey.toBuffer(), u64Le(ticketNonce));
+      const build = async (allowances: PublicKey[]) => {
+        const light = await lightArgs([], [ticketAddress]);
+        return swapProgram.methods
+          .requestUnwrap(
+            light.proof as any,
+            light.addressTreeInfo,
+            light.outputTreeIndex,
+            bn(ticketNonce),
+            bn(UNWRAP_AMOUNT.toString()),
+            await encryptAmount(UNWRAP_AMOUNT),
+            INPUT_TYPE
+          )
+          .accounts({
+            feePayer: authority,
+            owner: trader.publicKey,
+            ownerToken: traderW,
+            incoMint: wrappedMint,
+            wrapAuthority,
+            splBackingVault: backingVault,
+            recipient: traderWsol,
+            allowance: allowances[0],
+          } as any)
+          .remainingAccounts(light.remainingAccounts)
+          .instruction();
+      };
+      const traderWBefore = await handleOf(traderW);
+      await send([await build(await resolveAllowances(build, 1, [trader]))], [trader]);
+      expect(await handleOf(traderW)).to.not.equal(traderWBefore);
+
+      const ticket = decodeUnwrapTicket((await mustFetch(ticketAddress)).data);
+      expect(ticket.owner.toBase58()).to.equal(trader.publicKey.toBase58());
+      expect(ticket.recipient.toBase58()).to.equal(traderWsol.toBase58());
+      expect(ticket.amount).to.equal(UNWRAP_AMOUNT);
+
+      // claim_unwrap pays the SPL backing once the burn is attested
+      const attestation = await attestedDecrypt([ticket.burnCovers], trader);
+      expect(isTrue(attestation.plaintexts[0])).to.be.true;
+      const ticketAccount = await mustFetch(ticketAddress);
+      const light = await lightArgs([ticketAccount]);
+      const claimIx = await swapProgram.methods
+        .claimUnwrap(
+          light.proof as any,
+          light.metas[0],
+          ticketAccount.data,
+          plaintextBytes(attestation.plaintexts[0])
+        )
+        .accounts({
+          feePayer: authority,
+          owner: trader.publicKey,
+          incoMint: wrappedMint,
+          wrapAuthority,
+          splBackingVault: backingVault,
+          recipient: traderWsol,
+        } as any)
+        .remainingAccounts(light.remainingAccounts)
+        .instruction();
+      const recipientBefore = await splAmount(traderWsol);
+      await send([...attestation.ed25519Instructions, claimIx], [trader]);
+
+      expect(await splAmount(traderWsol)).to.equal(recipientBefore + UNWRAP_AMOUNT);
+      expect(await fetchCompressed(ticketAddress), "ticket closed").to.be.null;
+    });
+  });
+
+  // ─── Stable pool: amp ramps, pausing and closing ─────────────────────
+
+  describe("ramp_amp / stop_ramp_amp", () => {
+    it("ramp_amp only applies to stable pools", async () => {
+      const end = Math.floor(Date.now() / 1000) + 2 * 86_400;
+      await expectError(poolIx(pool, "rampAmp", [bn(2 * STABLE_AMP), bn(end)]), "InvalidPoolKind");
+    });
+
+    it("ramp_amp rejects a ramp shorter than a day", async () => {
+      const end = Math.floor(Date.now() / 1000) + 3_600;
+      await expectError(poolIx(stablePool, "rampAmp", [bn(2 * STABLE_AMP), bn(end)]), "InvalidAmpRamp");
+    });
+
+    it("ramp_amp schedules the new amplification", async () => {
+      const end = Math.floor(Date.now() / 1000) + 2 * 86_400;
+      await send([await poolIx(stablePool, "rampAmp", [bn(2 * STABLE_AMP), bn(end)])]);
+      const { state } = await fetchPool(stablePool);
+      expect(state.ampFuture).to.equal(BigInt(2 * STABLE_AMP));
+      expect(state.ampRampEndTs).to.equal(BigInt(end));
+    });
+
+    it("stop_ramp_amp freezes the amplification at its current value", async () => {
+      await send([await poolIx(stablePool, "stopRampAmp", [])]);
+      const { state } = await fetchPool(stablePool);
+      expect(state.ampFuture).to.equal(state.ampInitial);
+      expect(state.ampRampEndTs).to.equal(state.ampRampStartTs);
+      expect(state.ampFuture >= BigInt(STABLE_AMP)).to.be.true;
+    });
+  });
+
+  describe("set_emergency_delay / set_guardian / pause_pool / unpause_pool", () => {
+    it("set_emergency_delay rejects a delay shorter than the unpause delay", async () => {
+      await expectError(poolIx(stablePool, "setEmergencyDelay", [UNPAUSE_DELAY_SECS - 1]), "InvalidEmergencyDelay");
+    });
+
+    it("set_emergency_delay stores the delay", async () => {
+      await send([await poolIx(stablePool, "setEmergencyDelay", [UNPAUSE_DELAY_SECS])]);
+      expect((await fetchPool(stablePool)).state.emergencyDelaySecs).to.equal(UNPAUSE_DELAY_SECS);
+    });
+
+    it("set_guardian records the guardian", async () => {
+      await send([await poolIx(stablePool, "setGuardian", [guardian.publicKey])]);
+      expect((await fetchPool(stablePool)).state.guardian.toBase58()).to.equal(guardian.publicKey.toBase58());
+    });
+
+    it("pause_pool rejects callers other than the authority and guardian", async () => {
+      await expectError(
+        poolIx(stablePool, "pausePool", [], { caller: trader.publicKey }),
+        "Unauthorized",
+        [trader]
+      );
+    });
+
+    it("pause_pool lets the guardian halt trading", async () => {
+      await send(
+        [await poolIx(stablePool, "pausePool", [], { caller: guardian.publicKey })],
+        [guardian]
+      );
+      const { state } = await fetchPool(stablePool);
+      expect(state.isPaused).to.be.true;
+      expect(state.pausedAtTs > 0n).to.be.true;
+
+      await expectError(
+        poolIx(stablePool, "pausePool", [], { caller: guardian.publicKey }),
+        "PoolPaused",
+        [guardian]
+      );
+      const params: SwapParams = {
+        ref: stablePool,
+        aToB: true,
+        amountIn: SWAP_AMOUNT_IN,
+        amountOut: 0n,
+        feeAmount: 0n,
+        userTokenA: traderB,
+        userTokenB: traderA,
+        payoutNonce: nextNonce(),
+      };
+      const placeholder = [Keypair.generate().publicKey, Keypair.generate().publicKey];
+      await expectError(swapIx(params, placeholder), "PoolPaused", [trader]);
+    });
+
+    it("unpause_pool waits out the unpause delay", async () => {
+      await expectError(poolIx(stablePool, "unpausePool", []), "UnpauseTooEarly");
+    });
+  });
+
+  describe("grant_withdrawal_access / emergency_withdraw", () => {
+    it("grant_withdrawal_access waits out the emergency delay", async () => {
+      const placeholder = Keypair.generate().publicKey;
+      await expectError(
+        poolIx(stablePool, "grantWithdrawalAccess", [], {
+          reserveAAllowance: placeholder,
+          reserveBAllowance: placeholder,
+          protocolFeeAAllowance: placeholder,
+          protocolFeeBAllowance: placeholder,
+        }),
+        "EmergencyDelayNotElapsed"
+      );
+    });
+
+    it("emergency_withdraw waits out the emergency delay", async () => {
+      const { account } = await fetchPool(stablePool);
+      const payoutNonce = nextNonce();
+      const light = await lightArgs([account], [payoutAddress(authority, payoutNonce)]);
+      const ix = swapProgram.methods
+        .emergencyWithdraw(
+          light.proof as any,
+          light.metas[0],
+          account.data,
+          await encryptAmount(0n),
+          await encryptAmount(0n),
+          INPUT_TYPE,
+          light.addressTreeInfo,
+          light.outputTreeIndex,
+          bn(payoutNonce)
+        )
+        .accounts({
+          feePayer: authority,
+          authority,
+          poolVaultA: stablePool.vaultA,
+          poolVaultB: stablePool.vaultB,
+          recipientTokenA: authorityB,
+          recipientTokenB: authorityA,
+          approvedAllowance: Keypair.generate().publicKey,
+        } as any)
+        .remainingAccounts(light.remainingAccounts)
+        .instruction();
+      await expectError(ix, "EmergencyDelayNotElapsed");
+    });
+  });
+
+  describe("prepare_close_pool / close_pool", () => {
+    it("prepare_close_pool records an emptiness check for the current state", async () => {
+      const build = (allowances: PublicKey[]) =>
+        poolIx(stablePool, "prepareClosePool", [], { allowance: allowances[0] });
+      await send([await build(await resolveAllowances(build, 1))]);
+
+      const { state } = await fetchPool(stablePool);
+      expect(state.closeCheck).to.not.equal(0n);
+      expect(state.closeCheckNonce).to.equal(state.stateNonce);
+    });
+
+    it("close_pool closes the pool, its index entry and both vaults", async () => {
+      const { account, state } = await fetchPool(stablePool);
+      const index = await mustFetch(stablePool.indexAddress);
+      const attestation = await attestedDecrypt([state.closeCheck], walletKeypair);
+      expect(isTrue(attestation.plaintexts[0])).to.be.true;
+
+      const light = await lightArgs([account, index]);
+      const ix = await swapProgram.methods
+        .closePool(
+          light.proof as any,
+          light.metas[0],
+          account.data,
+          light.metas[1],
+          index.data,
+          plaintextBytes(attestation.plaintexts[0])
+        )
+        .accounts({
+          feePayer: authority,
+          authority,
+          poolAuthority: stablePool.poolAuthority,
+          poolVaultA: stablePool.vaultA,
+          poolVaultB: stablePool.vaultB,
+        } as any)
+        .remainingAccounts(light.remainingAccounts)
+        .instruction();
+      await send([...attestation.ed25519Instructions, ix]);
+
+      expect(await fetchCompressed(stablePool.address), "pool closed").to.be.null;
+      expect(await fetchCompressed(stablePool.indexAddress), "index entry closed").to.be.null;
+      expect(await connection.getAccountInfo(stablePool.vaultA)).to.be.null;
+      expect(await connection.getAccountInfo(stablePool.vaultB)).to.be.null;
+    });
+  });
+});
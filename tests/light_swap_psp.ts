@@ -119,6 +119,8 @@ describe("light_swap_psp", function () {
   let poolAuthorityPda: PublicKey;
   let poolAuthorityBump: number;
   let permissionForPoolAuthority: PublicKey;
+  let poolVaultA: PublicKey;
+  let poolVaultB: PublicKey;
 
   const encryptAmount = async (amount: bigint) =>
     hexToBuffer(await encryptValue(amount));
@@ -140,6 +142,13 @@ describe("light_swap_psp", function () {
       swapProgram.programId
     );
     permissionForPoolAuthority = permissionPdaFromAccount(poolAuthorityPda);
+    [poolVaultA, poolVaultB] = [mintA, mintB].map(
+      (mint) =>
+        PublicKey.findProgramAddressSync(
+          [Buffer.from("pool_vault"), poolAuthorityPda.toBuffer(), mint.publicKey.toBuffer()],
+          swapProgram.programId
+        )[0]
+    );
 
     console.log("Mint A:", mintA.publicKey.toBase58());
     console.log("Mint B:", mintB.publicKey.toBase58());
@@ -286,17 +295,23 @@ describe("light_swap_psp", function () {
     const poolAddress = deriveAddressV2(poolAddressSeed, addressTree, swapProgram.programId);
     console.log("Pool address:", poolAddress.toBase58());
 
+    // initialize_pool also creates the pool's index entry
+    const feeBps = Buffer.alloc(2);
+    feeBps.writeUInt16LE(30);
+    const indexSeeds = [Buffer.from("pool_index"), mintA.publicKey.toBuffer(), mintB.publicKey.toBuffer(), feeBps];
+    const indexAddress = deriveAddressV2(deriveAddressSeedV2(indexSeeds), addressTree, swapProgram.programId);
+
     // Get validity proof from Light RPC for the new address
     // Note: use same pubkey for tree and queue (per Light Protocol example)
     let proofResult: any;
     try {
       proofResult = await lightRpc.getValidityProofV0(
         [], // No existing hashes (creating new)
-        [{
-          address: bn(poolAddress.toBytes()),
+        [poolAddress, indexAddress].map((address) => ({
+          address: bn(address.toBytes()),
           tree: addressTree,
           queue: addressTree, // Same as tree per example
-        }]
+        }))
       );
       console.log("Got validity proof from Light RPC, rootIndex:", proofResult.rootIndices?.[0]);
     } catch (e: any) {
@@ -365,11 +380,21 @@ describe("light_swap_psp", function () {
           outputStateTreeIndex,
           mintA.publicKey,
           mintB.publicKey,
-          30 // fee_bps (0.3%)
+          30, // fee_bps (0.3%)
+          { constantProduct: {} },
+          bn(0), // amp (stable pools only)
+          0, // weight_a (weighted pools only)
+          0, // weight_b
+          0 // curve_scale_bits
         )
         .accounts({
           feePayer: authority,
           authority: authority,
+          poolAuthority: poolAuthorityPda,
+          mintA: mintA.publicKey,
+          mintB: mintB.publicKey,
+          poolVaultA,
+          poolVaultB,
           incoLightningProgram: INCO_LIGHTNING_PROGRAM_ID,
         })
         .remainingAccounts(remainingAccounts)